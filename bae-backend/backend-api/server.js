@@ -4,6 +4,7 @@ import cors from 'cors';
 import { ethers } from 'ethers';
 import crypto from 'crypto';
 import dotenv from 'dotenv';
+import fs from 'fs';
 
 dotenv.config();
 
@@ -41,6 +42,23 @@ app.use(express.json());
 const RPC_URL = process.env.RPC_URL;
 const CONTRACT_ADDRESS = process.env.CONTRACT_ADDRESS;
 const ENCRYPTION_KEY = process.env.ENCRYPTION_KEY;
+const AAD_FORMAT_VERSION = 1;
+
+// Anillo de claves maestras del gateway (`gateway rotate-key`): la activa primero,
+// después las retiradas. Sin KEYRING_PATH se usa sólo ENCRYPTION_KEY.
+function loadMasterKeys() {
+  if (!process.env.KEYRING_PATH) {
+    return [ENCRYPTION_KEY];
+  }
+  const keyring = JSON.parse(fs.readFileSync(process.env.KEYRING_PATH, 'utf8'));
+  const retired = Object.entries(keyring.keys)
+    .filter(([version]) => Number(version) !== keyring.active)
+    .sort(([a], [b]) => Number(b) - Number(a))
+    .map(([, key]) => key);
+  return [keyring.keys[String(keyring.active)], ...retired];
+}
+
+const MASTER_KEYS = loadMasterKeys();
 
 // ABI del contrato
 const CONTRACT_ABI = [
@@ -98,11 +116,20 @@ const contract = new ethers.Contract(CONTRACT_ADDRESS, CONTRACT_ABI, provider);
 // ============================================
 // FUNCIÓN DE DESENCRIPTACIÓN
 // ============================================
-function decryptData(ciphertextHex, nonceHex, key) {
+function decryptData(ciphertextHex, nonceHex, key, aad) {
+  // Convertir hex a buffer
+  const ciphertext = Buffer.from(ciphertextHex.slice(2), 'hex');
+  const nonce = Buffer.from(nonceHex.slice(2), 'hex');
+
+  // Parsear JSON
+  return JSON.parse(decryptBytes(ciphertext, nonce, key, aad).toString('utf8'));
+}
+
+/**
+ * AES-256-GCM sobre buffers; devuelve el texto plano sin interpretar
+ */
+function decryptBytes(ciphertext, nonce, key, aad) {
   try {
-    // Convertir hex a buffer
-    const ciphertext = Buffer.from(ciphertextHex.slice(2), 'hex');
-    const nonce = Buffer.from(nonceHex.slice(2), 'hex');
     const keyBuffer = Buffer.from(key, 'hex');
 
     // Crear decipher
@@ -113,27 +140,195 @@ function decryptData(ciphertextHex, nonceHex, key) {
     const encrypted = ciphertext.slice(0, -16);
 
     decipher.setAuthTag(authTag);
+    if (aad) {
+      decipher.setAAD(aad);
+    }
 
     // Desencriptar
-    let decrypted = decipher.update(encrypted);
-    decrypted = Buffer.concat([decrypted, decipher.final()]);
-
-    // Parsear JSON
-    return JSON.parse(decrypted.toString('utf8'));
+    const decrypted = decipher.update(encrypted);
+    return Buffer.concat([decrypted, decipher.final()]);
   } catch (error) {
-    console.error('Decryption error:', error);
     throw new Error('Failed to decrypt data: ' + error.message);
   }
 }
 
+/**
+ * Clave AES de un dispositivo: HKDF-SHA256 de la clave maestra
+ * (misma derivación que el KeyRegistry del gateway)
+ */
+function deviceKey(masterKey, deviceId) {
+  const derived = crypto.hkdfSync(
+    'sha256',
+    Buffer.from(masterKey, 'hex'),
+    Buffer.from('bae-device-key-v1'),
+    Buffer.from(deviceId, 'utf8'),
+    32
+  );
+  return Buffer.from(derived).toString('hex');
+}
+
+/**
+ * Datos asociados (AAD) de una lectura, igual que `CryptoHandler::reading_aad`:
+ * versión de formato, device_id con prefijo de longitud y timestamp (big endian)
+ */
+function readingAad(deviceId, timestamp) {
+  const device = Buffer.from(deviceId, 'utf8');
+  const aad = Buffer.alloc(1 + 4 + device.length + 8);
+  aad.writeUInt8(AAD_FORMAT_VERSION, 0);
+  aad.writeUInt32BE(device.length, 1);
+  device.copy(aad, 5);
+  aad.writeBigUInt64BE(BigInt(timestamp), 5 + device.length);
+  return aad;
+}
+
+// Envelope v1 del gateway: [0xE1][codificación: 0 JSON, 1 binario, 2 medidas][key_id 8 bytes][AES-GCM]
+const ENVELOPE_V1 = 0xe1;
+const ENVELOPE_HEADER_LEN = 10;
+const BODY_FORMATS = ['json', 'binary', 'metrics'];
+
+function parseEnvelope(ciphertextHex) {
+  const bytes = Buffer.from(ciphertextHex.slice(2), 'hex');
+  if (bytes.length < ENVELOPE_HEADER_LEN || bytes[0] !== ENVELOPE_V1 || bytes[1] >= BODY_FORMATS.length) {
+    return null;
+  }
+  return {
+    header: bytes.subarray(0, ENVELOPE_HEADER_LEN),
+    body: BODY_FORMATS[bytes[1]],
+    keyId: bytes.subarray(2, ENVELOPE_HEADER_LEN).toString('hex'),
+    ciphertext: bytes.subarray(ENVELOPE_HEADER_LEN),
+  };
+}
+
+// Huella de una clave: primeros 8 bytes de SHA-256 (igual que `CryptoHandler::key_id_for`)
+function keyId(keyHex) {
+  return crypto.createHash('sha256').update(Buffer.from(keyHex, 'hex')).digest().subarray(0, 8).toString('hex');
+}
+
+/**
+ * Cuerpo de medidas (big endian): timestamp u64, número de medidas u8 y, por medida,
+ * nombre y unidad (u8 longitud + UTF-8), tipo u8 y valor (0 f64, 1 bool u8, 2 texto).
+ * Devuelve registros SenML `{ n, u, v | vb | vs }`, como el JSON v2.
+ */
+function decodeMetrics(bytes) {
+  let offset = 0;
+  const take = (len) => {
+    if (offset + len > bytes.length) throw new Error('Truncated metrics body');
+    const slice = bytes.subarray(offset, offset + len);
+    offset += len;
+    return slice;
+  };
+  const string = () => take(take(1)[0]).toString('utf8');
+
+  const timestamp = Number(take(8).readBigUInt64BE(0));
+  const count = take(1)[0];
+  const measurements = [];
+  for (let i = 0; i < count; i++) {
+    const measurement = { n: string() };
+    const unit = string();
+    if (unit) measurement.u = unit;
+    const kind = take(1)[0];
+    if (kind === 0) measurement.v = take(8).readDoubleBE(0);
+    else if (kind === 1) measurement.vb = take(1)[0] !== 0;
+    else if (kind === 2) measurement.vs = string();
+    else throw new Error(`Unknown value type ${kind} in metrics body`);
+    measurements.push(measurement);
+  }
+  return { timestamp, measurements };
+}
+
+/**
+ * Desencripta un envelope con la clave que indica su cabecera. El cuerpo binario
+ * son 16 bytes big endian: temperatura f32, humedad f32, timestamp u64.
+ */
+function openEnvelope(reading, envelope, aad) {
+  const candidates = MASTER_KEYS.flatMap((masterKey) => [deviceKey(masterKey, reading.deviceId), masterKey]);
+  const key = candidates.find((candidate) => keyId(candidate) === envelope.keyId);
+  if (!key) {
+    throw new Error(`Unknown key ${envelope.keyId}`);
+  }
+
+  const nonce = Buffer.from(reading.nonce.slice(2), 'hex');
+  const plaintext = decryptBytes(envelope.ciphertext, nonce, key, Buffer.concat([aad, envelope.header]));
+
+  if (envelope.body === 'json') {
+    return JSON.parse(plaintext.toString('utf8'));
+  }
+  if (envelope.body === 'metrics') {
+    return { schema_version: 2, device_id: reading.deviceId, ...decodeMetrics(plaintext) };
+  }
+  return {
+    device_id: reading.deviceId,
+    temperature: plaintext.readFloatBE(0),
+    humidity: plaintext.readFloatBE(4),
+    timestamp: Number(plaintext.readBigUInt64BE(8)),
+  };
+}
+
+/**
+ * Desencripta una lectura con la clave de su dispositivo y su AAD, probando
+ * cada versión del anillo. Las lecturas antiguas no tienen AAD, y las
+ * anteriores a las claves por dispositivo usan la clave maestra.
+ */
+function decryptReading(reading) {
+  const aad = readingAad(reading.deviceId, reading.timestamp);
+
+  const envelope = parseEnvelope(reading.ciphertext);
+  if (envelope) {
+    try {
+      return openEnvelope(reading, envelope, aad);
+    } catch (error) {
+      // Puede ser una lectura antigua cuyo primer byte coincide con la versión
+    }
+  }
+
+  const attempts = [];
+  for (const masterKey of MASTER_KEYS) {
+    const key = deviceKey(masterKey, reading.deviceId);
+    attempts.push([key, aad], [key, null]);
+  }
+  for (const masterKey of MASTER_KEYS) {
+    attempts.push([masterKey, null]);
+  }
+
+  let lastError;
+  for (const [attemptKey, aad] of attempts) {
+    try {
+      return decryptData(reading.ciphertext, reading.nonce, attemptKey, aad);
+    } catch (error) {
+      lastError = error;
+    }
+  }
+  console.error('Decryption error:', lastError);
+  throw lastError;
+}
+
 // ============================================
 // HELPER FUNCTIONS
 // ============================================
+
+// Medidas SenML de una lectura descifrada: v2 las trae; v1 y el cuerpo binario
+// sólo tienen temperatura y humedad
+function measurementsOf(decryptedData) {
+  if (Array.isArray(decryptedData.measurements)) {
+    return decryptedData.measurements;
+  }
+  return [
+    { n: 'temperature', u: 'Cel', v: decryptedData.temperature },
+    { n: 'humidity', u: '%RH', v: decryptedData.humidity },
+  ];
+}
+
+function metricValue(decryptedData, name) {
+  const measurement = measurementsOf(decryptedData).find((m) => m.n === name);
+  return typeof measurement?.v === 'number' ? measurement.v : undefined;
+}
+
 function formatReading(reading, decryptedData) {
   return {
     deviceId: reading.deviceId,
-    temperature: decryptedData.temperature,
-    humidity: decryptedData.humidity,
+    temperature: metricValue(decryptedData, 'temperature'),
+    humidity: metricValue(decryptedData, 'humidity'),
+    measurements: measurementsOf(decryptedData),
     timestamp: decryptedData.timestamp,
     timestampDate: new Date(decryptedData.timestamp * 1000).toISOString(),
     blockNumber: Number(reading.blockNumber),
@@ -196,11 +391,7 @@ app.get('/api/readings/latest/decrypt', async (req, res) => {
     });
 
     // 2. Desencriptar
-    const decryptedData = decryptData(
-      reading.ciphertext,
-      reading.nonce,
-      ENCRYPTION_KEY
-    );
+    const decryptedData = decryptReading(reading);
 
     console.log('🔓 Data decrypted successfully');
 
@@ -243,11 +434,7 @@ app.get('/api/readings/:index/decrypt', async (req, res) => {
     const reading = await contract.getReading(readingIndex);
 
     // 3. Desencriptar
-    const decryptedData = decryptData(
-      reading.ciphertext,
-      reading.nonce,
-      ENCRYPTION_KEY
-    );
+    const decryptedData = decryptReading(reading);
 
     console.log(`🔓 Reading ${readingIndex} decrypted successfully`);
 
@@ -302,11 +489,7 @@ app.get('/api/readings/history', async (req, res) => {
     for (let i = endIndex - 1; i >= startIndex; i--) {
       try {
         const reading = await contract.getReading(i);
-        const decryptedData = decryptData(
-          reading.ciphertext,
-          reading.nonce,
-          ENCRYPTION_KEY
-        );
+        const decryptedData = decryptReading(reading);
         readings.push(formatReading(reading, decryptedData));
       } catch (error) {
         console.error(`⚠️ Failed to decrypt reading ${i}:`, error.message);
@@ -381,17 +564,17 @@ app.get('/api/readings/stats', async (req, res) => {
     for (let i = startIndex; i < total; i++) {
       try {
         const reading = await contract.getReading(i);
-        const decryptedData = decryptData(
-          reading.ciphertext,
-          reading.nonce,
-          ENCRYPTION_KEY
-        );
+        const decryptedData = decryptReading(reading);
 
-        temperatures.push(decryptedData.temperature);
-        humidities.push(decryptedData.humidity);
+        // Sólo cuentan las lecturas que traen temperatura/humedad
+        const temperature = metricValue(decryptedData, 'temperature');
+        const humidity = metricValue(decryptedData, 'humidity');
+        if (humidity !== undefined) humidities.push(humidity);
+        if (temperature === undefined) continue;
+        temperatures.push(temperature);
 
-        if (decryptedData.temperature > 29) hotAlerts++;
-        if (decryptedData.temperature < 17) coldAlerts++;
+        if (temperature > 29) hotAlerts++;
+        if (temperature < 17) coldAlerts++;
       } catch (error) {
         console.error(`⚠️ Failed to process reading ${i}:`, error.message);
       }
@@ -0,0 +1,100 @@
+mod routes;
+
+use anyhow::{Result, anyhow};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+use gateway::index_store::IndexStore;
+use gateway::key_registry::KeyRegistry;
+use gateway::keyring::Keyring;
+use gateway::reader::RegistryReader;
+use gateway::secrets::SecretStore;
+
+use routes::AppState;
+
+/// Orígenes permitidos por defecto (los mismos que la API Node)
+const DEFAULT_CORS_ORIGINS: [&str; 2] = [
+    "http://localhost:3000",
+    "https://bae-frontend-plum.vercel.app",
+];
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("bae_api=info".parse().unwrap())
+        )
+        .init();
+
+    let rpc_url = std::env::var("RPC_URL").map_err(|_| anyhow!("RPC_URL is not set"))?;
+    let contract_address = std::env::var("CONTRACT_ADDRESS").map_err(|_| anyhow!("CONTRACT_ADDRESS is not set"))?;
+    let port: u16 = std::env::var("PORT")
+        .unwrap_or_else(|_| "3001".to_string())
+        .parse()
+        .map_err(|_| anyhow!("Invalid PORT"))?;
+
+    // `CORS_ORIGINS` separado por comas sustituye a la lista por defecto
+    let allowed_origins: Vec<String> = match std::env::var("CORS_ORIGINS") {
+        Ok(origins) => origins.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect(),
+        Err(_) => DEFAULT_CORS_ORIGINS.iter().map(|o| o.to_string()).collect(),
+    };
+
+    let state = Arc::new(AppState {
+        reader: RegistryReader::new(&rpc_url, &contract_address)?,
+        keys: load_keys()?,
+        index: open_index()?.map(Arc::new),
+        contract_address: contract_address.clone(),
+        allowed_origins,
+        started: Instant::now(),
+    });
+
+    let app = routes::router(state.clone())?;
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| anyhow!("Failed to bind port {}: {}", port, e))?;
+
+    info!("🚀 Backend API Server Started");
+    info!("📡 Server: http://localhost:{}", port);
+    info!("🔗 RPC: {}", rpc_url);
+    info!("📝 Contract: {}", contract_address);
+    info!("🌐 CORS Origins: {}", state.allowed_origins.join(", "));
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+            info!("👋 Shutdown signal received, shutting down gracefully...");
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Índice de `bae-indexer` si `INDEX_DB` está configurado
+fn open_index() -> Result<Option<IndexStore>> {
+    match std::env::var("INDEX_DB") {
+        Ok(path) => {
+            info!("🗄️  Serving readings from index {}", path);
+            Ok(Some(IndexStore::open(&path)?))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Claves para descifrar: las mismas fuentes que usa el gateway
+/// (`KEYRING_PATH` o `ENCRYPTION_KEY`, y `KEYSTORE_PATH` para claves propias)
+fn load_keys() -> Result<KeyRegistry> {
+    let secrets = SecretStore::from_env()?;
+    let keyring = Keyring::load_configured(&secrets)?;
+    info!("🔐 Keyring loaded (active version {})", keyring.active);
+
+    let mut keys = KeyRegistry::new().with_keyring(&keyring)?;
+    if let Ok(path) = std::env::var("KEYSTORE_PATH") {
+        keys = keys.with_keystore(path)?;
+    }
+
+    Ok(keys)
+}
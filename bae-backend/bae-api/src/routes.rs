@@ -0,0 +1,616 @@
+use anyhow::{Result, anyhow};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderValue, Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{error, info, warn};
+
+use bae_core::metric::Measurement;
+use bae_core::reading::SensorReading;
+use gateway::index_store::{IndexStore, IndexedReading};
+use gateway::key_registry::KeyRegistry;
+use gateway::reader::{OnChainReading, RegistryReader};
+
+/// Máximo de lecturas por petición (sin índice, cada una es una llamada al RPC)
+const MAX_LIMIT: i64 = 100;
+const DEFAULT_HISTORY_LIMIT: i64 = 50;
+const DEFAULT_STATS_LIMIT: i64 = 20;
+/// Umbrales de alerta de temperatura (°C)
+const HOT_THRESHOLD: f64 = 29.0;
+const COLD_THRESHOLD: f64 = 17.0;
+
+pub struct AppState {
+    pub reader: RegistryReader,
+    pub keys: KeyRegistry,
+    /// Índice local de `bae-indexer` (`INDEX_DB`); si está, las lecturas salen de ahí y no del RPC
+    pub index: Option<Arc<IndexStore>>,
+    pub contract_address: String,
+    pub allowed_origins: Vec<String>,
+    pub started: Instant,
+}
+
+type SharedState = Arc<AppState>;
+
+/// Mismas rutas y formas JSON que `backend-api/server.js`
+pub fn router(state: SharedState) -> Result<Router> {
+    let origins = state.allowed_origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin).map_err(|_| anyhow!("Invalid CORS origin '{}'", origin)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+
+    Ok(Router::new()
+        .route("/health", get(health))
+        .route("/api/test", get(test))
+        .route("/api/readings/latest/decrypt", get(latest))
+        .route("/api/readings/{index}/decrypt", get(by_index))
+        .route("/api/readings/history", get(history))
+        .route("/api/readings/count", get(count))
+        .route("/api/readings/stats", get(stats))
+        .fallback(not_found)
+        .layer(cors)
+        .with_state(state))
+}
+
+/// Error con el cuerpo `{ "error": ..., "message": ... }` de la API Node
+struct ApiError {
+    status: StatusCode,
+    error: &'static str,
+    message: Option<String>,
+}
+
+impl ApiError {
+    fn bad_request(error: &'static str) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, error, message: None }
+    }
+
+    fn internal(error: &'static str, e: anyhow::Error) -> Self {
+        Self { status: StatusCode::INTERNAL_SERVER_ERROR, error, message: Some(e.to_string()) }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = match self.message {
+            Some(message) => json!({ "error": self.error, "message": message }),
+            None => json!({ "error": self.error }),
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Lectura descifrada tal como la devuelve la API. `temperature` y `humidity`
+/// se mantienen en el nivel superior para el frontend; todas las medidas van en `measurements`.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ReadingResponse {
+    device_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    humidity: Option<f64>,
+    measurements: Vec<Measurement>,
+    timestamp: u64,
+    timestamp_date: String,
+    block_number: u64,
+}
+
+impl ReadingResponse {
+    fn new(on_chain: &OnChainReading, data: SensorReading) -> Self {
+        Self::from_data(on_chain.device_id.clone(), on_chain.block_number, data)
+    }
+
+    fn from_indexed(indexed: IndexedReading) -> Result<Self> {
+        let data = indexed.data.map_err(|e| anyhow!("Reading {} could not be decrypted: {}", indexed.index, e))?;
+        Ok(Self::from_data(indexed.device_id, indexed.block_number, data))
+    }
+
+    fn from_data(device_id: String, block_number: u64, data: SensorReading) -> Self {
+        Self {
+            device_id,
+            temperature: data.temperature(),
+            humidity: data.humidity(),
+            timestamp: data.timestamp,
+            timestamp_date: iso_date(data.timestamp),
+            measurements: data.measurements,
+            block_number,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct StatsResponse {
+    total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    analyzed: Option<usize>,
+    avg_temperature: f64,
+    avg_humidity: f64,
+    min_temperature: f64,
+    max_temperature: f64,
+    hot_alerts: usize,
+    cold_alerts: usize,
+}
+
+impl StatsResponse {
+    fn empty() -> Self {
+        Self {
+            total: 0,
+            analyzed: None,
+            avg_temperature: 0.0,
+            avg_humidity: 0.0,
+            min_temperature: 0.0,
+            max_temperature: 0.0,
+            hot_alerts: 0,
+            cold_alerts: 0,
+        }
+    }
+
+    fn from_readings(total: u64, readings: &[ReadingResponse]) -> Self {
+        if readings.is_empty() {
+            return Self { total, analyzed: Some(0), ..Self::empty() };
+        }
+
+        // Las estadísticas de clima sólo cuentan las lecturas que traen esa métrica
+        let temperatures: Vec<f64> = readings.iter().filter_map(|r| r.temperature).collect();
+        let humidities: Vec<f64> = readings.iter().filter_map(|r| r.humidity).collect();
+
+        Self {
+            total,
+            analyzed: Some(temperatures.len()),
+            avg_temperature: round1(average(&temperatures)),
+            avg_humidity: round1(average(&humidities)),
+            min_temperature: round1(temperatures.iter().copied().reduce(f64::min).unwrap_or(0.0)),
+            max_temperature: round1(temperatures.iter().copied().reduce(f64::max).unwrap_or(0.0)),
+            hot_alerts: temperatures.iter().filter(|t| **t > HOT_THRESHOLD).count(),
+            cold_alerts: temperatures.iter().filter(|t| **t < COLD_THRESHOLD).count(),
+        }
+    }
+}
+
+async fn health(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "ok",
+        "timestamp": now_iso(),
+        "uptime": state.started.elapsed().as_secs_f64(),
+    }))
+}
+
+async fn test(State(state): State<SharedState>) -> Response {
+    match state.reader.reading_count().await {
+        Ok(total) => Json(json!({
+            "status": "ok",
+            "message": "API is working correctly",
+            "blockchain": {
+                "connected": true,
+                "contractAddress": state.contract_address,
+                "totalReadings": total,
+            },
+            "cors": { "allowedOrigins": state.allowed_origins },
+            "timestamp": now_iso(),
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "error",
+                "message": "Blockchain connection failed",
+                "error": e.to_string(),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+async fn latest(State(state): State<SharedState>) -> Result<Json<ReadingResponse>, ApiError> {
+    info!("📥 Fetching latest reading...");
+    fetch_latest(&state).await.map(Json).map_err(|e| {
+        error!("❌ Error in /api/readings/latest/decrypt: {}", e);
+        ApiError::internal("Failed to fetch and decrypt latest reading", e)
+    })
+}
+
+async fn by_index(State(state): State<SharedState>, Path(index): Path<String>) -> Result<Json<ReadingResponse>, ApiError> {
+    let index = match parse_int(&index) {
+        Some(index) if index >= 0 => index as u64,
+        _ => return Err(ApiError::bad_request("Invalid index parameter")),
+    };
+    info!("📥 Fetching reading at index {}...", index);
+
+    let total = total_readings(&state).await.map_err(|e| {
+        error!("❌ Error in /api/readings/{}/decrypt: {}", index, e);
+        ApiError::internal("Failed to fetch and decrypt reading", e)
+    })?;
+    if index >= total {
+        return Err(ApiError {
+            status: StatusCode::NOT_FOUND,
+            error: "Reading not found",
+            message: Some(format!("Index {} is out of range. Total readings: {}", index, total)),
+        });
+    }
+
+    fetch_reading(&state, index).await.map(Json).map_err(|e| {
+        error!("❌ Error in /api/readings/{}/decrypt: {}", index, e);
+        ApiError::internal("Failed to fetch and decrypt reading", e)
+    })
+}
+
+async fn history(State(state): State<SharedState>, Query(params): Query<HashMap<String, String>>) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = int_param(&params, "limit", DEFAULT_HISTORY_LIMIT);
+    let offset = int_param(&params, "offset", 0);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request("Limit must be between 1 and 100"));
+    }
+    info!("📥 Fetching history: limit={}, offset={}", limit, offset);
+
+    let total = total_readings(&state).await.map_err(|e| {
+        error!("❌ Error in /api/readings/history: {}", e);
+        ApiError::internal("Failed to fetch history", e)
+    })?;
+    if total == 0 {
+        return Ok(Json(json!({ "readings": [], "total": 0, "limit": limit, "offset": offset })));
+    }
+
+    // Más recientes primero
+    let mut readings = fetch_range(&state, history_range(total, limit, offset)).await.map_err(|e| {
+        error!("❌ Error in /api/readings/history: {}", e);
+        ApiError::internal("Failed to fetch history", e)
+    })?;
+    readings.reverse();
+    info!("✅ Successfully fetched {} readings", readings.len());
+
+    Ok(Json(json!({
+        "returned": readings.len(),
+        "readings": readings,
+        "total": total,
+        "limit": limit,
+        "offset": offset,
+    })))
+}
+
+async fn count(State(state): State<SharedState>) -> Result<Json<serde_json::Value>, ApiError> {
+    let total = total_readings(&state).await.map_err(|e| {
+        error!("❌ Error in /api/readings/count: {}", e);
+        ApiError::internal("Failed to get reading count", e)
+    })?;
+
+    Ok(Json(json!({ "total": total })))
+}
+
+async fn stats(State(state): State<SharedState>, Query(params): Query<HashMap<String, String>>) -> Result<Json<StatsResponse>, ApiError> {
+    let limit = int_param(&params, "limit", DEFAULT_STATS_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request("Limit must be between 1 and 100"));
+    }
+    info!("📊 Calculating stats for last {} readings...", limit);
+
+    let stats_error = |e: anyhow::Error| {
+        error!("❌ Error in /api/readings/stats: {}", e);
+        ApiError::internal("Failed to calculate stats", e)
+    };
+    let total = total_readings(&state).await.map_err(stats_error)?;
+    if total == 0 {
+        return Ok(Json(StatsResponse::empty()));
+    }
+
+    let readings = fetch_range(&state, history_range(total, limit, 0)).await.map_err(stats_error)?;
+
+    Ok(Json(StatsResponse::from_readings(total, &readings)))
+}
+
+async fn not_found() -> ApiError {
+    ApiError { status: StatusCode::NOT_FOUND, error: "Endpoint not found", message: None }
+}
+
+/// Consulta al índice local. rusqlite bloquea, así que corre en un hilo de
+/// `spawn_blocking` y no en los workers de tokio que atienden las peticiones.
+async fn query_index<T, F>(index: &Arc<IndexStore>, query: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&IndexStore) -> Result<T> + Send + 'static,
+{
+    let index = index.clone();
+    tokio::task::spawn_blocking(move || query(&index)).await?
+}
+
+/// Total de lecturas: del índice local si está configurado, si no del contrato
+async fn total_readings(state: &AppState) -> Result<u64> {
+    match &state.index {
+        Some(index) => query_index(index, |index| index.count()).await,
+        None => state.reader.reading_count().await,
+    }
+}
+
+async fn fetch_latest(state: &AppState) -> Result<ReadingResponse> {
+    match &state.index {
+        Some(index) => {
+            let indexed = query_index(index, |index| index.latest()).await?;
+            ReadingResponse::from_indexed(indexed.ok_or_else(|| anyhow!("No readings indexed yet"))?)
+        }
+        None => {
+            let reading = state.reader.latest_reading().await?;
+            let data = reading.decrypt(&state.keys)?;
+            Ok(ReadingResponse::new(&reading, data))
+        }
+    }
+}
+
+async fn fetch_reading(state: &AppState, index: u64) -> Result<ReadingResponse> {
+    match &state.index {
+        Some(store) => {
+            let indexed = query_index(store, move |store| store.get(index)).await?;
+            ReadingResponse::from_indexed(indexed.ok_or_else(|| anyhow!("Reading {} is not indexed", index))?)
+        }
+        None => {
+            let reading = state.reader.reading(index).await?;
+            let data = reading.decrypt(&state.keys)?;
+            Ok(ReadingResponse::new(&reading, data))
+        }
+    }
+}
+
+/// Lecturas descifradas con índice en `range`, en orden ascendente.
+/// Las que no se pueden leer o descifrar se saltan.
+async fn fetch_range(state: &AppState, range: Range<u64>) -> Result<Vec<ReadingResponse>> {
+    let mut readings = Vec::new();
+
+    if let Some(index) = &state.index {
+        for indexed in query_index(index, move |index| index.range(range)).await? {
+            match ReadingResponse::from_indexed(indexed) {
+                Ok(reading) => readings.push(reading),
+                Err(e) => warn!("⚠️ {}", e),
+            }
+        }
+        return Ok(readings);
+    }
+
+    for index in range {
+        match fetch_reading(state, index).await {
+            Ok(reading) => readings.push(reading),
+            Err(e) => warn!("⚠️ Failed to decrypt reading {}: {}", index, e),
+        }
+    }
+    Ok(readings)
+}
+
+/// Índices de la página: las `limit` lecturas anteriores a las `offset` más recientes
+fn history_range(total: u64, limit: i64, offset: i64) -> Range<u64> {
+    let total = total as i64;
+    let end = (total - offset).clamp(0, total);
+    let start = (end - limit).max(0);
+    start as u64..end as u64
+}
+
+/// Parámetro entero de la query con la semántica de `parseInt(x) || default`:
+/// ausente, no numérico o cero usan el valor por defecto
+fn int_param(params: &HashMap<String, String>, name: &str, default: i64) -> i64 {
+    params
+        .get(name)
+        .and_then(|value| parse_int(value))
+        .filter(|value| *value != 0)
+        .unwrap_or(default)
+}
+
+/// Prefijo entero de la cadena, como `parseInt(x, 10)` (`"12abc"` → 12)
+fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim_start();
+    let digits_start = usize::from(value.starts_with(['-', '+']));
+    let digits_end = value[digits_start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(value.len(), |end| digits_start + end);
+
+    value[..digits_end].parse().ok()
+}
+
+/// Media aritmética (0 sin valores)
+fn average(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Redondeo a un decimal, como `parseFloat(x.toFixed(1))`
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Fecha ISO 8601 con milisegundos en UTC, como `Date.toISOString()`
+fn iso_date(timestamp: u64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn now_iso() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use gateway::keyring::Keyring;
+    use tower::ServiceExt;
+
+    fn reading(temperature: f64, humidity: f64) -> SensorReading {
+        SensorReading::climate("ESP32-001", temperature, humidity, 1_700_000_000)
+    }
+
+    fn response(temperature: f64, humidity: f64) -> ReadingResponse {
+        let on_chain = OnChainReading {
+            index: 0,
+            device_id: "ESP32-001".to_string(),
+            ciphertext: Vec::new(),
+            nonce: Vec::new(),
+            signature: Vec::new(),
+            timestamp: 1_700_000_000,
+            block_number: 1,
+        };
+        ReadingResponse::new(&on_chain, reading(temperature, humidity))
+    }
+
+    /// Router con un RPC inalcanzable: sirve para las rutas que no tocan la cadena
+    /// o que salen del índice local
+    fn app(index: Option<IndexStore>) -> Router {
+        let keyring = Keyring::from_master_key(&"ab".repeat(32)).unwrap();
+        let state = Arc::new(AppState {
+            reader: RegistryReader::new("http://127.0.0.1:1", "0xfD0b399898efC0186E32eb81B630d7Cf7Bb6f217").unwrap(),
+            keys: KeyRegistry::new().with_keyring(&keyring).unwrap(),
+            index: index.map(Arc::new),
+            contract_address: "0xfD0b399898efC0186E32eb81B630d7Cf7Bb6f217".to_string(),
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            started: Instant::now(),
+        });
+        router(state).unwrap()
+    }
+
+    async fn get_json(uri: &str) -> (StatusCode, serde_json::Value) {
+        get_json_from(app(None), uri).await
+    }
+
+    async fn get_json_from(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_routes_without_chain() {
+        let (status, body) = get_json("/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+
+        let (status, body) = get_json("/api/readings/abc/decrypt").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({ "error": "Invalid index parameter" }));
+
+        let (status, body) = get_json("/api/readings/history?limit=500").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({ "error": "Limit must be between 1 and 100" }));
+
+        let (status, body) = get_json("/api/nope").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, json!({ "error": "Endpoint not found" }));
+
+        let (status, body) = get_json("/api/readings/count").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], "Failed to get reading count");
+    }
+
+    #[tokio::test]
+    async fn test_readings_from_index() {
+        let store = IndexStore::open_in_memory().unwrap();
+        let readings: Vec<IndexedReading> = (0..3u64)
+            .map(|index| IndexedReading {
+                index,
+                device_id: "ESP32-001".to_string(),
+                timestamp: 1_700_000_000 + index,
+                block_number: 10 + index,
+                block_hash: format!("0xb{}", index),
+                tx_hash: format!("0xt{}", index),
+                data: if index == 1 {
+                    Err("bad tag".to_string())
+                } else {
+                    Ok(SensorReading { timestamp: 1_700_000_000 + index, ..reading(20.0 + index as f64, 50.0) })
+                },
+            })
+            .collect();
+        store.commit(12, "0xb2", &readings).unwrap();
+        let app = app(Some(store));
+
+        let (status, body) = get_json_from(app.clone(), "/api/readings/history?limit=10").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["total"].as_u64(), body["returned"].as_u64()), (Some(3), Some(2)));
+        assert_eq!(body["readings"][0]["blockNumber"], 12);
+        assert_eq!(body["readings"][1]["temperature"], 20.0);
+
+        let (_, body) = get_json_from(app.clone(), "/api/readings/latest/decrypt").await;
+        assert_eq!(body["timestamp"], 1_700_000_002u64);
+
+        let (status, body) = get_json_from(app.clone(), "/api/readings/1/decrypt").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], "Failed to fetch and decrypt reading");
+
+        let (_, body) = get_json_from(app, "/api/readings/stats").await;
+        assert_eq!((body["analyzed"].as_u64(), body["maxTemperature"].as_f64()), (Some(2), Some(22.0)));
+    }
+
+    #[test]
+    fn test_history_range() {
+        assert_eq!(history_range(120, 50, 0), 70..120);
+        assert_eq!(history_range(120, 50, 100), 0..20);
+        assert_eq!(history_range(120, 50, 200), 0..0);
+        assert_eq!(history_range(10, 50, -5), 0..10);
+    }
+
+    #[test]
+    fn test_query_params_follow_parse_int() {
+        let params = HashMap::from([
+            ("limit".to_string(), "12abc".to_string()),
+            ("offset".to_string(), "0".to_string()),
+            ("bad".to_string(), "x".to_string()),
+        ]);
+        assert_eq!(int_param(&params, "limit", 50), 12);
+        assert_eq!(int_param(&params, "offset", 7), 7);
+        assert_eq!(int_param(&params, "bad", 50), 50);
+        assert_eq!(int_param(&params, "missing", 20), 20);
+        assert_eq!(parse_int("-3"), Some(-3));
+    }
+
+    #[test]
+    fn test_stats_and_reading_shape() {
+        let co2 = SensorReading::new("AIR-042", 1_700_000_000, vec![Measurement::number("co2", "ppm", 612.0)]);
+        let co2 = ReadingResponse::from_data("AIR-042".to_string(), 1, co2);
+        let stats = StatsResponse::from_readings(40, &[response(30.04, 50.0), response(16.0, 61.0), co2, response(22.0, 55.5)]);
+        // La lectura de co2 no cuenta: no trae temperatura
+        assert_eq!(stats.analyzed, Some(3));
+        assert_eq!(stats.avg_temperature, 22.7);
+        assert_eq!(stats.avg_humidity, 55.5);
+        assert_eq!(stats.min_temperature, 16.0);
+        assert_eq!(stats.max_temperature, 30.0);
+        assert_eq!((stats.hot_alerts, stats.cold_alerts), (1, 1));
+        assert!(serde_json::to_value(StatsResponse::empty()).unwrap().get("analyzed").is_none());
+
+        let on_chain = OnChainReading {
+            index: 3,
+            device_id: "ESP32-001".to_string(),
+            ciphertext: Vec::new(),
+            nonce: Vec::new(),
+            signature: Vec::new(),
+            timestamp: 1_700_000_000,
+            block_number: 42,
+        };
+        assert_eq!(
+            serde_json::to_value(ReadingResponse::new(&on_chain, reading(23.5, 60.0))).unwrap(),
+            json!({
+                "deviceId": "ESP32-001",
+                "temperature": 23.5,
+                "humidity": 60.0,
+                "measurements": [
+                    { "n": "temperature", "u": "Cel", "v": 23.5 },
+                    { "n": "humidity", "u": "%RH", "v": 60.0 },
+                ],
+                "timestamp": 1_700_000_000u64,
+                "timestampDate": "2023-11-14T22:13:20.000Z",
+                "blockNumber": 42,
+            })
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use bae_core::metric::Measurement;
+use gateway::key_registry::KeyRegistry;
+use gateway::keyring::Keyring;
+use gateway::reader::{OnChainReading, RegistryReader};
+use gateway::secrets::SecretStore;
+
+#[derive(Parser)]
+#[command(name = "bae-cli", version, about = "Lee y descifra las lecturas guardadas en BaeSensorRegistry")]
+struct Cli {
+    /// Endpoint JSON-RPC
+    #[arg(long, env = "RPC_URL")]
+    rpc_url: String,
+    /// Dirección del contrato
+    #[arg(long, env = "CONTRACT_ADDRESS")]
+    contract: String,
+    /// Formato de salida
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    format: OutputFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Número de lecturas guardadas (`getReadingCount`)
+    Count,
+    /// Última lectura (`getLatestReading`)
+    Latest,
+    /// Una lectura por índice (`getReading`)
+    Get { index: u64 },
+    /// Lista lecturas (de la más reciente hacia atrás) con filtros
+    List(ListArgs),
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Primer índice a considerar (incluido)
+    #[arg(long, default_value_t = 0)]
+    from_index: u64,
+    /// Último índice a considerar (incluido; por defecto el último)
+    #[arg(long)]
+    to_index: Option<u64>,
+    /// Sólo lecturas minadas en este bloque o después
+    #[arg(long)]
+    from_block: Option<u64>,
+    /// Sólo lecturas minadas en este bloque o antes
+    #[arg(long)]
+    to_block: Option<u64>,
+    /// Sólo lecturas de este dispositivo
+    #[arg(long)]
+    device: Option<String>,
+    /// Máximo de lecturas a mostrar
+    #[arg(long, default_value_t = 50)]
+    limit: usize,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Lectura descifrada (o el error al descifrarla), con la forma de la API HTTP
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Row {
+    index: u64,
+    device_id: String,
+    timestamp: u64,
+    temperature: Option<f64>,
+    humidity: Option<f64>,
+    measurements: Vec<Measurement>,
+    block_number: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Row {
+    fn decrypt(reading: OnChainReading, keys: &KeyRegistry) -> Self {
+        let (temperature, humidity, measurements, error) = match reading.decrypt(keys) {
+            Ok(data) => (data.temperature(), data.humidity(), data.measurements, None),
+            Err(e) => (None, None, Vec::new(), Some(e.to_string())),
+        };
+
+        Self {
+            index: reading.index,
+            device_id: reading.device_id,
+            timestamp: reading.timestamp,
+            temperature,
+            humidity,
+            measurements,
+            block_number: reading.block_number,
+            error,
+        }
+    }
+
+    fn summary(&self) -> String {
+        self.measurements.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let reader = RegistryReader::new(&cli.rpc_url, &cli.contract)?;
+
+    let rows = match cli.command {
+        Command::Count => return print_count(reader.reading_count().await?, cli.format),
+        Command::Latest => vec![Row::decrypt(reader.latest_reading().await?, &load_keys()?)],
+        Command::Get { index } => vec![Row::decrypt(reader.reading(index).await?, &load_keys()?)],
+        Command::List(args) => list(&reader, &load_keys()?, &args).await?,
+    };
+
+    print_rows(&rows, cli.format)
+}
+
+/// Claves para descifrar: las mismas fuentes que usa el gateway
+/// (`KEYRING_PATH` o `ENCRYPTION_KEY`, y `KEYSTORE_PATH` para claves propias)
+fn load_keys() -> Result<KeyRegistry> {
+    let secrets = SecretStore::from_env()?;
+    let mut keys = KeyRegistry::new().with_keyring(&Keyring::load_configured(&secrets)?)?;
+    if let Ok(path) = std::env::var("KEYSTORE_PATH") {
+        keys = keys.with_keystore(path)?;
+    }
+
+    Ok(keys)
+}
+
+async fn list(reader: &RegistryReader, keys: &KeyRegistry, args: &ListArgs) -> Result<Vec<Row>> {
+    let count = reader.reading_count().await?;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let last = args.to_index.map_or(count - 1, |index| index.min(count - 1));
+    let mut rows = Vec::new();
+
+    // Los índices crecen con el bloque: se recorre hacia atrás hasta llenar `limit`
+    for index in (args.from_index..=last).rev() {
+        if rows.len() >= args.limit {
+            break;
+        }
+
+        let reading = reader.reading(index).await?;
+        if args.from_block.is_some_and(|block| reading.block_number < block) {
+            break;
+        }
+        if args.to_block.is_some_and(|block| reading.block_number > block) {
+            continue;
+        }
+        if args.device.as_ref().is_some_and(|device| *device != reading.device_id) {
+            continue;
+        }
+
+        rows.push(Row::decrypt(reading, keys));
+    }
+
+    rows.reverse();
+    Ok(rows)
+}
+
+fn print_count(count: u64, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => println!("{}", count),
+        OutputFormat::Json => println!("{}", serde_json::json!({ "count": count })),
+        OutputFormat::Csv => println!("count\n{}", count),
+    }
+
+    Ok(())
+}
+
+fn print_rows(rows: &[Row], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        OutputFormat::Csv => {
+            println!("index,deviceId,timestamp,temperature,humidity,measurements,blockNumber,error");
+            for row in rows {
+                println!(
+                    "{},{},{},{},{},{},{},{}",
+                    row.index,
+                    csv_field(&row.device_id),
+                    row.timestamp,
+                    row.temperature.map(|t| t.to_string()).unwrap_or_default(),
+                    row.humidity.map(|h| h.to_string()).unwrap_or_default(),
+                    csv_field(&row.summary()),
+                    row.block_number,
+                    csv_field(row.error.as_deref().unwrap_or_default()),
+                );
+            }
+        }
+        OutputFormat::Table => {
+            println!("{:>6}  {:<16}  {:>10}  {:>10}  {:<40}  ERROR", "INDEX", "DEVICE", "TIMESTAMP", "BLOCK", "MEASUREMENTS");
+            for row in rows {
+                println!(
+                    "{:>6}  {:<16}  {:>10}  {:>10}  {:<40}  {}",
+                    row.index,
+                    row.device_id,
+                    row.timestamp,
+                    row.block_number,
+                    if row.measurements.is_empty() { "-".to_string() } else { row.summary() },
+                    row.error.as_deref().unwrap_or_default(),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Campo CSV entrecomillado si contiene separadores o comillas
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_escaping() {
+        assert_eq!(csv_field("ESP32-001"), "ESP32-001");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Result, anyhow};
+
+use crate::metric::{Measurement, MetricValue};
+
+/// Codificación del cuerpo cifrado de una lectura (la declara la cabecera del envelope)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFormat {
+    Json,
+    /// Temperatura y humedad en 16 bytes fijos (`BinaryBody`)
+    Binary,
+    /// Cualquier conjunto de medidas en binario (`encode_metrics`)
+    Metrics,
+}
+
+impl BodyFormat {
+    /// Código en la cabecera del envelope
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Json => 0,
+            Self::Binary => 1,
+            Self::Metrics => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Json),
+            1 => Some(Self::Binary),
+            2 => Some(Self::Metrics),
+            _ => None,
+        }
+    }
+}
+
+/// Cuerpo binario fijo de una lectura (16 bytes, big endian):
+/// temperatura `f32`, humedad `f32` y timestamp `u64`.
+/// El device_id no se repite: va en claro en el contrato y ligado por el AAD.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinaryBody {
+    pub temperature: f32,
+    pub humidity: f32,
+    pub timestamp: u64,
+}
+
+impl BinaryBody {
+    pub const LEN: usize = 16;
+
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[0..4].copy_from_slice(&self.temperature.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.humidity.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: &[u8; Self::LEN] = bytes
+            .try_into()
+            .map_err(|_| anyhow!("Binary body must be {} bytes, got {}", Self::LEN, bytes.len()))?;
+
+        Ok(Self {
+            temperature: f32::from_be_bytes(bytes[0..4].try_into()?),
+            humidity: f32::from_be_bytes(bytes[4..8].try_into()?),
+            timestamp: u64::from_be_bytes(bytes[8..16].try_into()?),
+        })
+    }
+}
+
+/// Tipos de valor en el cuerpo `Metrics`
+const VALUE_NUMBER: u8 = 0;
+const VALUE_BOOL: u8 = 1;
+const VALUE_TEXT: u8 = 2;
+
+/// Cuerpo binario genérico (big endian): timestamp `u64`, número de medidas `u8` y,
+/// por medida, nombre y unidad (`u8` de longitud + UTF-8), tipo `u8` y valor
+/// (`f64`, `u8` 0/1, o `u8` de longitud + UTF-8).
+pub fn encode_metrics(timestamp: u64, measurements: &[Measurement]) -> Result<Vec<u8>> {
+    let count = u8::try_from(measurements.len()).map_err(|_| anyhow!("Too many measurements for a binary body"))?;
+    let mut bytes = timestamp.to_be_bytes().to_vec();
+    bytes.push(count);
+
+    for measurement in measurements {
+        push_str(&mut bytes, &measurement.name)?;
+        push_str(&mut bytes, &measurement.unit)?;
+        match &measurement.value {
+            MetricValue::Number(value) => {
+                bytes.push(VALUE_NUMBER);
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            MetricValue::Bool(value) => bytes.extend_from_slice(&[VALUE_BOOL, *value as u8]),
+            MetricValue::Text(value) => {
+                bytes.push(VALUE_TEXT);
+                push_str(&mut bytes, value)?;
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+pub fn decode_metrics(bytes: &[u8]) -> Result<(u64, Vec<Measurement>)> {
+    let mut reader = ByteReader { bytes };
+    let timestamp = u64::from_be_bytes(reader.take(8)?.try_into()?);
+    let count = reader.take(1)?[0];
+
+    let mut measurements = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name = reader.string()?;
+        let unit = reader.string()?;
+        let value = match reader.take(1)?[0] {
+            VALUE_NUMBER => MetricValue::Number(f64::from_be_bytes(reader.take(8)?.try_into()?)),
+            VALUE_BOOL => MetricValue::Bool(reader.take(1)?[0] != 0),
+            VALUE_TEXT => MetricValue::Text(reader.string()?),
+            other => return Err(anyhow!("Unknown value type {} in metrics body", other)),
+        };
+        measurements.push(Measurement { name, unit, value });
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err(anyhow!("{} trailing bytes in metrics body", reader.bytes.len()));
+    }
+    Ok((timestamp, measurements))
+}
+
+fn push_str(bytes: &mut Vec<u8>, value: &str) -> Result<()> {
+    let len = u8::try_from(value.len()).map_err(|_| anyhow!("'{}' is too long for a binary body", value))?;
+    bytes.push(len);
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(anyhow!("Truncated metrics body"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.take(1)?[0] as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| anyhow!("Invalid UTF-8 in metrics body"))
+    }
+}
+
+/// `f32` → `f64` conservando la representación decimal corta (22.3 y no 22.299999237060547)
+pub fn widen(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_body_roundtrip() {
+        let body = BinaryBody { temperature: 23.5, humidity: 61.25, timestamp: 1_700_000_000 };
+        assert_eq!(BinaryBody::from_bytes(&body.to_bytes()).unwrap(), body);
+        assert!(BinaryBody::from_bytes(&[0; 15]).is_err());
+    }
+
+    #[test]
+    fn test_metrics_body_roundtrip() {
+        let measurements = vec![
+            Measurement::number("co2", "ppm", 415.5),
+            Measurement { name: "door_open".to_string(), unit: String::new(), value: MetricValue::Bool(true) },
+            Measurement { name: "firmware".to_string(), unit: String::new(), value: MetricValue::Text("1.2.0".to_string()) },
+        ];
+        let bytes = encode_metrics(1_700_000_000, &measurements).unwrap();
+        assert_eq!(decode_metrics(&bytes).unwrap(), (1_700_000_000, measurements));
+
+        // Formato fijo: timestamp, número de medidas y "co2"/"ppm"/f64
+        let co2 = encode_metrics(1_700_000_000, &[Measurement::number("co2", "ppm", 415.5)]).unwrap();
+        assert_eq!(hex(&co2), "000000006553f100_01_03636f32_0370706d_00_4079f80000000000".replace('_', ""));
+
+        assert!(decode_metrics(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_metrics(&[bytes.clone(), vec![0]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_widen_keeps_decimal_value() {
+        assert_eq!(widen(22.3), 22.3);
+        assert_eq!(widen(-0.5), -0.5);
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_body_codes_are_stable() {
+        for format in [BodyFormat::Json, BodyFormat::Binary, BodyFormat::Metrics] {
+            assert_eq!(BodyFormat::from_code(format.code()), Some(format));
+        }
+        assert_eq!(BodyFormat::Binary.code(), 1);
+        assert_eq!(BodyFormat::Metrics.code(), 2);
+        assert_eq!(BodyFormat::from_code(3), None);
+    }
+}
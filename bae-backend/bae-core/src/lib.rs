@@ -0,0 +1,14 @@
+//! Modelo de datos compartido de Bae: la lectura de un sensor (un conjunto de medidas
+//! tipadas y con unidad), su versión de esquema, las reglas de validación por métrica
+//! y sus codificaciones (JSON, SenML o Protocol Buffers por MQTT, cuerpos binarios on-chain).
+//! Lo usan el gateway, el simulador y las herramientas de lectura.
+
+pub mod body;
+pub mod metric;
+pub mod proto;
+pub mod reading;
+pub mod senml;
+
+pub use body::{BinaryBody, BodyFormat};
+pub use metric::{Measurement, MetricCatalog, MetricSpec, MetricValue};
+pub use reading::{SensorReading, ValidationError, SCHEMA_VERSION};
@@ -0,0 +1,289 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::reading::ValidationError;
+
+/// Nombres y unidades (SenML, RFC 8428 §12.1) de las métricas conocidas
+pub const TEMPERATURE: &str = "temperature";
+pub const HUMIDITY: &str = "humidity";
+pub const CELSIUS: &str = "Cel";
+pub const RELATIVE_HUMIDITY: &str = "%RH";
+
+/// Longitud máxima del nombre y de la unidad de una medida
+pub const MAX_NAME_LEN: usize = 32;
+pub const MAX_UNIT_LEN: usize = 16;
+
+/// Valor de una medida (SenML: `v`, `vb` o `vs`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+}
+
+/// Una medida de la lectura, con la forma de un registro SenML:
+/// `{"n": "co2", "u": "ppm", "v": 415}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "WireMeasurement", into = "WireMeasurement")]
+pub struct Measurement {
+    pub name: String,
+    /// Unidad SenML (vacía si la medida no tiene)
+    pub unit: String,
+    pub value: MetricValue,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireMeasurement {
+    n: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    u: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    v: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vb: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vs: Option<String>,
+}
+
+impl TryFrom<WireMeasurement> for Measurement {
+    type Error = String;
+
+    fn try_from(wire: WireMeasurement) -> Result<Self, String> {
+        let value = match (wire.v, wire.vb, wire.vs) {
+            (Some(v), None, None) => MetricValue::Number(v),
+            (None, Some(vb), None) => MetricValue::Bool(vb),
+            (None, None, Some(vs)) => MetricValue::Text(vs),
+            _ => return Err(format!("Measurement '{}' must have exactly one of v, vb or vs", wire.n)),
+        };
+
+        Ok(Self { name: wire.n, unit: wire.u, value })
+    }
+}
+
+impl From<Measurement> for WireMeasurement {
+    fn from(measurement: Measurement) -> Self {
+        let (v, vb, vs) = match measurement.value {
+            MetricValue::Number(v) => (Some(v), None, None),
+            MetricValue::Bool(vb) => (None, Some(vb), None),
+            MetricValue::Text(vs) => (None, None, Some(vs)),
+        };
+
+        Self { n: measurement.name, u: measurement.unit, v, vb, vs }
+    }
+}
+
+impl Measurement {
+    pub fn number(name: impl Into<String>, unit: impl Into<String>, value: f64) -> Self {
+        Self { name: name.into(), unit: unit.into(), value: MetricValue::Number(value) }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self.value {
+            MetricValue::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            MetricValue::Number(value) => write!(f, "{}={}", self.name, value)?,
+            MetricValue::Bool(value) => write!(f, "{}={}", self.name, value)?,
+            MetricValue::Text(value) => write!(f, "{}={:?}", self.name, value)?,
+        }
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Regla de validación de una métrica: unidad obligatoria y rango opcional
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSpec {
+    pub name: String,
+    pub unit: String,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+impl MetricSpec {
+    pub fn new(name: &str, unit: &str, min: f64, max: f64) -> Self {
+        Self { name: name.to_string(), unit: unit.to_string(), min: Some(min), max: Some(max) }
+    }
+}
+
+/// Fichero `METRICS_PATH`: `{ "strict": false, "metrics": [{ "name": "co2", "unit": "ppm", "min": 0, "max": 10000 }] }`
+#[derive(Deserialize)]
+struct CatalogFile {
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    metrics: Vec<MetricSpec>,
+}
+
+/// Catálogo de métricas con sus reglas. Las métricas que no están en el catálogo
+/// se aceptan (sólo se exige un número finito) salvo en modo `strict`, así que
+/// un sensor nuevo no necesita cambios de código: como mucho, una entrada en `METRICS_PATH`.
+#[derive(Debug, Clone)]
+pub struct MetricCatalog {
+    specs: HashMap<String, MetricSpec>,
+    pub strict: bool,
+}
+
+impl Default for MetricCatalog {
+    /// Métricas conocidas de fábrica
+    fn default() -> Self {
+        let specs = [
+            MetricSpec::new(TEMPERATURE, CELSIUS, -50.0, 100.0),
+            MetricSpec::new(HUMIDITY, RELATIVE_HUMIDITY, 0.0, 100.0),
+            MetricSpec::new("co2", "ppm", 0.0, 10_000.0),
+            MetricSpec::new("pressure", "Pa", 30_000.0, 110_000.0),
+            MetricSpec::new("battery", "V", 0.0, 5.0),
+            MetricSpec::new("heart_rate", "beat/min", 20.0, 250.0),
+        ];
+
+        Self {
+            specs: specs.into_iter().map(|spec| (spec.name.clone(), spec)).collect(),
+            strict: false,
+        }
+    }
+}
+
+impl MetricCatalog {
+    /// Catálogo de fábrica ampliado con `METRICS_PATH`, si está configurado
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("METRICS_PATH") {
+            Ok(path) => Self::default().with_file(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Añade (o sustituye) las métricas del fichero
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read metrics catalog {}: {}", path.display(), e))?;
+        let file: CatalogFile = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid metrics catalog {}: {}", path.display(), e))?;
+
+        self.strict = file.strict;
+        for spec in file.metrics {
+            self = self.with_spec(spec);
+        }
+        Ok(self)
+    }
+
+    /// En modo estricto se rechazan las métricas que no están en el catálogo
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_spec(mut self, spec: MetricSpec) -> Self {
+        self.specs.insert(spec.name.clone(), spec);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MetricSpec> {
+        self.specs.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Comprueba una medida contra su regla (nombre y unidad ya validados en forma)
+    pub fn check(&self, measurement: &Measurement) -> Result<(), ValidationError> {
+        let name = &measurement.name;
+        let out_of_range = |value: f64| ValidationError::OutOfRange { name: name.clone(), value };
+
+        let Some(spec) = self.specs.get(name) else {
+            if self.strict {
+                return Err(ValidationError::UnknownMetric(name.clone()));
+            }
+            return match measurement.value {
+                MetricValue::Number(value) if !value.is_finite() => Err(out_of_range(value)),
+                _ => Ok(()),
+            };
+        };
+
+        if measurement.unit != spec.unit {
+            return Err(ValidationError::UnitMismatch {
+                name: name.clone(),
+                expected: spec.unit.clone(),
+                got: measurement.unit.clone(),
+            });
+        }
+
+        match measurement.value {
+            MetricValue::Number(value) => {
+                if !value.is_finite()
+                    || spec.min.is_some_and(|min| value < min)
+                    || spec.max.is_some_and(|max| value > max)
+                {
+                    return Err(out_of_range(value));
+                }
+                Ok(())
+            }
+            _ if spec.min.is_some() || spec.max.is_some() => Err(ValidationError::NotANumber(name.clone())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_senml_record_shape() {
+        let json = r#"[{"n":"co2","u":"ppm","v":415.0},{"n":"door_open","vb":true},{"n":"firmware","vs":"1.2.0"}]"#;
+        let measurements: Vec<Measurement> = serde_json::from_str(json).unwrap();
+        assert_eq!(measurements[0], Measurement::number("co2", "ppm", 415.0));
+        assert_eq!(measurements[1].value, MetricValue::Bool(true));
+        assert_eq!(measurements[2].value, MetricValue::Text("1.2.0".to_string()));
+        assert_eq!(serde_json::to_string(&measurements).unwrap(), json);
+
+        assert!(serde_json::from_str::<Measurement>(r#"{"n":"x"}"#).is_err());
+        assert!(serde_json::from_str::<Measurement>(r#"{"n":"x","v":1,"vb":true}"#).is_err());
+    }
+
+    #[test]
+    fn test_catalog_rules() {
+        let catalog = MetricCatalog::default();
+        assert!(catalog.check(&Measurement::number("co2", "ppm", 415.0)).is_ok());
+        assert!(matches!(catalog.check(&Measurement::number("co2", "ppm", -1.0)), Err(ValidationError::OutOfRange { .. })));
+        assert!(matches!(catalog.check(&Measurement::number("co2", "%", 4.0)), Err(ValidationError::UnitMismatch { .. })));
+
+        // Desconocidas: se aceptan salvo en modo estricto
+        let lux = Measurement::number("illuminance", "lx", 300.0);
+        assert!(catalog.check(&lux).is_ok());
+        assert!(catalog.check(&Measurement::number("illuminance", "lx", f64::NAN)).is_err());
+        let strict = MetricCatalog::default().with_strict(true);
+        assert_eq!(strict.check(&lux), Err(ValidationError::UnknownMetric("illuminance".to_string())));
+        assert!(strict.with_spec(MetricSpec::new("illuminance", "lx", 0.0, 100_000.0)).check(&lux).is_ok());
+    }
+
+    #[test]
+    fn test_catalog_file() {
+        let path = std::env::temp_dir().join(format!("bae-metrics-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"strict": true, "metrics": [{"name": "co2", "unit": "ppm", "max": 5000}, {"name": "lux", "unit": "lx"}]}"#).unwrap();
+        let catalog = MetricCatalog::default().with_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(catalog.strict);
+        assert!(catalog.check(&Measurement::number("co2", "ppm", 6000.0)).is_err());
+        assert!(catalog.check(&Measurement::number("lux", "lx", 1e6)).is_ok());
+        assert!(catalog.get(TEMPERATURE).is_some());
+    }
+}
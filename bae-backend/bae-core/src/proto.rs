@@ -0,0 +1,153 @@
+use anyhow::{Result, anyhow};
+use prost::Message;
+
+use crate::metric::{Measurement, MetricValue};
+use crate::reading::{SensorReading, ValidationError, SCHEMA_VERSION};
+
+/// Mensajes de `proto/reading.proto` (paquete `bae.v1`), escritos a mano con
+/// `prost` para no depender de `protoc` al compilar
+pub mod v1 {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Measurement {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub unit: String,
+        #[prost(oneof = "Value", tags = "3, 4, 5")]
+        pub value: Option<Value>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(double, tag = "3")]
+        Number(f64),
+        #[prost(bool, tag = "4")]
+        Flag(bool),
+        #[prost(string, tag = "5")]
+        Text(String),
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Reading {
+        #[prost(uint32, tag = "1")]
+        pub schema_version: u32,
+        #[prost(string, tag = "2")]
+        pub device_id: String,
+        #[prost(uint64, tag = "3")]
+        pub timestamp: u64,
+        #[prost(message, repeated, tag = "4")]
+        pub measurements: Vec<Measurement>,
+    }
+}
+
+impl From<&SensorReading> for v1::Reading {
+    fn from(reading: &SensorReading) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION as u32,
+            device_id: reading.device_id.clone(),
+            timestamp: reading.timestamp,
+            measurements: reading
+                .measurements
+                .iter()
+                .map(|measurement| v1::Measurement {
+                    name: measurement.name.clone(),
+                    unit: measurement.unit.clone(),
+                    value: Some(match &measurement.value {
+                        MetricValue::Number(value) => v1::Value::Number(*value),
+                        MetricValue::Bool(value) => v1::Value::Flag(*value),
+                        MetricValue::Text(value) => v1::Value::Text(value.clone()),
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<v1::Reading> for SensorReading {
+    type Error = anyhow::Error;
+
+    fn try_from(reading: v1::Reading) -> Result<Self> {
+        // proto3 no distingue 0 de ausente: un firmware que no lo envía usa la versión actual
+        if reading.schema_version > SCHEMA_VERSION as u32 {
+            return Err(ValidationError::UnsupportedSchemaVersion(reading.schema_version.min(u8::MAX as u32) as u8).into());
+        }
+
+        let measurements = reading
+            .measurements
+            .into_iter()
+            .map(|measurement| {
+                let value = match measurement.value {
+                    Some(v1::Value::Number(value)) => MetricValue::Number(value),
+                    Some(v1::Value::Flag(value)) => MetricValue::Bool(value),
+                    Some(v1::Value::Text(value)) => MetricValue::Text(value),
+                    None => return Err(anyhow!("Measurement '{}' has no value", measurement.name)),
+                };
+                Ok(Measurement { name: measurement.name, unit: measurement.unit, value })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self::new(reading.device_id, reading.timestamp, measurements))
+    }
+}
+
+impl SensorReading {
+    /// Payload Protocol Buffers de MQTT (`bae.v1.Reading`)
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        v1::Reading::from(self).encode_to_vec()
+    }
+
+    pub fn from_protobuf(payload: &[u8]) -> Result<Self> {
+        let reading = v1::Reading::decode(payload).map_err(|e| anyhow!("Invalid protobuf reading: {}", e))?;
+        reading.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading() -> SensorReading {
+        SensorReading::climate("ESP32-001", 23.5, 61.25, 1_700_000_000)
+    }
+
+    #[test]
+    fn test_protobuf_roundtrip() {
+        let air = SensorReading::new("AIR-042", 1_700_000_000, vec![
+            Measurement::number("co2", "ppm", 612.0),
+            Measurement { name: "door_open".to_string(), unit: String::new(), value: MetricValue::Bool(true) },
+            Measurement { name: "firmware".to_string(), unit: String::new(), value: MetricValue::Text("1.2.0".to_string()) },
+        ]);
+        for reading in [reading(), air] {
+            assert_eq!(SensorReading::from_protobuf(&reading.to_protobuf()).unwrap(), reading);
+        }
+
+        // Más compacto que el JSON
+        assert!(reading().to_protobuf().len() < reading().to_json().unwrap().len() / 2);
+    }
+
+    #[test]
+    fn test_protobuf_wire_format() {
+        // Formato fijo: lo comparte el firmware
+        let co2 = SensorReading::new("A", 1_700_000_000, vec![Measurement::number("co2", "ppm", 415.5)]);
+        let hex: String = co2.to_protobuf().iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "0802_120141_1880e2cfaa06_2213_0a03636f32_120370706d_190000000000f87940".replace('_', ""));
+    }
+
+    #[test]
+    fn test_rejects_invalid_protobuf() {
+        let mut future = v1::Reading::from(&reading());
+        future.schema_version = 9;
+        assert!(SensorReading::from_protobuf(&future.encode_to_vec()).is_err());
+
+        let mut no_value = v1::Reading::from(&reading());
+        no_value.measurements[0].value = None;
+        assert!(SensorReading::from_protobuf(&no_value.encode_to_vec()).is_err());
+
+        assert!(SensorReading::from_protobuf(&[0xff, 0xff]).is_err());
+
+        // Sin versión (0): la actual
+        let mut unversioned = v1::Reading::from(&reading());
+        unversioned.schema_version = 0;
+        assert_eq!(SensorReading::from_protobuf(&unversioned.encode_to_vec()).unwrap(), reading());
+    }
+}
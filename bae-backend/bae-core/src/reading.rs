@@ -0,0 +1,373 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::body::{self, BinaryBody, BodyFormat};
+use crate::metric::{self, Measurement, MetricCatalog, MetricValue, CELSIUS, HUMIDITY, RELATIVE_HUMIDITY, TEMPERATURE};
+
+/// Versión del esquema de `SensorReading`. Los payloads sin `schema_version`
+/// (dispositivos anteriores a versionar el esquema) son de la versión 1.
+///
+/// - v1: `temperature` y `humidity` fijos
+/// - v2: lista `measurements` de registros SenML (`n`, `u`, `v`/`vb`/`vs`)
+pub const SCHEMA_VERSION: u8 = 2;
+
+/// Longitud máxima del device_id
+pub const MAX_DEVICE_ID_LEN: usize = 100;
+/// Medidas por lectura como máximo
+pub const MAX_MEASUREMENTS: usize = 32;
+/// Antigüedad máxima de una lectura al llegar al gateway (segundos)
+pub const MAX_AGE_SECS: u64 = 3600;
+/// Desfase máximo hacia el futuro tolerado por relojes desajustados (segundos)
+pub const MAX_FUTURE_SECS: u64 = 300;
+
+/// Lectura de un sensor: un conjunto de medidas tipadas y con unidad
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "WireReading", into = "WireReading")]
+pub struct SensorReading {
+    pub device_id: String,
+    pub timestamp: u64,
+    pub measurements: Vec<Measurement>,
+}
+
+/// Forma JSON de la lectura en cualquier versión del esquema
+#[derive(Serialize, Deserialize)]
+struct WireReading {
+    #[serde(default = "legacy_schema_version")]
+    schema_version: u8,
+    device_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    humidity: Option<f64>,
+    timestamp: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    measurements: Vec<Measurement>,
+}
+
+fn legacy_schema_version() -> u8 {
+    1
+}
+
+impl TryFrom<WireReading> for SensorReading {
+    type Error = ValidationError;
+
+    fn try_from(wire: WireReading) -> Result<Self, ValidationError> {
+        match wire.schema_version {
+            1 => match (wire.temperature, wire.humidity) {
+                (Some(temperature), Some(humidity)) => {
+                    Ok(Self::climate(wire.device_id, temperature, humidity, wire.timestamp))
+                }
+                _ => Err(ValidationError::NoMeasurements),
+            },
+            2 => Ok(Self::new(wire.device_id, wire.timestamp, wire.measurements)),
+            version => Err(ValidationError::UnsupportedSchemaVersion(version)),
+        }
+    }
+}
+
+impl From<SensorReading> for WireReading {
+    fn from(reading: SensorReading) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            device_id: reading.device_id,
+            temperature: None,
+            humidity: None,
+            timestamp: reading.timestamp,
+            measurements: reading.measurements,
+        }
+    }
+}
+
+/// Motivo por el que se rechaza una lectura
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("Unsupported schema version {0} (max {SCHEMA_VERSION})")]
+    UnsupportedSchemaVersion(u8),
+    #[error("Invalid device_id length")]
+    InvalidDeviceId,
+    #[error("Reading has no measurements")]
+    NoMeasurements,
+    #[error("Too many measurements: {0} (max {MAX_MEASUREMENTS})")]
+    TooManyMeasurements(usize),
+    #[error("Invalid metric name or unit: {0:?}")]
+    InvalidMetricName(String),
+    #[error("Duplicate metric: {0}")]
+    DuplicateMetric(String),
+    #[error("Unknown metric: {0}")]
+    UnknownMetric(String),
+    #[error("Unit mismatch for {name}: expected {expected:?}, got {got:?}")]
+    UnitMismatch { name: String, expected: String, got: String },
+    #[error("{name} out of range: {value}")]
+    OutOfRange { name: String, value: f64 },
+    #[error("{0} must be a number")]
+    NotANumber(String),
+    #[error("Timestamp is in the future")]
+    TimestampInFuture,
+    #[error("Timestamp is too old")]
+    TimestampTooOld,
+}
+
+impl SensorReading {
+    pub fn new(device_id: impl Into<String>, timestamp: u64, measurements: Vec<Measurement>) -> Self {
+        Self { device_id: device_id.into(), timestamp, measurements }
+    }
+
+    /// Lectura de temperatura (°C) y humedad relativa (%), la del sensor original
+    pub fn climate(device_id: impl Into<String>, temperature: f64, humidity: f64, timestamp: u64) -> Self {
+        Self::new(device_id, timestamp, vec![
+            Measurement::number(TEMPERATURE, CELSIUS, temperature),
+            Measurement::number(HUMIDITY, RELATIVE_HUMIDITY, humidity),
+        ])
+    }
+
+    pub fn value(&self, name: &str) -> Option<&MetricValue> {
+        self.measurements.iter().find(|m| m.name == name).map(|m| &m.value)
+    }
+
+    /// Valor numérico de la métrica `name`, si la lectura la trae
+    pub fn number(&self, name: &str) -> Option<f64> {
+        self.measurements.iter().find(|m| m.name == name).and_then(Measurement::as_number)
+    }
+
+    pub fn temperature(&self) -> Option<f64> {
+        self.number(TEMPERATURE)
+    }
+
+    pub fn humidity(&self) -> Option<f64> {
+        self.number(HUMIDITY)
+    }
+
+    /// Medidas en una línea, para los logs
+    pub fn summary(&self) -> String {
+        self.measurements.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Payload JSON de MQTT
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| anyhow!("Serialization failed: {}", e))
+    }
+
+    /// Parsea un payload JSON de cualquier versión del esquema que conozcamos
+    pub fn from_json(payload: &[u8]) -> Result<Self> {
+        serde_json::from_slice(payload).map_err(|e| anyhow!("Deserialization failed: {}", e))
+    }
+
+    /// Valida la lectura contra el reloj del sistema
+    pub fn validate(&self, catalog: &MetricCatalog) -> Result<(), ValidationError> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.validate_at(catalog, now)
+    }
+
+    /// Valida la lectura como si ahora fuera `now` (segundos Unix)
+    pub fn validate_at(&self, catalog: &MetricCatalog, now: u64) -> Result<(), ValidationError> {
+        validate_metadata(&self.device_id, self.timestamp, now)?;
+
+        if self.measurements.is_empty() {
+            return Err(ValidationError::NoMeasurements);
+        }
+
+        if self.measurements.len() > MAX_MEASUREMENTS {
+            return Err(ValidationError::TooManyMeasurements(self.measurements.len()));
+        }
+
+        let mut seen = HashSet::new();
+        for measurement in &self.measurements {
+            if !is_metric_name(&measurement.name) || measurement.unit.len() > metric::MAX_UNIT_LEN {
+                return Err(ValidationError::InvalidMetricName(measurement.name.clone()));
+            }
+            if !seen.insert(measurement.name.as_str()) {
+                return Err(ValidationError::DuplicateMetric(measurement.name.clone()));
+            }
+            catalog.check(measurement)?;
+        }
+
+        Ok(())
+    }
+
+    /// Codificación binaria más compacta para la lectura: los 16 bytes fijos si es
+    /// exactamente temperatura y humedad, y si no el cuerpo genérico de medidas
+    pub fn compact_body_format(&self) -> BodyFormat {
+        match self.measurements.as_slice() {
+            [t, h] if t.name == TEMPERATURE && t.unit == CELSIUS && t.as_number().is_some()
+                && h.name == HUMIDITY && h.unit == RELATIVE_HUMIDITY && h.as_number().is_some() => BodyFormat::Binary,
+            _ => BodyFormat::Metrics,
+        }
+    }
+
+    /// Cuerpo a encriptar según la codificación del envelope
+    pub fn encode_body(&self, format: BodyFormat) -> Result<Vec<u8>> {
+        match format {
+            BodyFormat::Json => self.to_json(),
+            BodyFormat::Binary => match (self.temperature(), self.humidity()) {
+                (Some(temperature), Some(humidity)) if self.measurements.len() == 2 => Ok(BinaryBody {
+                    temperature: temperature as f32,
+                    humidity: humidity as f32,
+                    timestamp: self.timestamp,
+                }.to_bytes().to_vec()),
+                _ => Err(anyhow!("Binary body only holds temperature and humidity; use the metrics body")),
+            },
+            BodyFormat::Metrics => body::encode_metrics(self.timestamp, &self.measurements),
+        }
+    }
+
+    /// Reconstruye la lectura; los cuerpos binarios no incluyen el device_id,
+    /// que viene del registro on-chain (y está ligado por el AAD)
+    pub fn decode_body(format: BodyFormat, body: &[u8], device_id: &str) -> Result<Self> {
+        match format {
+            BodyFormat::Json => Self::from_json(body),
+            BodyFormat::Binary => {
+                let body = BinaryBody::from_bytes(body)?;
+                Ok(Self::climate(device_id, body::widen(body.temperature), body::widen(body.humidity), body.timestamp))
+            }
+            BodyFormat::Metrics => {
+                let (timestamp, measurements) = body::decode_metrics(body)?;
+                Ok(Self::new(device_id, timestamp, measurements))
+            }
+        }
+    }
+}
+
+/// Nombres de métrica: minúsculas, dígitos y `_`, empezando por letra
+fn is_metric_name(name: &str) -> bool {
+    name.len() <= metric::MAX_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Valida lo que de una lectura va en claro (device_id y timestamp). Es todo lo que
+/// se puede validar de una lectura cifrada en el dispositivo.
+pub fn validate_metadata(device_id: &str, timestamp: u64, now: u64) -> Result<(), ValidationError> {
+    if device_id.is_empty() || device_id.len() > MAX_DEVICE_ID_LEN {
+        return Err(ValidationError::InvalidDeviceId);
+    }
+
+    // No puede ser futuro ni muy antiguo
+    if timestamp > now + MAX_FUTURE_SECS {
+        return Err(ValidationError::TimestampInFuture);
+    }
+
+    if timestamp + MAX_AGE_SECS < now {
+        return Err(ValidationError::TimestampTooOld);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn reading() -> SensorReading {
+        SensorReading::climate("ESP32-001", 23.5, 61.25, NOW)
+    }
+
+    fn co2() -> SensorReading {
+        SensorReading::new("CO2-007", NOW, vec![
+            Measurement::number("co2", "ppm", 415.0),
+            Measurement::number("battery", "V", 3.7),
+            Measurement { name: "door_open".to_string(), unit: String::new(), value: MetricValue::Bool(false) },
+        ])
+    }
+
+    #[test]
+    fn test_json_wire_format() {
+        // Formato fijo: el gateway, el simulador, la API Node y el frontend lo comparten
+        let json = concat!(
+            r#"{"schema_version":2,"device_id":"ESP32-001","timestamp":1700000000,"measurements":["#,
+            r#"{"n":"temperature","u":"Cel","v":23.5},{"n":"humidity","u":"%RH","v":61.25}]}"#,
+        );
+        assert_eq!(String::from_utf8(reading().to_json().unwrap()).unwrap(), json);
+        assert_eq!(SensorReading::from_json(json.as_bytes()).unwrap(), reading());
+
+        // v1 y dispositivos anteriores a versionar el esquema
+        let v1 = r#"{"schema_version":1,"device_id":"ESP32-001","temperature":23.5,"humidity":61.25,"timestamp":1700000000}"#;
+        assert_eq!(SensorReading::from_json(v1.as_bytes()).unwrap(), reading());
+        let legacy = r#"{"device_id":"ESP32-001","temperature":23.5,"humidity":61.25,"timestamp":1700000000}"#;
+        assert_eq!(SensorReading::from_json(legacy.as_bytes()).unwrap(), reading());
+
+        for invalid in [
+            r#"{"schema_version":9,"device_id":"ESP32-001","timestamp":1700000000}"#,
+            r#"{"schema_version":0,"device_id":"ESP32-001","temperature":23.5,"humidity":61.25,"timestamp":1700000000}"#,
+            r#"{"device_id":"ESP32-001","temperature":23.5,"timestamp":1700000000}"#,
+        ] {
+            assert!(SensorReading::from_json(invalid.as_bytes()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_body_roundtrip() {
+        for format in [BodyFormat::Json, BodyFormat::Binary, BodyFormat::Metrics] {
+            let body = reading().encode_body(format).unwrap();
+            assert_eq!(SensorReading::decode_body(format, &body, "ESP32-001").unwrap(), reading());
+        }
+        for format in [BodyFormat::Json, BodyFormat::Metrics] {
+            let body = co2().encode_body(format).unwrap();
+            assert_eq!(SensorReading::decode_body(format, &body, "CO2-007").unwrap(), co2());
+        }
+
+        // Cuerpo binario: f32 temperatura, f32 humedad, u64 timestamp (big endian)
+        let body = reading().encode_body(BodyFormat::Binary).unwrap();
+        assert_eq!(hex(&body), "41bc000042750000000000006553f100");
+
+        // f32 on-chain, pero la lectura vuelve con el valor decimal original
+        let body = SensorReading::climate("ESP32-001", 22.3, 55.1, NOW).encode_body(BodyFormat::Binary).unwrap();
+        assert_eq!(SensorReading::decode_body(BodyFormat::Binary, &body, "ESP32-001").unwrap().temperature(), Some(22.3));
+
+        assert!(co2().encode_body(BodyFormat::Binary).is_err());
+    }
+
+    #[test]
+    fn test_compact_body_format() {
+        assert_eq!(reading().compact_body_format(), BodyFormat::Binary);
+        assert_eq!(co2().compact_body_format(), BodyFormat::Metrics);
+
+        let mut reversed = reading();
+        reversed.measurements.reverse();
+        assert_eq!(reversed.compact_body_format(), BodyFormat::Metrics);
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let catalog = MetricCatalog::default();
+        assert_eq!(reading().validate_at(&catalog, NOW), Ok(()));
+        assert_eq!(co2().validate_at(&catalog, NOW), Ok(()));
+
+        let with = |measurements: Vec<Measurement>| SensorReading { measurements, ..reading() };
+        let invalid = [
+            (SensorReading { device_id: String::new(), ..reading() }, ValidationError::InvalidDeviceId),
+            (SensorReading { device_id: "x".repeat(101), ..reading() }, ValidationError::InvalidDeviceId),
+            (SensorReading::climate("ESP32-001", 100.5, 50.0, NOW), ValidationError::OutOfRange { name: "temperature".to_string(), value: 100.5 }),
+            (SensorReading::climate("ESP32-001", 20.0, -1.0, NOW), ValidationError::OutOfRange { name: "humidity".to_string(), value: -1.0 }),
+            (SensorReading { timestamp: NOW + MAX_FUTURE_SECS + 1, ..reading() }, ValidationError::TimestampInFuture),
+            (SensorReading { timestamp: NOW - MAX_AGE_SECS - 1, ..reading() }, ValidationError::TimestampTooOld),
+            (with(vec![]), ValidationError::NoMeasurements),
+            (with(vec![Measurement::number("lux", "lx", 1.0); 33]), ValidationError::TooManyMeasurements(33)),
+            (with(vec![Measurement::number("CO2", "ppm", 1.0)]), ValidationError::InvalidMetricName("CO2".to_string())),
+            (with(vec![Measurement::number("co2", "ppm", 1.0), Measurement::number("co2", "ppm", 2.0)]), ValidationError::DuplicateMetric("co2".to_string())),
+            (
+                with(vec![Measurement::number("temperature", "degF", 70.0)]),
+                ValidationError::UnitMismatch { name: "temperature".to_string(), expected: "Cel".to_string(), got: "degF".to_string() },
+            ),
+            (
+                with(vec![Measurement { name: "battery".to_string(), unit: "V".to_string(), value: MetricValue::Text("low".to_string()) }]),
+                ValidationError::NotANumber("battery".to_string()),
+            ),
+        ];
+        for (reading, error) in invalid {
+            assert_eq!(reading.validate_at(&catalog, NOW), Err(error));
+        }
+
+        assert!(SensorReading::climate("ESP32-001", f64::NAN, 50.0, NOW).validate_at(&catalog, NOW).is_err());
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::metric::{Measurement, MetricValue};
+use crate::reading::SensorReading;
+
+/// Versión de SenML que entendemos (`bver`, RFC 8428 §4.4)
+pub const SENML_VERSION: i64 = 10;
+/// Tiempos menores que 2^28 son relativos al momento de recepción (RFC 8428 §4.5.3)
+const RELATIVE_TIME_LIMIT: f64 = 268_435_456.0;
+
+/// Registro de un pack SenML tal como llega, con sus campos base
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Record {
+    #[serde(default)]
+    pub bver: Option<i64>,
+    #[serde(default)]
+    pub bn: Option<String>,
+    #[serde(default)]
+    pub bt: Option<f64>,
+    #[serde(default)]
+    pub bu: Option<String>,
+    #[serde(default)]
+    pub bv: Option<f64>,
+    #[serde(default)]
+    pub bs: Option<f64>,
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub u: Option<String>,
+    #[serde(default)]
+    pub v: Option<f64>,
+    #[serde(default)]
+    pub vs: Option<String>,
+    #[serde(default)]
+    pub vb: Option<bool>,
+    #[serde(default)]
+    pub vd: Option<String>,
+    #[serde(default)]
+    pub s: Option<f64>,
+    #[serde(default)]
+    pub t: Option<f64>,
+    /// Campos que no conocemos: se ignoran salvo los terminados en `_` (must-understand)
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Registro con los campos base ya aplicados
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedRecord {
+    /// Nombre completo (`bn` + `n`)
+    pub name: String,
+    pub unit: String,
+    pub value: MetricValue,
+    /// Segundos Unix
+    pub time: u64,
+}
+
+/// Parsea un pack SenML en JSON (`[...]`) o CBOR (RFC 8428 §6), según su primer byte
+pub fn parse_pack(payload: &[u8]) -> Result<Vec<Record>> {
+    match payload.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[') => serde_json::from_slice(payload).map_err(|e| anyhow!("Invalid SenML JSON: {}", e)),
+        Some(_) => parse_cbor(payload),
+        None => Err(anyhow!("Empty SenML pack")),
+    }
+}
+
+/// En CBOR las etiquetas son enteros (RFC 8428 Tabla 4); se traducen a sus nombres
+/// JSON para deserializar el mismo `Record`
+fn parse_cbor(payload: &[u8]) -> Result<Vec<Record>> {
+    let value: ciborium::Value = ciborium::from_reader(payload).map_err(|e| anyhow!("Invalid SenML CBOR: {}", e))?;
+    let json = cbor_to_json(value)?;
+
+    serde_json::from_value(json).map_err(|e| anyhow!("Invalid SenML CBOR: {}", e))
+}
+
+fn cbor_label(label: i128) -> Option<&'static str> {
+    Some(match label {
+        -1 => "bver",
+        -2 => "bn",
+        -3 => "bt",
+        -4 => "bu",
+        -5 => "bv",
+        -6 => "bs",
+        0 => "n",
+        1 => "u",
+        2 => "v",
+        3 => "vs",
+        4 => "vb",
+        5 => "s",
+        6 => "t",
+        7 => "ut",
+        8 => "vd",
+        _ => return None,
+    })
+}
+
+fn cbor_to_json(value: ciborium::Value) -> Result<serde_json::Value> {
+    use ciborium::Value;
+
+    Ok(match value {
+        Value::Integer(i) => {
+            let i = i128::from(i);
+            i64::try_from(i)
+                .map(serde_json::Value::from)
+                .map_err(|_| anyhow!("CBOR integer out of range: {}", i))?
+        }
+        Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| anyhow!("Non-finite number in SenML CBOR"))?,
+        Value::Text(text) => serde_json::Value::String(text),
+        Value::Bool(b) => serde_json::Value::Bool(b),
+        Value::Null => serde_json::Value::Null,
+        // `vd` viaja como bytes; en JSON sería base64url. Sólo importa que exista.
+        Value::Bytes(bytes) => serde_json::Value::String(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+        Value::Tag(_, inner) => cbor_to_json(*inner)?,
+        Value::Array(items) => serde_json::Value::Array(items.into_iter().map(cbor_to_json).collect::<Result<_>>()?),
+        Value::Map(entries) => {
+            let mut map = serde_json::Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    Value::Integer(label) => {
+                        let label = i128::from(label);
+                        cbor_label(label).map_or_else(|| label.to_string(), str::to_string)
+                    }
+                    Value::Text(text) => text,
+                    other => return Err(anyhow!("Invalid SenML CBOR label: {:?}", other)),
+                };
+                map.insert(key, cbor_to_json(value)?);
+            }
+            serde_json::Value::Object(map)
+        }
+        other => return Err(anyhow!("Unsupported CBOR value in SenML pack: {:?}", other)),
+    })
+}
+
+/// Aplica los campos base a cada registro (RFC 8428 §4.6). Los base se mantienen
+/// hasta que otro registro los cambia; los tiempos relativos se cuentan desde `now`.
+/// Los registros sólo con campos base (sin nombre ni valor) no producen medida.
+pub fn resolve(records: Vec<Record>, now: u64) -> Result<Vec<ResolvedRecord>> {
+    let mut base = Record::default();
+    let mut resolved = Vec::with_capacity(records.len());
+
+    for record in records {
+        if let Some(field) = record.extra.keys().find(|key| key.ends_with('_')) {
+            return Err(anyhow!("Unsupported must-understand SenML field '{}'", field));
+        }
+        if let Some(version) = record.bver {
+            if version > SENML_VERSION {
+                return Err(anyhow!("Unsupported SenML version {} (max {})", version, SENML_VERSION));
+            }
+        }
+
+        base.bn = record.bn.clone().or(base.bn);
+        base.bt = record.bt.or(base.bt);
+        base.bu = record.bu.clone().or(base.bu);
+        base.bv = record.bv.or(base.bv);
+        base.bs = record.bs.or(base.bs);
+
+        let values = [record.v.is_some(), record.vs.is_some(), record.vb.is_some(), record.vd.is_some()];
+        let value = match (record.v, record.vs, record.vb, record.s) {
+            _ if values.iter().filter(|present| **present).count() > 1 => {
+                return Err(anyhow!("SenML record has more than one value"));
+            }
+            _ if record.vd.is_some() => return Err(anyhow!("SenML data values (vd) are not supported")),
+            (Some(v), _, _, _) => MetricValue::Number(base.bv.unwrap_or(0.0) + v),
+            (_, Some(vs), _, _) => MetricValue::Text(vs),
+            (_, _, Some(vb), _) => MetricValue::Bool(vb),
+            (_, _, _, Some(s)) => MetricValue::Number(base.bs.unwrap_or(0.0) + s),
+            _ if record.n.is_none() => continue,
+            _ => return Err(anyhow!("SenML record '{}' has no value", record.n.unwrap_or_default())),
+        };
+
+        let name = format!("{}{}", base.bn.as_deref().unwrap_or_default(), record.n.as_deref().unwrap_or_default());
+        if name.is_empty() {
+            return Err(anyhow!("SenML record has no name"));
+        }
+
+        resolved.push(ResolvedRecord {
+            name,
+            unit: record.u.or_else(|| base.bu.clone()).unwrap_or_default(),
+            value,
+            time: resolve_time(base.bt.unwrap_or(0.0) + record.t.unwrap_or(0.0), now)?,
+        });
+    }
+
+    Ok(resolved)
+}
+
+fn resolve_time(time: f64, now: u64) -> Result<u64> {
+    let time = if time < RELATIVE_TIME_LIMIT { now as f64 + time } else { time };
+    if !time.is_finite() || time < 0.0 {
+        return Err(anyhow!("Invalid SenML time {}", time));
+    }
+    Ok(time as u64)
+}
+
+/// Separa un nombre resuelto en dispositivo y métrica por el último `:` o `/`:
+/// `urn:dev:mac:0024befffe804ff1:temperature` → (`urn:dev:mac:0024befffe804ff1`, `temperature`)
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind([':', '/']) {
+        Some(at) => (&name[..at], &name[at + 1..]),
+        None => ("", name),
+    }
+}
+
+/// Normaliza un pack en lecturas: una por dispositivo y instante, en el orden del pack.
+/// Los nombres sin parte de dispositivo son de `default_device` (el del topic).
+pub fn to_readings(payload: &[u8], default_device: &str, now: u64) -> Result<Vec<SensorReading>> {
+    let records = resolve(parse_pack(payload)?, now)?;
+    if records.is_empty() {
+        return Err(anyhow!("SenML pack has no measurements"));
+    }
+
+    let mut readings: Vec<SensorReading> = Vec::new();
+    for record in records {
+        let (device, metric) = split_name(&record.name);
+        let device = if device.is_empty() { default_device } else { device };
+        let measurement = Measurement { name: metric.to_string(), unit: record.unit, value: record.value };
+
+        match readings.iter_mut().find(|r| r.device_id == device && r.timestamp == record.time) {
+            Some(reading) => reading.measurements.push(measurement),
+            None => readings.push(SensorReading::new(device, record.time, vec![measurement])),
+        }
+    }
+
+    Ok(readings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_resolves_base_fields() {
+        // RFC 8428 §5.1.2, con los nombres como los usa Bae
+        let pack = br#"[
+            {"bn":"urn:dev:ow:10e2073a01080063:","bt":1.276020076e+09,"bu":"Cel","bver":10,"n":"temperature","v":23.1},
+            {"n":"temperature","t":10,"v":23.4},
+            {"n":"humidity","u":"%RH","t":10,"v":55.0}
+        ]"#;
+        let readings = to_readings(pack, "ESP32-001", NOW).unwrap();
+
+        assert_eq!(readings, vec![
+            SensorReading::new("urn:dev:ow:10e2073a01080063", 1_276_020_076, vec![
+                Measurement::number("temperature", "Cel", 23.1),
+            ]),
+            SensorReading::climate("urn:dev:ow:10e2073a01080063", 23.4, 55.0, 1_276_020_086),
+        ]);
+    }
+
+    #[test]
+    fn test_relative_times_and_base_values() {
+        let pack = br#"[
+            {"bv":400,"bu":"ppm","n":"co2","v":15},
+            {"bn":"AIR-042/","n":"co2","t":-60,"v":12},
+            {"n":"door_open","t":-60,"vb":true},
+            {"n":"firmware","t":-60,"vs":"1.2.0"},
+            {"n":"energy","u":"J","t":-60,"s":5}
+        ]"#;
+        let readings = to_readings(pack, "AIR-042", NOW).unwrap();
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0], SensorReading::new("AIR-042", NOW, vec![Measurement::number("co2", "ppm", 415.0)]));
+        assert_eq!(readings[1].timestamp, NOW - 60);
+        assert_eq!(readings[1].number("co2"), Some(412.0));
+        assert_eq!(readings[1].value("door_open"), Some(&MetricValue::Bool(true)));
+        assert_eq!(readings[1].value("firmware"), Some(&MetricValue::Text("1.2.0".to_string())));
+        assert_eq!(readings[1].number("energy"), Some(5.0));
+    }
+
+    #[test]
+    fn test_cbor_pack() {
+        // [{-2: "ESP32-001/", -3: 1700000000, 0: "temperature", 1: "Cel", 2: 21.5}]
+        let mut cbor = Vec::new();
+        let record = ciborium::Value::Map(vec![
+            (ciborium::Value::from(-2), ciborium::Value::from("ESP32-001/")),
+            (ciborium::Value::from(-3), ciborium::Value::from(1_700_000_000)),
+            (ciborium::Value::from(0), ciborium::Value::from("temperature")),
+            (ciborium::Value::from(1), ciborium::Value::from("Cel")),
+            (ciborium::Value::from(2), ciborium::Value::from(21.5)),
+        ]);
+        ciborium::into_writer(&ciborium::Value::Array(vec![record]), &mut cbor).unwrap();
+
+        let readings = to_readings(&cbor, "ESP32-001", NOW).unwrap();
+        assert_eq!(readings, vec![SensorReading::new("ESP32-001", NOW, vec![Measurement::number("temperature", "Cel", 21.5)])]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_packs() {
+        for pack in [
+            &br#"[]"#[..],
+            br#"[{"n":"co2"}]"#,
+            br#"[{"n":"co2","v":1,"vb":true}]"#,
+            br#"[{"n":"blob","vd":"aGVsbG8"}]"#,
+            br#"[{"bver":11,"n":"co2","v":1}]"#,
+            br#"[{"n":"co2","v":1,"foo_":1}]"#,
+            br#"[{"v":1}]"#,
+            br#"{"n":"co2","v":1}"#,
+            b"",
+        ] {
+            assert!(to_readings(pack, "AIR-042", NOW).is_err(), "{}", String::from_utf8_lossy(pack));
+        }
+
+        // Los campos desconocidos sin `_` se ignoran
+        assert!(to_readings(br#"[{"n":"co2","u":"ppm","v":1,"ut":60}]"#, "AIR-042", NOW).is_ok());
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{Result, anyhow};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use gateway::index_store::{IndexStore, IndexedReading};
+use gateway::key_registry::KeyRegistry;
+use gateway::reader::{OnChainReading, RegistryReader, SubmittedEvent};
+
+/// Lo que el indexador necesita de la cadena (el contrato vía RPC, o una cadena falsa en los tests)
+pub trait Chain {
+    async fn head(&self) -> Result<u64>;
+    async fn block_hash(&self, number: u64) -> Result<Option<String>>;
+    async fn events(&self, from: u64, to: u64) -> Result<Vec<SubmittedEvent>>;
+    async fn reading(&self, index: u64, block: u64) -> Result<OnChainReading>;
+}
+
+impl Chain for RegistryReader {
+    async fn head(&self) -> Result<u64> {
+        self.head_block().await
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<String>> {
+        RegistryReader::block_hash(self, number).await
+    }
+
+    async fn events(&self, from: u64, to: u64) -> Result<Vec<SubmittedEvent>> {
+        self.submitted_events(from, to).await
+    }
+
+    async fn reading(&self, index: u64, block: u64) -> Result<OnChainReading> {
+        self.reading_at(index, block).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    /// Primer bloque a indexar si el índice está vacío (el del despliegue del contrato)
+    pub start_block: u64,
+    /// Bloques por detrás de la cabeza que se dejan sin indexar
+    pub confirmations: u64,
+    /// Tamaño máximo del rango de cada `eth_getLogs`
+    pub batch_blocks: u64,
+    /// Bloques indexados que se comparan con la cadena en cada pasada
+    pub reorg_depth: u64,
+}
+
+/// Resultado de una pasada de sincronización
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Bloque al que se volvió por un reorg
+    pub rolled_back_to: Option<u64>,
+    pub indexed: usize,
+    pub cursor: Option<u64>,
+}
+
+/// Replica los eventos `SensorDataSubmitted` (y las lecturas descifradas) en el `IndexStore`
+pub struct Indexer<C> {
+    chain: C,
+    store: Arc<IndexStore>,
+    keys: KeyRegistry,
+    config: IndexerConfig,
+}
+
+impl<C: Chain> Indexer<C> {
+    pub fn new(chain: C, store: Arc<IndexStore>, keys: KeyRegistry, config: IndexerConfig) -> Self {
+        Self { chain, store, keys, config }
+    }
+
+    /// Deshace lo indexado si la cadena se reorganizó y avanza hasta la cabeza menos las confirmaciones
+    pub async fn sync(&self) -> Result<SyncReport> {
+        let mut report = SyncReport::default();
+
+        if let Some(fork) = self.find_fork().await? {
+            let removed = self.store.rollback_to(fork)?;
+            warn!("🔀 Reorg detected: rolled back to block {} ({} readings removed)", fork, removed);
+            report.rolled_back_to = Some(fork);
+        }
+
+        let target = self.chain.head().await?.saturating_sub(self.config.confirmations);
+        let mut from = self.store.cursor()?.map_or(self.config.start_block, |cursor| cursor + 1);
+
+        while from <= target {
+            let to = target.min(from + self.config.batch_blocks.max(1) - 1);
+            let events = self.chain.events(from, to).await?;
+
+            let mut readings = Vec::with_capacity(events.len());
+            for event in events {
+                readings.push(self.index_event(event).await?);
+            }
+
+            let to_hash = self.chain
+                .block_hash(to)
+                .await?
+                .ok_or_else(|| anyhow!("Block {} not found", to))?;
+            self.store.commit(to, &to_hash, &readings)?;
+
+            if !readings.is_empty() {
+                info!("📥 Indexed {} readings in blocks {}..={}", readings.len(), from, to);
+            }
+            report.indexed += readings.len();
+            from = to + 1;
+        }
+
+        report.cursor = self.store.cursor()?;
+        Ok(report)
+    }
+
+    /// Último bloque indexado que sigue en la cadena, si alguno posterior ya no lo está
+    async fn find_fork(&self) -> Result<Option<u64>> {
+        let recent = self.store.recent_blocks(self.config.reorg_depth)?;
+
+        for (position, (number, hash)) in recent.iter().enumerate() {
+            if self.chain.block_hash(*number).await?.as_deref() == Some(hash.as_str()) {
+                return Ok((position > 0).then_some(*number));
+            }
+        }
+
+        // Ninguno coincide: el reorg es más profundo que `reorg_depth`
+        Ok(recent.last().map(|(oldest, _)| {
+            warn!("⚠️ Reorg deeper than {} indexed blocks", self.config.reorg_depth);
+            oldest.saturating_sub(1)
+        }))
+    }
+
+    /// Lee la lectura del evento en su bloque y la descifra; los fallos de descifrado
+    /// se guardan con la lectura para no bloquear el índice
+    async fn index_event(&self, event: SubmittedEvent) -> Result<IndexedReading> {
+        let reading = self.chain.reading(event.index, event.block_number).await?;
+        let data = reading.decrypt(&self.keys).map_err(|e| {
+            warn!("⚠️ Failed to decrypt reading {}: {}", event.index, e);
+            e.to_string()
+        });
+
+        Ok(IndexedReading {
+            index: event.index,
+            device_id: reading.device_id,
+            timestamp: reading.timestamp,
+            block_number: event.block_number,
+            block_hash: event.block_hash,
+            tx_hash: event.tx_hash,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bae_core::reading::SensorReading;
+    use gateway::crypto::CryptoHandler;
+    use gateway::envelope::EnvelopeFormat;
+    use gateway::keyring::Keyring;
+    use std::sync::Mutex;
+
+    /// Cadena en memoria: un hash por bloque y las lecturas emitidas en cada uno
+    #[derive(Default)]
+    struct FakeChain {
+        blocks: Mutex<Vec<(String, Vec<OnChainReading>)>>,
+    }
+
+    impl FakeChain {
+        fn push_block(&self, hash: &str, readings: Vec<OnChainReading>) {
+            self.blocks.lock().unwrap().push((hash.to_string(), readings));
+        }
+
+        /// Sustituye los bloques desde `number` (reorg)
+        fn truncate(&self, number: u64) {
+            self.blocks.lock().unwrap().truncate(number as usize);
+        }
+    }
+
+    impl Chain for Arc<FakeChain> {
+        async fn head(&self) -> Result<u64> {
+            Ok(self.blocks.lock().unwrap().len() as u64 - 1)
+        }
+
+        async fn block_hash(&self, number: u64) -> Result<Option<String>> {
+            Ok(self.blocks.lock().unwrap().get(number as usize).map(|(hash, _)| hash.clone()))
+        }
+
+        async fn events(&self, from: u64, to: u64) -> Result<Vec<SubmittedEvent>> {
+            let blocks = self.blocks.lock().unwrap();
+            Ok((from..=to)
+                .flat_map(|number| {
+                    let (hash, readings) = &blocks[number as usize];
+                    readings.iter().map(move |reading| SubmittedEvent {
+                        index: reading.index,
+                        block_number: number,
+                        block_hash: hash.clone(),
+                        tx_hash: format!("0xt{}", reading.index),
+                    })
+                })
+                .collect())
+        }
+
+        async fn reading(&self, index: u64, block: u64) -> Result<OnChainReading> {
+            self.blocks.lock().unwrap()[block as usize]
+                .1
+                .iter()
+                .find(|reading| reading.index == index)
+                .cloned()
+                .ok_or_else(|| anyhow!("Invalid index"))
+        }
+    }
+
+    fn keys() -> KeyRegistry {
+        KeyRegistry::new().with_keyring(&Keyring::from_master_key(&"ab".repeat(32)).unwrap()).unwrap()
+    }
+
+    fn on_chain(index: u64, temperature: f64) -> OnChainReading {
+        let reading = SensorReading::climate("ESP32-001", temperature, 50.0, 1_700_000_000 + index);
+        let handler = keys().handler_for("ESP32-001").unwrap();
+        let aad = CryptoHandler::reading_aad(&reading.device_id, reading.timestamp);
+        let payload = handler.seal_reading(&reading, EnvelopeFormat::Binary, &aad).unwrap();
+
+        OnChainReading {
+            index,
+            device_id: reading.device_id,
+            ciphertext: payload.ciphertext,
+            nonce: payload.nonce,
+            signature: Vec::new(),
+            timestamp: reading.timestamp,
+            block_number: 0,
+        }
+    }
+
+    fn indexer(chain: &Arc<FakeChain>, store: &Arc<IndexStore>) -> Indexer<Arc<FakeChain>> {
+        let config = IndexerConfig { start_block: 0, confirmations: 0, batch_blocks: 2, reorg_depth: 16 };
+        Indexer::new(chain.clone(), store.clone(), keys(), config)
+    }
+
+    #[tokio::test]
+    async fn test_backfills_and_decrypts() {
+        let chain = Arc::new(FakeChain::default());
+        chain.push_block("0xa0", vec![]);
+        chain.push_block("0xa1", vec![on_chain(0, 21.0), on_chain(1, 22.0)]);
+        chain.push_block("0xa2", vec![]);
+        chain.push_block("0xa3", vec![on_chain(2, 23.0)]);
+        let store = Arc::new(IndexStore::open_in_memory().unwrap());
+
+        let report = indexer(&chain, &store).sync().await.unwrap();
+        assert_eq!(report, SyncReport { rolled_back_to: None, indexed: 3, cursor: Some(3) });
+
+        let latest = store.latest().unwrap().unwrap();
+        assert_eq!(latest.block_number, 3);
+        assert_eq!(latest.data.unwrap().temperature(), Some(23.0));
+
+        // Sin bloques nuevos no hace nada
+        let report = indexer(&chain, &store).sync().await.unwrap();
+        assert_eq!(report, SyncReport { rolled_back_to: None, indexed: 0, cursor: Some(3) });
+    }
+
+    #[tokio::test]
+    async fn test_rolls_back_reorged_blocks() {
+        let chain = Arc::new(FakeChain::default());
+        chain.push_block("0xa0", vec![]);
+        chain.push_block("0xa1", vec![on_chain(0, 21.0)]);
+        chain.push_block("0xa2", vec![on_chain(1, 22.0)]);
+        let store = Arc::new(IndexStore::open_in_memory().unwrap());
+        indexer(&chain, &store).sync().await.unwrap();
+
+        // El bloque 2 se sustituye por otro con una lectura distinta en el mismo índice
+        chain.truncate(2);
+        chain.push_block("0xb2", vec![]);
+        chain.push_block("0xb3", vec![on_chain(1, 30.0)]);
+
+        let report = indexer(&chain, &store).sync().await.unwrap();
+        assert_eq!(report, SyncReport { rolled_back_to: Some(1), indexed: 1, cursor: Some(3) });
+
+        let reading = store.get(1).unwrap().unwrap();
+        assert_eq!((reading.block_number, reading.block_hash.as_str()), (3, "0xb3"));
+        assert_eq!(reading.data.unwrap().temperature(), Some(30.0));
+        assert_eq!(store.count().unwrap(), 2);
+    }
+}
@@ -0,0 +1,107 @@
+mod indexer;
+
+use anyhow::Result;
+use clap::Parser;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use gateway::index_store::IndexStore;
+use gateway::key_registry::KeyRegistry;
+use gateway::keyring::Keyring;
+use gateway::reader::RegistryReader;
+use gateway::secrets::SecretStore;
+
+use indexer::{Indexer, IndexerConfig};
+
+#[derive(Parser)]
+#[command(name = "bae-indexer", version, about = "Replica en SQLite las lecturas de BaeSensorRegistry para consultarlas sin el RPC")]
+struct Cli {
+    /// Endpoint JSON-RPC
+    #[arg(long, env = "RPC_URL")]
+    rpc_url: String,
+    /// Dirección del contrato
+    #[arg(long, env = "CONTRACT_ADDRESS")]
+    contract: String,
+    /// Base de datos del índice (la misma que lee `bae-api` con `INDEX_DB`)
+    #[arg(long, env = "INDEX_DB", default_value = "bae-index.db")]
+    db: String,
+    /// Bloque desde el que empezar si el índice está vacío (el del despliegue del contrato)
+    #[arg(long, env = "INDEX_START_BLOCK", default_value_t = 0)]
+    start_block: u64,
+    /// Bloques por detrás de la cabeza que no se indexan todavía
+    #[arg(long, env = "INDEX_CONFIRMATIONS", default_value_t = 2)]
+    confirmations: u64,
+    /// Bloques por consulta de eventos (`eth_getLogs`)
+    #[arg(long, env = "INDEX_BATCH_BLOCKS", default_value_t = 1000)]
+    batch_blocks: u64,
+    /// Bloques indexados que se revisan en cada pasada para detectar reorgs
+    #[arg(long, env = "INDEX_REORG_DEPTH", default_value_t = 64)]
+    reorg_depth: u64,
+    /// Segundos entre pasadas
+    #[arg(long, env = "INDEX_POLL_SECS", default_value_t = 10)]
+    poll_secs: u64,
+    /// Sincroniza una vez y termina (backfill)
+    #[arg(long)]
+    once: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("bae_indexer=info".parse().unwrap())
+        )
+        .init();
+
+    let cli = Cli::parse();
+    let reader = RegistryReader::new(&cli.rpc_url, &cli.contract)?;
+
+    info!("🗄️  Opening index: {}", cli.db);
+    let store = Arc::new(IndexStore::open(&cli.db)?);
+    let config = IndexerConfig {
+        start_block: cli.start_block,
+        confirmations: cli.confirmations,
+        batch_blocks: cli.batch_blocks,
+        reorg_depth: cli.reorg_depth,
+    };
+    let indexer = Indexer::new(reader, store.clone(), load_keys()?, config);
+
+    info!("🚀 Indexing {} from block {}", cli.contract, store.cursor()?.map_or(cli.start_block, |cursor| cursor + 1));
+    if cli.once {
+        let report = indexer.sync().await?;
+        info!("✅ Indexed {} readings (cursor: {:?})", report.indexed, report.cursor);
+        return Ok(());
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(cli.poll_secs.max(1)));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Un fallo del RPC no detiene el indexador: se reintenta en la siguiente pasada
+                if let Err(e) = indexer.sync().await {
+                    error!("❌ Sync failed: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("👋 Shutting down indexer");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Claves para descifrar: las mismas fuentes que usa el gateway
+/// (`KEYRING_PATH` o `ENCRYPTION_KEY`, y `KEYSTORE_PATH` para claves propias)
+fn load_keys() -> Result<KeyRegistry> {
+    let secrets = SecretStore::from_env()?;
+    let mut keys = KeyRegistry::new().with_keyring(&Keyring::load_configured(&secrets)?)?;
+    if let Ok(path) = std::env::var("KEYSTORE_PATH") {
+        keys = keys.with_keystore(path)?;
+    }
+
+    Ok(keys)
+}
@@ -3,6 +3,20 @@ pragma solidity ^0.8.28;
 
 contract BaeSensorRegistry {
     
+    // Roles: el owner gestiona operadores y gateways; los operadores (y el owner)
+    // dan de alta y revocan dispositivos; sólo los gateways autorizados envían lecturas
+    address public owner;
+    mapping(address => bool) public operators;
+    mapping(address => bool) public authorizedGateways;
+    
+    struct Device {
+        address owner;
+        bool active;
+        uint256 registeredAt;
+    }
+    
+    mapping(string => Device) public devices;
+    
     struct SensorData {
         string deviceId;
         bytes ciphertext;
@@ -15,6 +29,9 @@ contract BaeSensorRegistry {
     SensorData[] public allReadings;
     uint256 public totalReadings;
     
+    // Posiciones en allReadings de las lecturas de cada dispositivo, en orden de llegada
+    mapping(string => uint256[]) private deviceReadings;
+    
     event SensorDataSubmitted(
         string indexed deviceId,
         uint256 timestamp,
@@ -22,13 +39,116 @@ contract BaeSensorRegistry {
         uint256 index
     );
     
+    event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
+    event OperatorUpdated(address indexed account, bool enabled);
+    event GatewayUpdated(address indexed account, bool enabled);
+    event DeviceRegistered(string indexed deviceId, address indexed owner);
+    event DeviceRevoked(string indexed deviceId);
+    
+    modifier onlyOwner() {
+        require(msg.sender == owner, "Not owner");
+        _;
+    }
+    
+    modifier onlyOperator() {
+        require(msg.sender == owner || operators[msg.sender], "Not operator");
+        _;
+    }
+    
+    modifier onlyGateway() {
+        require(authorizedGateways[msg.sender], "Not authorized gateway");
+        _;
+    }
+    
+    constructor() {
+        owner = msg.sender;
+        emit OwnershipTransferred(address(0), msg.sender);
+    }
+    
+    function transferOwnership(address newOwner) external onlyOwner {
+        require(newOwner != address(0), "Invalid owner");
+        emit OwnershipTransferred(owner, newOwner);
+        owner = newOwner;
+    }
+    
+    function setOperator(address account, bool enabled) external onlyOwner {
+        operators[account] = enabled;
+        emit OperatorUpdated(account, enabled);
+    }
+    
+    function setGateway(address account, bool enabled) external onlyOwner {
+        authorizedGateways[account] = enabled;
+        emit GatewayUpdated(account, enabled);
+    }
+    
+    // Un dispositivo revocado puede volver a registrarse (p. ej. con otro dueño);
+    // sus lecturas anteriores se conservan
+    function registerDevice(string memory deviceId, address deviceOwner) external onlyOperator {
+        require(bytes(deviceId).length > 0 && bytes(deviceId).length <= 100, "Invalid device id");
+        require(deviceOwner != address(0), "Invalid owner");
+        require(!devices[deviceId].active, "Device already registered");
+        
+        devices[deviceId] = Device({
+            owner: deviceOwner,
+            active: true,
+            registeredAt: block.timestamp
+        });
+        
+        emit DeviceRegistered(deviceId, deviceOwner);
+    }
+    
+    function revokeDevice(string memory deviceId) external onlyOperator {
+        require(devices[deviceId].active, "Device not registered");
+        devices[deviceId].active = false;
+        
+        emit DeviceRevoked(deviceId);
+    }
+    
+    function isDeviceActive(string memory deviceId) external view returns (bool) {
+        return devices[deviceId].active;
+    }
+    
     function submitSensorData(
         string memory deviceId,
         bytes memory ciphertext,
         bytes memory nonce,
         bytes memory signature,
         uint256 timestamp
-    ) external {
+    ) external onlyGateway {
+        _storeReading(deviceId, ciphertext, nonce, signature, timestamp);
+    }
+    
+    function submitSensorDataBatch(
+        string[] memory deviceIds,
+        bytes[] memory ciphertexts,
+        bytes[] memory nonces,
+        bytes[] memory signatures,
+        uint256[] memory timestamps
+    ) external onlyGateway {
+        uint256 count = deviceIds.length;
+        require(count > 0, "Empty batch");
+        require(
+            ciphertexts.length == count &&
+            nonces.length == count &&
+            signatures.length == count &&
+            timestamps.length == count,
+            "Length mismatch"
+        );
+        
+        for (uint256 i = 0; i < count; i++) {
+            _storeReading(deviceIds[i], ciphertexts[i], nonces[i], signatures[i], timestamps[i]);
+        }
+    }
+    
+    function _storeReading(
+        string memory deviceId,
+        bytes memory ciphertext,
+        bytes memory nonce,
+        bytes memory signature,
+        uint256 timestamp
+    ) internal {
+        require(devices[deviceId].active, "Device not registered");
+        
         SensorData memory data = SensorData({
             deviceId: deviceId,
             ciphertext: ciphertext,
@@ -40,6 +160,7 @@ contract BaeSensorRegistry {
         
         allReadings.push(data);
         totalReadings++;
+        deviceReadings[deviceId].push(allReadings.length - 1);
         
         emit SensorDataSubmitted(
             deviceId,
@@ -74,4 +195,34 @@ contract BaeSensorRegistry {
         require(index < allReadings.length, "Invalid index");
         return allReadings[index];
     }
+    
+    function getDeviceReadingCount(string memory deviceId)
+        external
+        view
+        returns (uint256)
+    {
+        return deviceReadings[deviceId].length;
+    }
+    
+    // i-ésima lectura del dispositivo y su posición en allReadings
+    function getDeviceReading(string memory deviceId, uint256 i)
+        external
+        view
+        returns (SensorData memory data, uint256 index)
+    {
+        require(i < deviceReadings[deviceId].length, "Invalid index");
+        index = deviceReadings[deviceId][i];
+        data = allReadings[index];
+    }
+    
+    function getLatestReadingForDevice(string memory deviceId)
+        external
+        view
+        returns (SensorData memory data, uint256 index)
+    {
+        uint256 count = deviceReadings[deviceId].length;
+        require(count > 0, "No readings");
+        index = deviceReadings[deviceId][count - 1];
+        data = allReadings[index];
+    }
 }
@@ -15,8 +15,14 @@ async function main() {
   fs.writeFileSync('../CONTRACT_ADDRESS.txt', address);
   console.log("💾 Address saved to CONTRACT_ADDRESS.txt");
   
+  // El deployer es el owner; autoriza al gateway (por defecto, la misma cuenta)
+  const [deployer] = await ethers.getSigners();
+  const gateway = process.env.GATEWAY_ADDRESS || deployer.address;
+  await (await registry.setGateway(gateway, true)).wait();
+  console.log("🔐 Authorized gateway:", gateway);
+  
   console.log("\n✅ Contract ready!");
-  console.log("   Devices will auto-register on first data submission");
+  console.log("   Register devices before submitting data: gateway chain register-device <id> --owner <address>");
 }
 
 main()
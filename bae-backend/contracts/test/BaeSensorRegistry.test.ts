@@ -0,0 +1,251 @@
+import { expect } from "chai";
+import { ethers } from "hardhat";
+
+// Lectura cifrada de ejemplo: el contrato no interpreta el contenido
+const CIPHERTEXT = "0xe101" + "ab".repeat(30);
+const NONCE = "0x" + "00".repeat(12);
+const SIGNATURE = "0x" + "cd".repeat(64);
+const TIMESTAMP = 1_700_000_000;
+
+describe("BaeSensorRegistry", function () {
+  // Owner (deployer), un operador, un gateway autorizado, el dueño de ESP32-001 y un extraño
+  async function deploy() {
+    const [owner, operator, gateway, deviceOwner, stranger] = await ethers.getSigners();
+    const registry = await ethers.deployContract("BaeSensorRegistry");
+    await registry.waitForDeployment();
+
+    await (await registry.setOperator(operator.address, true)).wait();
+    await (await registry.setGateway(gateway.address, true)).wait();
+    await (await registry.connect(operator).registerDevice("ESP32-001", deviceOwner.address)).wait();
+
+    return { registry, owner, operator, gateway, deviceOwner, stranger };
+  }
+
+  describe("Roles", function () {
+    it("makes the deployer the owner", async function () {
+      const { registry, owner } = await deploy();
+      expect(await registry.owner()).to.equal(owner.address);
+    });
+
+    it("only lets the owner manage operators and gateways", async function () {
+      const { registry, operator, gateway, stranger } = await deploy();
+
+      for (const caller of [operator, gateway, stranger]) {
+        await expect(registry.connect(caller).setOperator(stranger.address, true)).to.be.revertedWith("Not owner");
+        await expect(registry.connect(caller).setGateway(stranger.address, true)).to.be.revertedWith("Not owner");
+        await expect(registry.connect(caller).transferOwnership(stranger.address)).to.be.revertedWith("Not owner");
+      }
+
+      await expect(registry.setGateway(stranger.address, true))
+        .to.emit(registry, "GatewayUpdated")
+        .withArgs(stranger.address, true);
+      expect(await registry.authorizedGateways(stranger.address)).to.equal(true);
+    });
+
+    it("transfers ownership", async function () {
+      const { registry, owner, stranger } = await deploy();
+
+      await expect(registry.transferOwnership(ethers.ZeroAddress)).to.be.revertedWith("Invalid owner");
+      await expect(registry.transferOwnership(stranger.address))
+        .to.emit(registry, "OwnershipTransferred")
+        .withArgs(owner.address, stranger.address);
+
+      await expect(registry.setGateway(owner.address, true)).to.be.revertedWith("Not owner");
+      await (await registry.connect(stranger).setGateway(owner.address, true)).wait();
+      expect(await registry.authorizedGateways(owner.address)).to.equal(true);
+    });
+
+    it("only lets the owner and operators register and revoke devices", async function () {
+      const { registry, owner, operator, gateway, deviceOwner, stranger } = await deploy();
+
+      for (const caller of [gateway, deviceOwner, stranger]) {
+        await expect(registry.connect(caller).registerDevice("NODE-7", deviceOwner.address)).to.be.revertedWith("Not operator");
+        await expect(registry.connect(caller).revokeDevice("ESP32-001")).to.be.revertedWith("Not operator");
+      }
+
+      await expect(registry.connect(owner).registerDevice("NODE-7", deviceOwner.address))
+        .to.emit(registry, "DeviceRegistered");
+      await expect(registry.connect(operator).revokeDevice("NODE-7"))
+        .to.emit(registry, "DeviceRevoked");
+
+      // Un operador retirado pierde el permiso
+      await (await registry.setOperator(operator.address, false)).wait();
+      await expect(registry.connect(operator).registerDevice("NODE-8", deviceOwner.address)).to.be.revertedWith("Not operator");
+    });
+
+    it("only accepts readings from authorized gateways", async function () {
+      const { registry, owner, operator, gateway, stranger } = await deploy();
+
+      // Ni siquiera el owner u operadores envían lecturas sin el rol de gateway
+      for (const caller of [owner, operator, stranger]) {
+        await expect(
+          registry.connect(caller).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP)
+        ).to.be.revertedWith("Not authorized gateway");
+        await expect(
+          registry.connect(caller).submitSensorDataBatch(["ESP32-001"], [CIPHERTEXT], [NONCE], [SIGNATURE], [TIMESTAMP])
+        ).to.be.revertedWith("Not authorized gateway");
+      }
+
+      await expect(registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP))
+        .to.emit(registry, "SensorDataSubmitted");
+      expect(await registry.getReadingCount()).to.equal(1n);
+
+      // Gateway retirado
+      await (await registry.setGateway(gateway.address, false)).wait();
+      await expect(
+        registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP)
+      ).to.be.revertedWith("Not authorized gateway");
+    });
+  });
+
+  describe("Devices", function () {
+    it("rejects readings of unregistered devices", async function () {
+      const { registry, gateway } = await deploy();
+
+      await expect(
+        registry.connect(gateway).submitSensorData("ROGUE-1", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP)
+      ).to.be.revertedWith("Device not registered");
+    });
+
+    it("validates registrations", async function () {
+      const { registry, operator, deviceOwner } = await deploy();
+
+      await expect(registry.connect(operator).registerDevice("", deviceOwner.address)).to.be.revertedWith("Invalid device id");
+      await expect(registry.connect(operator).registerDevice("X".repeat(101), deviceOwner.address)).to.be.revertedWith("Invalid device id");
+      await expect(registry.connect(operator).registerDevice("NODE-7", ethers.ZeroAddress)).to.be.revertedWith("Invalid owner");
+      await expect(registry.connect(operator).registerDevice("ESP32-001", deviceOwner.address)).to.be.revertedWith("Device already registered");
+      await expect(registry.connect(operator).revokeDevice("NODE-7")).to.be.revertedWith("Device not registered");
+    });
+
+    it("reverts a whole batch that includes a revoked device", async function () {
+      const { registry, operator, gateway, deviceOwner } = await deploy();
+      await (await registry.connect(operator).registerDevice("AIR-042", deviceOwner.address)).wait();
+      await (await registry.connect(operator).revokeDevice("AIR-042")).wait();
+      expect(await registry.isDeviceActive("AIR-042")).to.equal(false);
+
+      await expect(
+        registry.connect(gateway).submitSensorDataBatch(
+          ["ESP32-001", "AIR-042"],
+          [CIPHERTEXT, CIPHERTEXT],
+          [NONCE, NONCE],
+          [SIGNATURE, SIGNATURE],
+          [TIMESTAMP, TIMESTAMP]
+        )
+      ).to.be.revertedWith("Device not registered");
+      expect(await registry.getReadingCount()).to.equal(0n);
+
+      // Sin él, el lote entra completo
+      await (
+        await registry.connect(gateway).submitSensorDataBatch(
+          ["ESP32-001", "ESP32-001"],
+          [CIPHERTEXT, CIPHERTEXT],
+          [NONCE, NONCE],
+          [SIGNATURE, SIGNATURE],
+          [TIMESTAMP, TIMESTAMP + 30]
+        )
+      ).wait();
+      expect(await registry.getReadingCount()).to.equal(2n);
+    });
+
+    it("allows re-registering a revoked device, keeping its old readings", async function () {
+      const { registry, operator, gateway, deviceOwner, stranger } = await deploy();
+      await (await registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP)).wait();
+
+      await (await registry.connect(operator).revokeDevice("ESP32-001")).wait();
+      await expect(
+        registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP + 30)
+      ).to.be.revertedWith("Device not registered");
+
+      // Vuelve con otro dueño
+      await expect(registry.connect(operator).registerDevice("ESP32-001", stranger.address))
+        .to.emit(registry, "DeviceRegistered");
+      const device = await registry.devices("ESP32-001");
+      expect(device.owner).to.equal(stranger.address);
+      expect(device.owner).to.not.equal(deviceOwner.address);
+      expect(device.active).to.equal(true);
+
+      await (await registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP + 60)).wait();
+      expect(await registry.getReadingCount()).to.equal(2n);
+      expect((await registry.getReading(0)).timestamp).to.equal(BigInt(TIMESTAMP));
+    });
+  });
+
+  describe("Per-device readings", function () {
+    // Mezcla envíos sueltos y en lote de dos dispositivos:
+    // global 0: ESP32-001, 1: AIR-042, 2: ESP32-001, 3: AIR-042, 4: ESP32-001
+    async function deployWithReadings() {
+      const fixture = await deploy();
+      const { registry, operator, gateway, deviceOwner } = fixture;
+      await (await registry.connect(operator).registerDevice("AIR-042", deviceOwner.address)).wait();
+      await (await registry.connect(operator).registerDevice("NODE-7", deviceOwner.address)).wait();
+
+      await (await registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP)).wait();
+      await (
+        await registry.connect(gateway).submitSensorDataBatch(
+          ["AIR-042", "ESP32-001", "AIR-042"],
+          [CIPHERTEXT, CIPHERTEXT, CIPHERTEXT],
+          [NONCE, NONCE, NONCE],
+          [SIGNATURE, SIGNATURE, SIGNATURE],
+          [TIMESTAMP + 1, TIMESTAMP + 30, TIMESTAMP + 31]
+        )
+      ).wait();
+      await (await registry.connect(gateway).submitSensorData("ESP32-001", CIPHERTEXT, NONCE, SIGNATURE, TIMESTAMP + 60)).wait();
+
+      return fixture;
+    }
+
+    it("indexes each device's readings across single and batch submissions", async function () {
+      const { registry } = await deployWithReadings();
+
+      expect(await registry.getReadingCount()).to.equal(5n);
+      expect(await registry.getDeviceReadingCount("ESP32-001")).to.equal(3n);
+      expect(await registry.getDeviceReadingCount("AIR-042")).to.equal(2n);
+      expect(await registry.getDeviceReadingCount("NODE-7")).to.equal(0n);
+
+      const expected: Array<[string, number, number, number]> = [
+        // [dispositivo, i, índice global, timestamp]
+        ["ESP32-001", 0, 0, TIMESTAMP],
+        ["ESP32-001", 1, 2, TIMESTAMP + 30],
+        ["ESP32-001", 2, 4, TIMESTAMP + 60],
+        ["AIR-042", 0, 1, TIMESTAMP + 1],
+        ["AIR-042", 1, 3, TIMESTAMP + 31],
+      ];
+      for (const [deviceId, i, globalIndex, timestamp] of expected) {
+        const [data, index] = await registry.getDeviceReading(deviceId, i);
+        expect(index).to.equal(BigInt(globalIndex));
+        expect(data.deviceId).to.equal(deviceId);
+        expect(data.timestamp).to.equal(BigInt(timestamp));
+
+        // El índice devuelto apunta a la misma lectura en el histórico global
+        expect((await registry.getReading(index)).timestamp).to.equal(BigInt(timestamp));
+      }
+    });
+
+    it("returns the latest reading of each device with its global index", async function () {
+      const { registry } = await deployWithReadings();
+
+      const [esp, espIndex] = await registry.getLatestReadingForDevice("ESP32-001");
+      expect(espIndex).to.equal(4n);
+      expect(esp.timestamp).to.equal(BigInt(TIMESTAMP + 60));
+
+      const [air, airIndex] = await registry.getLatestReadingForDevice("AIR-042");
+      expect(airIndex).to.equal(3n);
+      expect(air.timestamp).to.equal(BigInt(TIMESTAMP + 31));
+    });
+
+    it("reverts on an out-of-range index", async function () {
+      const { registry } = await deployWithReadings();
+
+      await expect(registry.getDeviceReading("ESP32-001", 3)).to.be.revertedWith("Invalid index");
+      await expect(registry.getDeviceReading("NODE-7", 0)).to.be.revertedWith("Invalid index");
+      await expect(registry.getDeviceReading("ROGUE-1", 0)).to.be.revertedWith("Invalid index");
+    });
+
+    it("reverts when a device has no readings", async function () {
+      const { registry } = await deployWithReadings();
+
+      await expect(registry.getLatestReadingForDevice("NODE-7")).to.be.revertedWith("No readings");
+      await expect(registry.getLatestReadingForDevice("ROGUE-1")).to.be.revertedWith("No readings");
+    });
+  });
+});
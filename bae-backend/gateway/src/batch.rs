@@ -0,0 +1,162 @@
+use anyhow::{Result, anyhow};
+use std::time::Duration;
+
+use crate::outbox::{OutboxEntry, OutboxReading};
+
+/// Política de agrupación de lecturas en una sola transacción.
+/// Un lote se envía cuando se llena (lecturas o bytes) o cuando la lectura
+/// más antigua supera la latencia máxima.
+#[derive(Debug, Clone)]
+pub struct BatchPolicy {
+    pub max_readings: usize,
+    pub max_bytes: usize,
+    pub max_latency: Duration,
+}
+
+/// Qué hacer con las lecturas pendientes
+#[derive(Debug, PartialEq, Eq)]
+pub enum BatchDecision {
+    /// Enviar ya las primeras `n` lecturas
+    Submit(usize),
+    /// Esperar como máximo este tiempo a que lleguen más lecturas
+    Wait(Duration),
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        // Por defecto una lectura por transacción (compatible con contratos sin batch)
+        Self {
+            max_readings: 1,
+            max_bytes: 16 * 1024,
+            max_latency: Duration::from_secs(10),
+        }
+    }
+}
+
+impl BatchPolicy {
+    /// Lee `BATCH_MAX_READINGS`, `BATCH_MAX_BYTES` y `BATCH_MAX_LATENCY_SECS`
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let env_usize = |name: &str, default: usize| -> Result<usize> {
+            match std::env::var(name) {
+                Ok(value) => value.parse().map_err(|_| anyhow!("Invalid {}", name)),
+                Err(_) => Ok(default),
+            }
+        };
+
+        let policy = Self {
+            max_readings: env_usize("BATCH_MAX_READINGS", defaults.max_readings)?,
+            max_bytes: env_usize("BATCH_MAX_BYTES", defaults.max_bytes)?,
+            max_latency: Duration::from_secs(
+                env_usize("BATCH_MAX_LATENCY_SECS", defaults.max_latency.as_secs() as usize)? as u64,
+            ),
+        };
+
+        if policy.max_readings == 0 || policy.max_bytes == 0 {
+            return Err(anyhow!("BATCH_MAX_READINGS and BATCH_MAX_BYTES must be greater than zero"));
+        }
+
+        Ok(policy)
+    }
+
+    pub fn is_batching(&self) -> bool {
+        self.max_readings > 1
+    }
+
+    /// Tamaño aproximado que ocupa una lectura en los argumentos de la transacción
+    pub fn reading_size(reading: &OutboxReading) -> usize {
+        reading.device_id.len()
+            + reading.ciphertext.len()
+            + reading.nonce.len()
+            + reading.signature.len()
+            + 32 // timestamp (uint256)
+    }
+
+    /// Decide si enviar ya (y cuántas) o esperar más lecturas.
+    /// `pending` debe venir en orden de llegada; `now` en segundos unix.
+    pub fn decide(&self, pending: &[OutboxEntry], now: u64) -> BatchDecision {
+        let Some(oldest) = pending.first() else {
+            return BatchDecision::Wait(self.max_latency);
+        };
+
+        // Cuántas caben por tamaño (al menos una, aunque exceda max_bytes)
+        let mut bytes = 0;
+        let mut fit = 0;
+        for entry in pending.iter().take(self.max_readings) {
+            bytes += Self::reading_size(&entry.reading);
+            if fit > 0 && bytes > self.max_bytes {
+                break;
+            }
+            fit += 1;
+        }
+
+        let full = fit == self.max_readings || fit < pending.len().min(self.max_readings);
+        let age = Duration::from_secs(now.saturating_sub(oldest.created_at));
+
+        if full || age >= self.max_latency {
+            BatchDecision::Submit(fit)
+        } else {
+            BatchDecision::Wait(self.max_latency - age)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbox::OutboxStatus;
+
+    fn entry(id: i64, ciphertext_len: usize, created_at: u64) -> OutboxEntry {
+        OutboxEntry {
+            id,
+            reading: OutboxReading {
+                device_id: format!("ESP32-{:03}", id),
+                ciphertext: vec![0; ciphertext_len],
+                nonce: vec![0; 12],
+                key_id: String::new(),
+                key_version: 0,
+                signature: vec![0; 32],
+                timestamp: created_at,
+            },
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            tx_hash: None,
+            created_at,
+        }
+    }
+
+    fn policy(max_readings: usize, max_bytes: usize) -> BatchPolicy {
+        BatchPolicy { max_readings, max_bytes, max_latency: Duration::from_secs(10) }
+    }
+
+    #[test]
+    fn test_single_reading_policy_submits_immediately() {
+        let pending = vec![entry(1, 100, 1000), entry(2, 100, 1000)];
+        assert_eq!(BatchPolicy::default().decide(&pending, 1000), BatchDecision::Submit(1));
+    }
+
+    #[test]
+    fn test_waits_until_full_or_latency() {
+        let policy = policy(5, 16 * 1024);
+        let pending = vec![entry(1, 100, 1000), entry(2, 100, 1002)];
+
+        assert_eq!(policy.decide(&pending, 1004), BatchDecision::Wait(Duration::from_secs(6)));
+        assert_eq!(policy.decide(&pending, 1010), BatchDecision::Submit(2));
+
+        let full: Vec<_> = (1..=6).map(|i| entry(i, 100, 1000)).collect();
+        assert_eq!(policy.decide(&full, 1000), BatchDecision::Submit(5));
+    }
+
+    #[test]
+    fn test_max_bytes_limits_batch() {
+        // Cada lectura ocupa 9 + 100 + 12 + 32 + 32 = 185 bytes
+        let policy = policy(10, 400);
+        let pending: Vec<_> = (1..=4).map(|i| entry(i, 100, 1000)).collect();
+        assert_eq!(policy.decide(&pending, 1000), BatchDecision::Submit(2));
+
+        // Una lectura sola siempre sale aunque exceda el límite
+        let policy = self::policy(10, 50);
+        assert_eq!(policy.decide(&pending, 1000), BatchDecision::Submit(1));
+    }
+}
@@ -1,25 +1,211 @@
 use anyhow::{Result, anyhow};
 use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
+use crate::nonce_manager::NonceManager;
+use crate::outbox::OutboxReading;
+use crate::reader::{self, OnChainReading};
+
 abigen!(
     BaeSensorRegistry,
     r#"[
+        struct SensorData { string deviceId; bytes ciphertext; bytes nonce; bytes signature; uint256 timestamp; uint256 blockNumber; }
         function submitSensorData(string memory deviceId, bytes memory ciphertext, bytes memory nonce, bytes memory signature, uint256 timestamp) external
+        function submitSensorDataBatch(string[] memory deviceIds, bytes[] memory ciphertexts, bytes[] memory nonces, bytes[] memory signatures, uint256[] memory timestamps) external
         function getReadingCount() external view returns (uint256)
+        function getReading(uint256 index) external view returns (SensorData memory)
+        function getLatestReading() external view returns (SensorData memory)
         function totalReadings() external view returns (uint256)
+        function getDeviceReadingCount(string memory deviceId) external view returns (uint256)
+        function getDeviceReading(string memory deviceId, uint256 i) external view returns (SensorData memory data, uint256 index)
+        function getLatestReadingForDevice(string memory deviceId) external view returns (SensorData memory data, uint256 index)
+        function owner() external view returns (address)
+        function operators(address account) external view returns (bool)
+        function authorizedGateways(address account) external view returns (bool)
+        function isDeviceActive(string memory deviceId) external view returns (bool)
+        function registerDevice(string memory deviceId, address deviceOwner) external
+        function revokeDevice(string memory deviceId) external
+        function setOperator(address account, bool enabled) external
+        function setGateway(address account, bool enabled) external
         event SensorDataSubmitted(string indexed deviceId, uint256 timestamp, uint256 blockNumber, uint256 index)
+        event DeviceRegistered(string indexed deviceId, address indexed owner)
+        event DeviceRevoked(string indexed deviceId)
     ]"#
 );
 
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// Subidas de gas seguidas ante un "underpriced" antes de rendirse en ese envío
+const MAX_UNDERPRICED_RETRIES: u32 = 3;
+
+/// Cuándo y cómo reemplazar una transacción atascada en el mempool
+#[derive(Debug, Clone)]
+pub struct ReplacementPolicy {
+    /// Tiempo sin confirmación antes de re-emitir con más gas
+    pub stuck_after: Duration,
+    /// Re-emisiones máximas antes de darla por perdida
+    pub max_replacements: u32,
+    /// Incremento del precio del gas en cada re-emisión (los nodos exigen >= 10%)
+    pub gas_bump_percent: u64,
+    /// Frecuencia de consulta de receipts
+    pub poll_interval: Duration,
+}
+
+impl Default for ReplacementPolicy {
+    fn default() -> Self {
+        Self {
+            stuck_after: Duration::from_secs(120),
+            max_replacements: 3,
+            gas_bump_percent: 20,
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ReplacementPolicy {
+    /// Lee `STUCK_TX_SECS`, `MAX_TX_REPLACEMENTS` y `GAS_BUMP_PERCENT`
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let env_u64 = |name: &str, default: u64| -> Result<u64> {
+            match std::env::var(name) {
+                Ok(value) => value.parse().map_err(|_| anyhow!("Invalid {}", name)),
+                Err(_) => Ok(default),
+            }
+        };
+
+        let policy = Self {
+            stuck_after: Duration::from_secs(env_u64("STUCK_TX_SECS", defaults.stuck_after.as_secs())?),
+            max_replacements: env_u64("MAX_TX_REPLACEMENTS", defaults.max_replacements as u64)? as u32,
+            gas_bump_percent: env_u64("GAS_BUMP_PERCENT", defaults.gas_bump_percent)?,
+            poll_interval: defaults.poll_interval,
+        };
+
+        if policy.gas_bump_percent < 10 {
+            return Err(anyhow!("GAS_BUMP_PERCENT must be at least 10"));
+        }
+
+        Ok(policy)
+    }
+}
+
+/// Cómo terminó una transacción confirmada
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxOutcome {
+    /// Se minó la transacción original
+    Mined,
+    /// Se minó una re-emisión con más gas
+    Replaced,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmittedTx {
+    pub tx_hash: String,
+    pub outcome: TxOutcome,
+}
+
+/// Ninguna de las versiones de la transacción se minó tras agotar los reemplazos
+/// y ninguna sigue en el mempool: las lecturas pueden reenviarse sin duplicarse
+#[derive(Debug, thiserror::Error)]
+#[error("Transaction dropped after {attempts} broadcasts (nonce {nonce})")]
+pub struct TxDropped {
+    pub nonce: U256,
+    pub attempts: usize,
+}
+
+/// Roles de una cuenta en el contrato
+#[derive(Debug, Clone, Copy)]
+pub struct AccountRoles {
+    pub owner: bool,
+    pub operator: bool,
+    pub gateway: bool,
+}
+
+/// La wallet del gateway no puede enviar lecturas al contrato
+#[derive(Debug, thiserror::Error)]
+#[error("Wallet {0:?} is not an authorized gateway (the contract owner must run `gateway chain gateway {0:?}`)")]
+pub struct GatewayNotAuthorized(pub Address);
+
+/// Caché de `isDeviceActive`: el gateway rechaza antes de encolar las lecturas de
+/// dispositivos que el contrato no acepta, sin una llamada RPC por mensaje
+pub struct DeviceStatusCache {
+    sender: Option<Arc<BlockchainSender>>,
+    ttl: Duration,
+    entries: std::sync::Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl DeviceStatusCache {
+    pub fn new(sender: Arc<BlockchainSender>, ttl: Duration) -> Self {
+        Self { sender: Some(sender), ttl, entries: Default::default() }
+    }
+
+    /// Estado fijo sin RPC: sólo los dispositivos indicados están registrados
+    pub fn fixed<'a>(active: impl IntoIterator<Item = &'a str>) -> Self {
+        let now = Instant::now();
+        let entries = active.into_iter().map(|device_id| (device_id.to_string(), (true, now))).collect();
+        Self { sender: None, ttl: Duration::MAX, entries: std::sync::Mutex::new(entries) }
+    }
+
+    /// Estado cacheado si no ha caducado; si no, se consulta al contrato
+    pub async fn is_active(&self, device_id: &str) -> Result<bool> {
+        let cached = self.entries.lock().unwrap().get(device_id).copied();
+        match cached {
+            Some((active, checked_at)) if checked_at.elapsed() < self.ttl => Ok(active),
+            _ => self.refresh(device_id).await,
+        }
+    }
+
+    /// Consulta al contrato ignorando la caché (p. ej. tras revertir un lote)
+    pub async fn refresh(&self, device_id: &str) -> Result<bool> {
+        let Some(sender) = &self.sender else {
+            return Ok(self.entries.lock().unwrap().get(device_id).is_some_and(|(active, _)| *active));
+        };
+
+        let active = sender.is_device_active(device_id).await?;
+        self.entries.lock().unwrap().insert(device_id.to_string(), (active, Instant::now()));
+        Ok(active)
+    }
+}
+
+/// Envía lecturas al contrato. Los nonces se asignan localmente, así que varias
+/// transacciones pueden estar en vuelo a la vez sin envolver el sender en un `Mutex`.
 pub struct BlockchainSender {
-    contract: BaeSensorRegistry<SignerMiddleware<Provider<Http>, LocalWallet>>,
-    chain_id: u64,
+    contract: BaeSensorRegistry<Client>,
+    address: Address,
+    nonces: NonceManager,
+    replacement: ReplacementPolicy,
 }
 
 impl BlockchainSender {
-    pub async fn new(rpc_url: &str, contract_address: &str, private_key: &str) -> Result<Self> {
+    /// Conecta y comprueba que la wallet es un gateway autorizado del contrato
+    pub async fn new(
+        rpc_url: &str,
+        contract_address: &str,
+        wallet: LocalWallet,
+        replacement: ReplacementPolicy,
+    ) -> Result<Self> {
+        let sender = Self::connect(rpc_url, contract_address, wallet, replacement).await?;
+        
+        let roles = sender.roles(sender.address).await
+            .map_err(|e| anyhow!("Could not check gateway authorization (contract without access control?): {}", e))?;
+        if !roles.gateway {
+            return Err(GatewayNotAuthorized(sender.address).into());
+        }
+        info!("🔐 Wallet is an authorized gateway");
+        
+        Ok(sender)
+    }
+
+    /// Conecta sin comprobar roles (administración del contrato con la wallet del owner u operador)
+    pub async fn connect(
+        rpc_url: &str,
+        contract_address: &str,
+        wallet: LocalWallet,
+        replacement: ReplacementPolicy,
+    ) -> Result<Self> {
         info!("🔗 Connecting to Paseo Hub...");
         
         // Conectar al provider con timeout
@@ -38,9 +224,6 @@ impl BlockchainSender {
         info!("✅ Connected to chain ID: {}", chain_id);
         
         // Configurar wallet
-        let wallet: LocalWallet = private_key
-            .parse()
-            .map_err(|e| anyhow!("Invalid private key format: {:?}", e))?;
         let wallet = wallet.with_chain_id(chain_id.as_u64());
         
         info!("👛 Wallet address: {:?}", wallet.address());
@@ -57,6 +240,15 @@ impl BlockchainSender {
             warn!("⚠️  Get test tokens from: https://faucet.polkadot.io/paseo");
         }
         
+        // Nonce inicial (incluye transacciones pendientes de ejecuciones anteriores)
+        let wallet_address = wallet.address();
+        let next_nonce = provider
+            .get_transaction_count(wallet_address, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|e| anyhow!("Failed to get account nonce: {}", e))?;
+        
+        info!("🔢 Next nonce: {}", next_nonce);
+        
         // Crear cliente con middleware
         let client = SignerMiddleware::new(provider, wallet);
         let client = Arc::new(client);
@@ -83,7 +275,9 @@ impl BlockchainSender {
         
         Ok(Self { 
             contract,
-            chain_id: chain_id.as_u64(),
+            address: wallet_address,
+            nonces: NonceManager::new(next_nonce),
+            replacement,
         })
     }
 
@@ -94,7 +288,7 @@ impl BlockchainSender {
         nonce: &[u8],
         signature: &[u8],
         timestamp: u64,
-    ) -> Result<String> {
+    ) -> Result<SubmittedTx> {
         info!("📤 Submitting to contract...");
         info!("   Device: {}", device_id);
         info!("   Data size: {} bytes", ciphertext.len());
@@ -115,44 +309,127 @@ impl BlockchainSender {
             timestamp_u256,
         );
         
-        // Estimar gas antes de enviar
-        match call.estimate_gas().await {
-            Ok(gas_estimate) => {
-                info!("⛽ Estimated gas: {}", gas_estimate);
-            }
-            Err(e) => {
-                warn!("⚠️  Could not estimate gas: {}. Proceeding anyway...", e);
-            }
+        self.send_and_confirm(call).await
+    }
+
+    /// Envía varias lecturas (de cualquier dispositivo) en una sola transacción
+    pub async fn submit_sensor_data_batch(&self, readings: &[OutboxReading]) -> Result<SubmittedTx> {
+        if readings.is_empty() {
+            return Err(anyhow!("Empty batch"));
         }
         
-        // Configurar gas price (opcional, usa el default del provider)
-        // let call = call.gas_price(U256::from(1_000_000_000u64)); // 1 Gwei
+        info!("📤 Submitting batch of {} readings to contract...", readings.len());
         
-        // Enviar transacción
-        let pending_tx = call
-            .send()
+        let call = self.contract.submit_sensor_data_batch(
+            readings.iter().map(|r| r.device_id.clone()).collect(),
+            readings.iter().map(|r| Bytes::from(r.ciphertext.clone())).collect(),
+            readings.iter().map(|r| Bytes::from(r.nonce.clone())).collect(),
+            readings.iter().map(|r| Bytes::from(r.signature.clone())).collect(),
+            readings.iter().map(|r| U256::from(r.timestamp)).collect(),
+        );
+        
+        self.send_and_confirm(call).await
+    }
+
+    /// Da de alta un dispositivo on-chain (owner u operador)
+    pub async fn register_device(&self, device_id: &str, owner: Address) -> Result<SubmittedTx> {
+        info!("📝 Registering device {} (owner {:?})...", device_id, owner);
+        self.send_and_confirm(self.contract.register_device(device_id.to_string(), owner)).await
+    }
+
+    /// Revoca un dispositivo: el contrato deja de aceptar sus lecturas (owner u operador)
+    pub async fn revoke_device(&self, device_id: &str) -> Result<SubmittedTx> {
+        info!("🚫 Revoking device {}...", device_id);
+        self.send_and_confirm(self.contract.revoke_device(device_id.to_string())).await
+    }
+
+    /// Autoriza o retira un gateway (sólo el owner)
+    pub async fn set_gateway(&self, account: Address, enabled: bool) -> Result<SubmittedTx> {
+        info!("🔐 Setting gateway {:?} to {}...", account, enabled);
+        self.send_and_confirm(self.contract.set_gateway(account, enabled)).await
+    }
+
+    /// Nombra o retira un operador (sólo el owner)
+    pub async fn set_operator(&self, account: Address, enabled: bool) -> Result<SubmittedTx> {
+        info!("🔐 Setting operator {:?} to {}...", account, enabled);
+        self.send_and_confirm(self.contract.set_operator(account, enabled)).await
+    }
+
+    pub async fn roles(&self, account: Address) -> Result<AccountRoles> {
+        let owner = self.contract.owner().call().await
+            .map_err(|e| anyhow!("Failed to get contract owner: {}", e))?;
+        let operator = self.contract.operators(account).call().await
+            .map_err(|e| anyhow!("Failed to get operator role: {}", e))?;
+        let gateway = self.contract.authorized_gateways(account).call().await
+            .map_err(|e| anyhow!("Failed to get gateway role: {}", e))?;
+        
+        Ok(AccountRoles { owner: owner == account, operator, gateway })
+    }
+
+    pub async fn is_device_active(&self, device_id: &str) -> Result<bool> {
+        self.contract
+            .is_device_active(device_id.to_string())
+            .call()
             .await
-            .map_err(|e| anyhow!("Failed to send transaction: {}", e))?;
-        
-        let tx_hash = format!("{:?}", pending_tx.tx_hash());
-        info!("⏳ TX sent: {}", tx_hash);
-        info!("🔗 Explorer: https://blockscout-passet-hub.parity-testnet.parity.io/tx/{}", tx_hash);
-        
-        // Esperar confirmación con timeout
-        info!("⏳ Waiting for confirmation (max 120s)...");
-        
-        match tokio::time::timeout(
-            tokio::time::Duration::from_secs(120),
-            pending_tx
-        ).await {
-            Ok(Ok(Some(receipt))) => {
-                let confirmed_hash = format!("{:?}", receipt.transaction_hash);
-                let block_number = receipt.block_number.unwrap_or_default();
-                let gas_used = receipt.gas_used.unwrap_or_default();
+            .map_err(|e| anyhow!("Failed to get device status: {}", e))
+    }
+
+    /// Dirección de la wallet que firma las transacciones
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Envía la transacción y la sigue hasta que se mina. Si queda atascada más de
+    /// `stuck_after`, se re-emite con el mismo nonce y más gas (hasta `max_replacements`);
+    /// después se sigue esperando mientras alguna versión esté en el mempool.
+    async fn send_and_confirm(&self, call: ContractCall<Client, ()>) -> Result<SubmittedTx> {
+        let client = self.contract.client();
+        
+        // Reservar nonce y completar gas/precio (EIP-1559 o legacy según la red)
+        let nonce = self.nonces.next();
+        let mut tx = call.tx;
+        tx.set_nonce(nonce);
+        
+        if let Err(e) = client.fill_transaction(&mut tx, None).await {
+            self.nonces.release(nonce);
+            return Err(anyhow!("Failed to prepare transaction: {}", e));
+        }
+        
+        info!("⛽ Estimated gas: {}", tx.gas().copied().unwrap_or_default());
+        
+        let first_hash = match self.broadcast(&mut tx).await {
+            Ok(hash) => hash,
+            Err(message) => {
+                if Self::is_nonce_conflict(&message) {
+                    // Otro proceso usó el nonce: alinearse con la red
+                    self.resync_nonce().await;
+                } else {
+                    // La transacción no llegó a la red: el nonce queda libre
+                    self.nonces.release(nonce);
+                }
+                return Err(anyhow!("Failed to send transaction (nonce {}): {}", nonce, message));
+            }
+        };
+        
+        info!("⏳ TX sent: {:?} (nonce {})", first_hash, nonce);
+        info!("🔗 Explorer: https://blockscout-passet-hub.parity-testnet.parity.io/tx/{:?}", first_hash);
+        
+        // Todas las versiones emitidas con este nonce; cualquiera puede minarse
+        let mut hashes = vec![first_hash];
+        
+        loop {
+            info!("⏳ Waiting for confirmation (max {:?})...", self.replacement.stuck_after);
+            
+            if let Some(receipt) = self.wait_for_any_receipt(&hashes).await? {
+                let outcome = if receipt.transaction_hash == first_hash {
+                    TxOutcome::Mined
+                } else {
+                    TxOutcome::Replaced
+                };
                 
-                info!("✅ Confirmed!");
-                info!("   Block: {}", block_number);
-                info!("   Gas used: {}", gas_used);
+                info!("✅ Confirmed! ({:?})", outcome);
+                info!("   Block: {}", receipt.block_number.unwrap_or_default());
+                info!("   Gas used: {}", receipt.gas_used.unwrap_or_default());
                 info!("   Status: {:?}", receipt.status);
                 
                 // Verificar status de la transacción
@@ -160,23 +437,155 @@ impl BlockchainSender {
                     return Err(anyhow!("Transaction failed on-chain"));
                 }
                 
-                Ok(confirmed_hash)
+                return Ok(SubmittedTx {
+                    tx_hash: format!("{:?}", receipt.transaction_hash),
+                    outcome,
+                });
+            }
+            
+            if hashes.len() as u32 > self.replacement.max_replacements {
+                // Sin más reemplazos. Mientras alguna versión siga en el mempool puede
+                // minarse todavía: si se diera por perdida, el outbox reenviaría las
+                // lecturas con otro nonce y quedarían dos veces on-chain
+                if self.any_in_mempool(&hashes).await {
+                    warn!("⏳ TX still pending after {} broadcasts (nonce {}), keeping track of it", hashes.len(), nonce);
+                    continue;
+                }
+                break;
+            }
+            
+            // Re-emitir con más gas
+            Self::bump_gas(&mut tx, self.replacement.gas_bump_percent);
+            match self.broadcast(&mut tx).await {
+                Ok(hash) => {
+                    warn!("🔁 TX stuck, replaced with {:?} (nonce {}, +{}% gas, attempt {}/{})",
+                        hash, nonce, self.replacement.gas_bump_percent,
+                        hashes.len(), self.replacement.max_replacements);
+                    hashes.push(hash);
+                }
+                Err(e) if Self::is_nonce_conflict(&e) => {
+                    // El nonce ya se consumió: probablemente se minó una versión anterior
+                    warn!("⚠️  Replacement rejected ({}), re-checking receipts", e);
+                }
+                Err(e) => {
+                    warn!("⚠️  Could not broadcast replacement: {}", e);
+                }
+            }
+        }
+        
+        // Ninguna versión se minó ni sigue en el mempool: el nonce está libre
+        self.nonces.release(nonce);
+        
+        warn!("🗑️  TX dropped after {} broadcasts (nonce {})", hashes.len(), nonce);
+        Err(TxDropped { nonce, attempts: hashes.len() }.into())
+    }
+
+    /// Si alguna versión sigue en el mempool (o ya minada). Ante un error del RPC se
+    /// asume que sí: es preferible esperar a arriesgarse a duplicar las lecturas.
+    async fn any_in_mempool(&self, hashes: &[H256]) -> bool {
+        let client = self.contract.client();
+        for hash in hashes {
+            match client.get_transaction(*hash).await {
+                Ok(Some(_)) => return true,
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("⚠️  Could not look up {:?}: {}", hash, e);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Espera hasta `stuck_after` a que se mine alguna de las versiones
+    async fn wait_for_any_receipt(&self, hashes: &[H256]) -> Result<Option<TransactionReceipt>> {
+        let client = self.contract.client();
+        let deadline = tokio::time::Instant::now() + self.replacement.stuck_after;
+        
+        loop {
+            for hash in hashes {
+                match client.get_transaction_receipt(*hash).await {
+                    Ok(Some(receipt)) => return Ok(Some(receipt)),
+                    Ok(None) => {}
+                    Err(e) => warn!("⚠️  Could not fetch receipt for {:?}: {}", hash, e),
+                }
+            }
+            
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(None);
+            }
+            
+            tokio::time::sleep(self.replacement.poll_interval).await;
+        }
+    }
+
+    /// Sube el precio del gas un `percent`% (al menos 1 wei) para poder reemplazar
+    fn bump_gas(tx: &mut TypedTransaction, percent: u64) {
+        let bump = |value: U256| value * (100 + percent) / 100 + 1;
+        
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                inner.max_fee_per_gas = inner.max_fee_per_gas.map(bump);
+                inner.max_priority_fee_per_gas = inner.max_priority_fee_per_gas.map(bump);
             }
-            Ok(Ok(None)) => {
-                warn!("⚠️  Transaction pending (no receipt)");
-                Ok(tx_hash)
+            TypedTransaction::Legacy(inner) => {
+                inner.gas_price = inner.gas_price.map(bump);
             }
-            Ok(Err(e)) => {
-                Err(anyhow!("Transaction failed: {}", e))
+            TypedTransaction::Eip2930(inner) => {
+                inner.tx.gas_price = inner.tx.gas_price.map(bump);
+            }
+        }
+    }
+
+    /// Envía la transacción. Si el nodo la rechaza por precio insuficiente (mínimo de la
+    /// red o regla de reemplazo de otra versión con el mismo nonce) sube el gas y reintenta,
+    /// hasta `MAX_UNDERPRICED_RETRIES` veces. Devuelve el hash o el mensaje de error.
+    async fn broadcast(&self, tx: &mut TypedTransaction) -> Result<H256, String> {
+        let client = self.contract.client();
+        let mut retries = 0;
+        
+        loop {
+            match client.send_transaction(tx.clone(), None).await {
+                Ok(pending_tx) => return Ok(pending_tx.tx_hash()),
+                Err(e) => {
+                    let message = e.to_string();
+                    if !Self::is_underpriced(&message) || retries >= MAX_UNDERPRICED_RETRIES {
+                        return Err(message);
+                    }
+                    retries += 1;
+                    Self::bump_gas(tx, self.replacement.gas_bump_percent);
+                    warn!("💸 TX underpriced ({}), retrying with +{}% gas ({}/{})",
+                        message, self.replacement.gas_bump_percent, retries, MAX_UNDERPRICED_RETRIES);
+                }
             }
-            Err(_) => {
-                warn!("⚠️  Transaction timeout (still might be pending)");
-                Ok(tx_hash)
+        }
+    }
+
+    fn is_nonce_conflict(message: &str) -> bool {
+        let message = message.to_lowercase();
+        message.contains("nonce too low") || message.contains("already known")
+    }
+
+    /// "transaction underpriced" o "replacement transaction underpriced": el nonce sigue
+    /// siendo nuestro, sólo falta precio
+    fn is_underpriced(message: &str) -> bool {
+        message.to_lowercase().contains("underpriced")
+    }
+
+    async fn resync_nonce(&self) {
+        match self.contract
+            .client()
+            .get_transaction_count(self.address, Some(BlockNumber::Pending.into()))
+            .await
+        {
+            Ok(chain_nonce) => {
+                self.nonces.resync(chain_nonce);
+                warn!("🔢 Nonce conflict, next nonce is now {}", self.nonces.peek());
             }
+            Err(e) => warn!("⚠️  Could not resync nonce: {}", e),
         }
     }
 
-    #[allow(dead_code)]
     pub async fn get_reading_count(&self) -> Result<u64> {
         let count = self.contract
             .get_reading_count()
@@ -186,4 +595,54 @@ impl BlockchainSender {
         
         Ok(count.as_u64())
     }
-}
\ No newline at end of file
+
+    pub async fn get_device_reading_count(&self, device_id: &str) -> Result<u64> {
+        reader::device_reading_count(&self.contract, device_id).await
+    }
+
+    /// `i`-ésima lectura del dispositivo (0 es la más antigua)
+    pub async fn get_device_reading(&self, device_id: &str, i: u64) -> Result<OnChainReading> {
+        reader::device_reading(&self.contract, device_id, i).await
+    }
+
+    pub async fn get_latest_reading_for_device(&self, device_id: &str) -> Result<OnChainReading> {
+        reader::latest_reading_for_device(&self.contract, device_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_underpriced_is_not_a_nonce_conflict() {
+        for message in ["replacement transaction underpriced", "(code: -32000, message: transaction underpriced)"] {
+            assert!(BlockchainSender::is_underpriced(message));
+            assert!(!BlockchainSender::is_nonce_conflict(message));
+        }
+        for message in ["nonce too low", "already known"] {
+            assert!(BlockchainSender::is_nonce_conflict(message));
+            assert!(!BlockchainSender::is_underpriced(message));
+        }
+    }
+
+    #[test]
+    fn test_bump_legacy_gas_price() {
+        let mut tx: TypedTransaction = TransactionRequest::new().gas_price(1_000_000_000u64).into();
+        BlockchainSender::bump_gas(&mut tx, 20);
+        assert_eq!(tx.gas_price(), Some(U256::from(1_200_000_001u64)));
+    }
+
+    #[test]
+    fn test_bump_eip1559_fees() {
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(100u64)
+            .max_priority_fee_per_gas(10u64)
+            .into();
+        BlockchainSender::bump_gas(&mut tx, 10);
+
+        let TypedTransaction::Eip1559(inner) = tx else { panic!("expected EIP-1559") };
+        assert_eq!(inner.max_fee_per_gas, Some(U256::from(111)));
+        assert_eq!(inner.max_priority_fee_per_gas, Some(U256::from(12)));
+    }
+}
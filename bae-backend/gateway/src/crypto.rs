@@ -1,21 +1,68 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{Aead, KeyInit, OsRng, Payload},
     Aes256Gcm, Nonce,
 };
 use anyhow::{Result, anyhow};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use serde::{Serialize, Deserialize};
 use sha2::{Sha256, Digest};
 use rand::RngCore;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use bae_core::reading::SensorReading;
+
+use crate::envelope::{BodyFormat, EnvelopeFormat, EnvelopeHeader, KEY_ID_LEN};
+use crate::key_registry::decode_key;
+
+/// Separador de dominio del mensaje firmado (evita reutilizar firmas en otro contexto)
+const SIGNATURE_DOMAIN: &[u8] = b"bae-reading-v1";
+
+/// Versión del formato de los datos asociados (AAD) de una lectura
+pub const AAD_FORMAT_VERSION: u8 = 1;
+
+/// Versión de un handler con una sola clave (sin anillo de claves)
+pub const SINGLE_KEY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EncryptedPayload {
     pub ciphertext: Vec<u8>,
     pub nonce: Vec<u8>,
+    /// Identificador de la clave AES usada (vacío en payloads antiguos)
+    #[serde(default)]
+    pub key_id: String,
+    /// Versión de la clave en el anillo (0 en payloads anteriores a la rotación)
+    #[serde(default)]
+    pub key_version: u32,
 }
 
 #[derive(Clone)]
-pub struct CryptoHandler {
+struct KeySlot {
     cipher: Aes256Gcm,
+    key_id: String,
+}
+
+impl KeySlot {
+    fn new(key_bytes: &[u8; 32]) -> Self {
+        let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key_bytes);
+        Self {
+            cipher: Aes256Gcm::new(key),
+            key_id: CryptoHandler::key_id_for(key_bytes),
+        }
+    }
+
+    fn key_id_bytes(&self) -> Result<[u8; KEY_ID_LEN]> {
+        hex::decode(&self.key_id)?
+            .try_into()
+            .map_err(|_| anyhow!("Key id must be {} bytes", KEY_ID_LEN))
+    }
+}
+
+/// Encripta siempre con la clave activa y descifra con cualquier versión del anillo
+#[derive(Clone)]
+pub struct CryptoHandler {
+    keys: BTreeMap<u32, KeySlot>,
+    active: u32,
+    signing_key: Option<SigningKey>,
 }
 
 impl CryptoHandler {
@@ -35,54 +82,135 @@ impl CryptoHandler {
             .map_err(|e| anyhow!("Invalid hex key: {}. Key must contain only 0-9, a-f characters", e))?;
         
         // Verificar longitud de bytes
-        if key_bytes.len() != 32 {
-            return Err(anyhow!("Key must be exactly 32 bytes, got {}", key_bytes.len()));
-        }
+        let key_bytes: [u8; 32] = key_bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| anyhow!("Key must be exactly 32 bytes, got {}", bytes.len()))?;
         
-        // Crear cipher
-        let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
-        
-        Ok(Self { cipher })
+        Ok(Self::from_key(&key_bytes))
+    }
+
+    /// Crea el handler a partir de los 32 bytes de la clave
+    pub fn from_key(key_bytes: &[u8; 32]) -> Self {
+        Self {
+            keys: BTreeMap::from([(SINGLE_KEY_VERSION, KeySlot::new(key_bytes))]),
+            active: SINGLE_KEY_VERSION,
+            signing_key: None,
+        }
+    }
+
+    /// Crea el handler con un anillo de claves por versión; `active` es la que encripta
+    pub fn from_keyring(active: u32, keys: &BTreeMap<u32, [u8; 32]>) -> Result<Self> {
+        if !keys.contains_key(&active) {
+            return Err(anyhow!("Active key version {} is not in the keyring", active));
+        }
+
+        Ok(Self {
+            keys: keys.iter().map(|(version, key)| (*version, KeySlot::new(key))).collect(),
+            active,
+            signing_key: None,
+        })
+    }
+
+    fn active_slot(&self) -> &KeySlot {
+        &self.keys[&self.active]
+    }
+
+    /// Huella pública de una clave: primeros 8 bytes de SHA-256 en hex
+    pub fn key_id_for(key_bytes: &[u8]) -> String {
+        hex::encode(&Sha256::digest(key_bytes)[..8])
     }
 
-    /// Encripta datos serializables usando AES-256-GCM
-    pub fn encrypt<T: Serialize>(&self, data: &T) -> Result<EncryptedPayload> {
+    /// Usa una clave de firma ya cargada (p. ej. compartida por el `KeyRegistry`)
+    pub fn with_signer(mut self, signing_key: Option<SigningKey>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    /// Añade la clave Ed25519 del gateway (semilla de 32 bytes en hex) para firmar lecturas
+    pub fn with_signing_key(self, key_hex: &str) -> Result<Self> {
+        Ok(self.with_signer(Some(parse_signing_key(key_hex)?)))
+    }
+
+    /// Genera una nueva semilla Ed25519 en hex
+    pub fn generate_signing_key() -> String {
+        hex::encode(SigningKey::generate(&mut OsRng).to_bytes())
+    }
+
+    /// Clave pública del gateway (hex), para que terceros puedan auditar las lecturas
+    pub fn public_key_hex(&self) -> Option<String> {
+        self.signing_key.as_ref().map(verifying_key_hex)
+    }
+
+    /// Datos asociados de una lectura: versión de formato, device_id (con prefijo
+    /// de longitud) y timestamp. Ligan el ciphertext a los metadatos en claro del
+    /// contrato, de modo que no se puede mover a otro registro sin que falle el descifrado.
+    pub fn reading_aad(device_id: &str, timestamp: u64) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(1 + 4 + device_id.len() + 8);
+        aad.push(AAD_FORMAT_VERSION);
+        aad.extend_from_slice(&(device_id.len() as u32).to_be_bytes());
+        aad.extend_from_slice(device_id.as_bytes());
+        aad.extend_from_slice(&timestamp.to_be_bytes());
+        aad
+    }
+
+    /// Encripta datos serializables como JSON sin envelope (formato `Legacy`)
+    pub fn encrypt<T: Serialize>(&self, data: &T, aad: &[u8]) -> Result<EncryptedPayload> {
         // Serializar a JSON
         let plaintext = serde_json::to_vec(data)
             .map_err(|e| anyhow!("Serialization failed: {}", e))?;
         
+        self.seal(&plaintext, None, aad)
+    }
+
+    /// Codifica y encripta una lectura en el formato de envelope indicado
+    pub fn seal_reading(&self, reading: &SensorReading, format: EnvelopeFormat, aad: &[u8]) -> Result<EncryptedPayload> {
+        let body_format = format.body_format_for(reading);
+        let body = reading.encode_body(body_format)?;
+
+        self.seal(&body, (format != EnvelopeFormat::Legacy).then_some(body_format), aad)
+    }
+
+    /// Encripta un cuerpo ya codificado usando AES-256-GCM, autenticando también `aad`.
+    /// Con `body_format`, el ciphertext lleva delante la cabecera del envelope
+    /// (versión, codificación y key_id), que se añade al AAD; sin él es `Legacy`.
+    pub fn seal(&self, body: &[u8], body_format: Option<BodyFormat>, aad: &[u8]) -> Result<EncryptedPayload> {
         // Generar nonce aleatorio (12 bytes para GCM)
         let mut nonce_bytes = [0u8; 12];
         OsRng.fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
         
+        let slot = self.active_slot();
+        let header = match body_format {
+            None => None,
+            Some(body) => Some(EnvelopeHeader { body, key_id: slot.key_id_bytes()? }.encode()),
+        };
+        let mut full_aad = aad.to_vec();
+        full_aad.extend_from_slice(header.as_ref().map_or(&[][..], |h| &h[..]));
+        
         // Encriptar
-        let ciphertext = self.cipher
-            .encrypt(nonce, plaintext.as_ref())
+        let encrypted = slot.cipher
+            .encrypt(nonce, Payload { msg: body, aad: &full_aad })
             .map_err(|e| anyhow!("Encryption failed: {}", e))?;
         
+        let mut ciphertext = header.map(|h| h.to_vec()).unwrap_or_default();
+        ciphertext.extend_from_slice(&encrypted);
+        
         Ok(EncryptedPayload {
             ciphertext,
             nonce: nonce_bytes.to_vec(),
+            key_id: slot.key_id.clone(),
+            key_version: self.active,
         })
     }
 
-    /// Desencripta datos (útil para verificación local)
-    #[allow(dead_code)]
-    pub fn decrypt<T: for<'de> Deserialize<'de>>(&self, payload: &EncryptedPayload) -> Result<T> {
-        // Validar nonce
-        if payload.nonce.len() != 12 {
-            return Err(anyhow!("Invalid nonce length: expected 12 bytes, got {}", payload.nonce.len()));
+    /// Desencripta datos JSON (útil para verificación local).
+    /// Falla si `aad` no coincide con el usado al encriptar.
+    pub fn decrypt<T: for<'de> Deserialize<'de>>(&self, payload: &EncryptedPayload, aad: &[u8]) -> Result<T> {
+        let (body, plaintext) = self.open(payload, aad)?;
+        if body != BodyFormat::Json {
+            return Err(anyhow!("Payload has a {:?} body, not JSON", body));
         }
         
-        let nonce = Nonce::from_slice(&payload.nonce);
-        
-        // Desencriptar
-        let plaintext = self.cipher
-            .decrypt(nonce, payload.ciphertext.as_ref())
-            .map_err(|e| anyhow!("Decryption failed: {}", e))?;
-        
         // Deserializar
         let data = serde_json::from_slice(&plaintext)
             .map_err(|e| anyhow!("Deserialization failed: {}", e))?;
@@ -90,31 +218,155 @@ impl CryptoHandler {
         Ok(data)
     }
 
-    /// Genera una firma SHA-256 del payload encriptado
-    /// Esto ayuda a verificar integridad sin desencriptar
-    pub fn sign(&self, payload: &EncryptedPayload) -> Result<Vec<u8>> {
-        let mut hasher = Sha256::new();
+    /// Desencripta detectando el formato: envelope v1 (por su cabecera) o
+    /// JSON antiguo sin cabecera. Devuelve la codificación del cuerpo y el cuerpo.
+    pub fn open(&self, payload: &EncryptedPayload, aad: &[u8]) -> Result<(BodyFormat, Vec<u8>)> {
+        // Validar nonce
+        if payload.nonce.len() != 12 {
+            return Err(anyhow!("Invalid nonce length: expected 12 bytes, got {}", payload.nonce.len()));
+        }
         
-        // Hash del ciphertext
-        hasher.update(&payload.ciphertext);
+        let nonce = Nonce::from_slice(&payload.nonce);
         
-        // Hash del nonce
-        hasher.update(&payload.nonce);
+        // Envelope: la cabecera dice qué clave usar. Si no descifra, puede ser una
+        // lectura antigua cuyo primer byte coincide por azar con la versión
+        let mut last_error = None;
+        if let Some((header, encrypted)) = EnvelopeHeader::parse(&payload.ciphertext) {
+            let key_id = hex::encode(header.key_id);
+            match self.keys.values().find(|slot| slot.key_id == key_id) {
+                Some(slot) => {
+                    let mut full_aad = aad.to_vec();
+                    full_aad.extend_from_slice(&header.encode());
+                    match slot.cipher.decrypt(nonce, Payload { msg: encrypted, aad: &full_aad }) {
+                        Ok(plaintext) => return Ok((header.body, plaintext)),
+                        Err(e) => last_error = Some(anyhow!("Decryption failed: {}", e)),
+                    }
+                }
+                None => last_error = Some(anyhow!("Key mismatch: envelope uses key {}, not in keyring", key_id)),
+            }
+        }
         
-        // Obtener digest final
-        let signature = hasher.finalize().to_vec();
+        // Desencriptar con la clave que indica el payload, o probando todas
+        // (la activa primero) si es un payload sin versión
+        let slots = match self.candidate_slots(payload) {
+            Ok(slots) => slots,
+            Err(e) => return Err(last_error.unwrap_or(e)),
+        };
+        for slot in slots {
+            match slot.cipher.decrypt(nonce, Payload { msg: &payload.ciphertext, aad }) {
+                Ok(plaintext) => return Ok((BodyFormat::Json, plaintext)),
+                Err(e) => {
+                    last_error.get_or_insert_with(|| anyhow!("Decryption failed: {}", e));
+                }
+            }
+        }
         
-        Ok(signature)
+        Err(last_error.unwrap_or_else(|| anyhow!("Decryption failed: no keys")))
+    }
+
+    /// Claves con las que intentar descifrar un payload
+    fn candidate_slots(&self, payload: &EncryptedPayload) -> Result<Vec<&KeySlot>> {
+        if payload.key_version != 0 {
+            let slot = self.keys
+                .get(&payload.key_version)
+                .ok_or_else(|| anyhow!("Unknown key version {}", payload.key_version))?;
+
+            // Si el payload indica su clave, debe ser la de esa versión
+            if !payload.key_id.is_empty() && payload.key_id != slot.key_id {
+                return Err(anyhow!(
+                    "Key mismatch: payload uses key {}, version {} is {}",
+                    payload.key_id, payload.key_version, slot.key_id
+                ));
+            }
+            return Ok(vec![slot]);
+        }
+
+        if !payload.key_id.is_empty() {
+            let slot = self.keys
+                .values()
+                .find(|slot| slot.key_id == payload.key_id)
+                .ok_or_else(|| anyhow!("Key mismatch: payload uses key {}, not in keyring", payload.key_id))?;
+            return Ok(vec![slot]);
+        }
+
+        let mut slots = vec![self.active_slot()];
+        slots.extend(self.keys.iter().filter(|(v, _)| **v != self.active).map(|(_, s)| s));
+        Ok(slots)
+    }
+
+    /// Mensaje firmado: dominio y cada campo con prefijo de longitud, para que
+    /// no haya dos combinaciones de campos que produzcan los mismos bytes
+    fn signed_message(payload: &EncryptedPayload, device_id: &str, timestamp: u64) -> Vec<u8> {
+        let mut message = Vec::with_capacity(
+            SIGNATURE_DOMAIN.len() + payload.ciphertext.len() + payload.nonce.len() + device_id.len() + 32,
+        );
+
+        for field in [SIGNATURE_DOMAIN, &payload.ciphertext, &payload.nonce, device_id.as_bytes()] {
+            message.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            message.extend_from_slice(field);
+        }
+        message.extend_from_slice(&timestamp.to_be_bytes());
+
+        message
     }
 
-    /// Verifica la firma de un payload
-    #[allow(dead_code)]
-    pub fn verify_signature(&self, payload: &EncryptedPayload, signature: &[u8]) -> Result<bool> {
-        let computed_signature = self.sign(payload)?;
-        Ok(computed_signature == signature)
+    /// Firma Ed25519 del ciphertext, nonce, device_id y timestamp (64 bytes)
+    pub fn sign(&self, payload: &EncryptedPayload, device_id: &str, timestamp: u64) -> Result<Vec<u8>> {
+        let signing_key = self.signing_key
+            .as_ref()
+            .ok_or_else(|| anyhow!("No signing key configured"))?;
+
+        let message = Self::signed_message(payload, device_id, timestamp);
+        Ok(signing_key.sign(&message).to_bytes().to_vec())
+    }
+
+    /// Verifica una firma contra la clave pública (hex) del gateway que la emitió
+    pub fn verify_signature(
+        public_key_hex: &str,
+        payload: &EncryptedPayload,
+        device_id: &str,
+        timestamp: u64,
+        signature: &[u8],
+    ) -> Result<bool> {
+        let public_key: [u8; 32] = hex::decode(public_key_hex.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid hex public key: {}", e))?
+            .try_into()
+            .map_err(|_| anyhow!("Public key must be exactly 32 bytes"))?;
+        let public_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|e| anyhow!("Invalid Ed25519 public key: {}", e))?;
+
+        Ok(Self::verify(&public_key, payload, device_id, timestamp, signature))
+    }
+
+    /// Verifica una firma contra una clave pública ya cargada (la de un gateway
+    /// o la de un dispositivo que firma sus propias lecturas)
+    pub fn verify(
+        public_key: &VerifyingKey,
+        payload: &EncryptedPayload,
+        device_id: &str,
+        timestamp: u64,
+        signature: &[u8],
+    ) -> bool {
+        let Ok(signature) = Signature::from_slice(signature) else {
+            return false;
+        };
+
+        let message = Self::signed_message(payload, device_id, timestamp);
+        public_key.verify(&message, &signature).is_ok()
     }
 }
 
+/// Semilla Ed25519 en hex → clave de firma
+pub(crate) fn parse_signing_key(key_hex: &str) -> Result<SigningKey> {
+    let seed = decode_key(key_hex).map_err(|e| anyhow!("Invalid signing key: {}", e))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Clave pública (hex) de una clave de firma
+pub(crate) fn verifying_key_hex(signing_key: &SigningKey) -> String {
+    hex::encode(signing_key.verifying_key().to_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,30 +389,131 @@ mod tests {
         };
 
         // Encriptar
-        let encrypted = crypto.encrypt(&original).unwrap();
+        let aad = CryptoHandler::reading_aad("ESP32-001", 1_700_000_000);
+        let encrypted = crypto.encrypt(&original, &aad).unwrap();
         assert!(!encrypted.ciphertext.is_empty());
         assert_eq!(encrypted.nonce.len(), 12);
 
         // Desencriptar
-        let decrypted: TestData = crypto.decrypt(&encrypted).unwrap();
+        let decrypted: TestData = crypto.decrypt(&encrypted, &aad).unwrap();
         assert_eq!(original, decrypted);
     }
 
     #[test]
-    fn test_signature() {
+    fn test_aad_mismatch_fails() {
         let key = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
         let crypto = CryptoHandler::new(key).unwrap();
 
+        let encrypted = crypto
+            .encrypt(&"data", &CryptoHandler::reading_aad("ESP32-001", 1_700_000_000))
+            .unwrap();
+
+        // Otro dispositivo, otro timestamp o sin AAD: el descifrado falla
+        assert!(crypto.decrypt::<String>(&encrypted, &CryptoHandler::reading_aad("ESP32-002", 1_700_000_000)).is_err());
+        assert!(crypto.decrypt::<String>(&encrypted, &CryptoHandler::reading_aad("ESP32-001", 1_700_000_001)).is_err());
+        assert!(crypto.decrypt::<String>(&encrypted, &[]).is_err());
+
+        // El prefijo de longitud evita ambigüedades entre device_id y timestamp
+        assert_ne!(CryptoHandler::reading_aad("A", 0), CryptoHandler::reading_aad("A\0", 0));
+    }
+
+    #[test]
+    fn test_rotated_keyring_decrypts_old_payloads() {
+        let old_key = [1u8; 32];
+        let new_key = [2u8; 32];
+        let aad = CryptoHandler::reading_aad("ESP32-001", 1_700_000_000);
+
+        let old = CryptoHandler::from_key(&old_key).encrypt(&"old", &aad).unwrap();
+        assert_eq!(old.key_version, SINGLE_KEY_VERSION);
+
+        let keyring = BTreeMap::from([(1, old_key), (2, new_key)]);
+        let crypto = CryptoHandler::from_keyring(2, &keyring).unwrap();
+        let new = crypto.encrypt(&"new", &aad).unwrap();
+        assert_eq!(new.key_version, 2);
+        assert_eq!(new.key_id, CryptoHandler::key_id_for(&new_key));
+
+        assert_eq!(crypto.decrypt::<String>(&old, &aad).unwrap(), "old");
+        assert_eq!(crypto.decrypt::<String>(&new, &aad).unwrap(), "new");
+
+        // Payload sin versión ni key_id (como los leídos de la cadena): se prueban todas
+        let bare = EncryptedPayload { key_id: String::new(), key_version: 0, ..old.clone() };
+        assert_eq!(crypto.decrypt::<String>(&bare, &aad).unwrap(), "old");
+
+        // Una versión desconocida falla
+        let unknown = EncryptedPayload { key_version: 3, ..new };
+        assert!(crypto.decrypt::<String>(&unknown, &aad).is_err());
+        assert!(CryptoHandler::from_keyring(3, &keyring).is_err());
+    }
+
+    #[test]
+    fn test_envelope_formats_are_detected() {
+        let crypto = CryptoHandler::from_key(&[3u8; 32]);
+        let aad = CryptoHandler::reading_aad("ESP32-001", 1_700_000_000);
+        let body = br#"{"temperature":21.5}"#;
+
+        let legacy = crypto.seal(body, None, &aad).unwrap();
+        let json = crypto.seal(body, Some(BodyFormat::Json), &aad).unwrap();
+        let binary = crypto.seal(&[1, 2, 3], Some(BodyFormat::Binary), &aad).unwrap();
+
+        // La cabecera ocupa 10 bytes
+        assert_eq!(json.ciphertext.len(), legacy.ciphertext.len() + 10);
+        assert_eq!(json.ciphertext[0], crate::envelope::ENVELOPE_V1);
+
+        // Sin metadatos del outbox (como al leer de la cadena) se detecta el formato
+        let bare = |p: &EncryptedPayload| EncryptedPayload { key_id: String::new(), key_version: 0, ..p.clone() };
+        assert_eq!(crypto.open(&bare(&legacy), &aad).unwrap(), (BodyFormat::Json, body.to_vec()));
+        assert_eq!(crypto.open(&bare(&json), &aad).unwrap(), (BodyFormat::Json, body.to_vec()));
+        assert_eq!(crypto.open(&bare(&binary), &aad).unwrap(), (BodyFormat::Binary, vec![1, 2, 3]));
+
+        // Cambiar la codificación declarada en la cabecera rompe el descifrado
+        let mut tampered = bare(&binary);
+        tampered.ciphertext[1] = 0;
+        assert!(crypto.open(&tampered, &aad).is_err());
+
+        // Otra clave no reconoce el envelope
+        assert!(CryptoHandler::from_key(&[4u8; 32]).open(&bare(&json), &aad).is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        let key = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let crypto = CryptoHandler::new(key)
+            .unwrap()
+            .with_signing_key(&CryptoHandler::generate_signing_key())
+            .unwrap();
+        let public_key = crypto.public_key_hex().unwrap();
+
         let data = TestData {
             message: "Test".to_string(),
             value: 123,
         };
 
-        let encrypted = crypto.encrypt(&data).unwrap();
-        let signature = crypto.sign(&encrypted).unwrap();
+        let encrypted = crypto.encrypt(&data, &[]).unwrap();
+        let signature = crypto.sign(&encrypted, "ESP32-001", 1_700_000_000).unwrap();
+
+        assert_eq!(signature.len(), 64); // Ed25519 produce 64 bytes
+        assert!(CryptoHandler::verify_signature(&public_key, &encrypted, "ESP32-001", 1_700_000_000, &signature).unwrap());
+
+        // Cambiar cualquier metadato invalida la firma
+        assert!(!CryptoHandler::verify_signature(&public_key, &encrypted, "ESP32-002", 1_700_000_000, &signature).unwrap());
+        assert!(!CryptoHandler::verify_signature(&public_key, &encrypted, "ESP32-001", 1_700_000_001, &signature).unwrap());
+
+        // Otra clave pública no verifica
+        let other = CryptoHandler::new(key)
+            .unwrap()
+            .with_signing_key(&CryptoHandler::generate_signing_key())
+            .unwrap();
+        let other_key = other.public_key_hex().unwrap();
+        assert!(!CryptoHandler::verify_signature(&other_key, &encrypted, "ESP32-001", 1_700_000_000, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_sign_requires_key() {
+        let key = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let crypto = CryptoHandler::new(key).unwrap();
+        let encrypted = crypto.encrypt(&"data", &[]).unwrap();
 
-        assert_eq!(signature.len(), 32); // SHA-256 produces 32 bytes
-        assert!(crypto.verify_signature(&encrypted, &signature).unwrap());
+        assert!(crypto.sign(&encrypted, "ESP32-001", 0).is_err());
     }
 
     #[test]
@@ -0,0 +1,316 @@
+use anyhow::{Result, anyhow};
+use ed25519_dalek::VerifyingKey;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::Mutex;
+
+use bae_core::reading::MAX_DEVICE_ID_LEN;
+
+use crate::key_registry::decode_key;
+use crate::unix_now;
+
+/// Estado de un dispositivo en el registro
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DeviceStatus {
+    /// Sus lecturas se aceptan
+    Active,
+    /// Rechazado temporalmente (p. ej. sospecha de compromiso); se puede reactivar
+    Suspended,
+    /// Dado de baja para siempre; su device_id no se reutiliza
+    Retired,
+}
+
+impl DeviceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Suspended => "suspended",
+            Self::Retired => "retired",
+        }
+    }
+}
+
+impl std::str::FromStr for DeviceStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "active" => Ok(Self::Active),
+            "suspended" => Ok(Self::Suspended),
+            "retired" => Ok(Self::Retired),
+            other => Err(anyhow!("Unknown device status: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub device_id: String,
+    pub status: DeviceStatus,
+    pub owner: String,
+    /// Tipo de sensor (`climate`, `air-quality`...), informativo
+    pub sensor_type: String,
+    /// Clave pública Ed25519 (hex) si el dispositivo cifra y firma sus lecturas
+    /// (modo extremo a extremo); entonces no se aceptan sus lecturas en claro
+    pub public_key: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Device {
+    pub fn verifying_key(&self) -> Result<Option<VerifyingKey>> {
+        self.public_key.as_deref().map(parse_public_key).transpose()
+    }
+}
+
+/// Cambios de `DeviceRegistry::update`; `None` deja el campo como está
+#[derive(Debug, Clone, Default)]
+pub struct DeviceUpdate {
+    pub owner: Option<String>,
+    pub sensor_type: Option<String>,
+    /// `Some(None)` quita la clave pública
+    pub public_key: Option<Option<String>>,
+}
+
+/// Lecturas rechazadas por el registro
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceRejected {
+    #[error("Device {0} is not registered")]
+    Unknown(String),
+    #[error("Device {0} is suspended")]
+    Suspended(String),
+    #[error("Device {0} is retired")]
+    Retired(String),
+    /// Activo en el registro local, pero el contrato no lo tiene registrado (o lo revocó)
+    #[error("Device {0} is not registered on-chain")]
+    NotOnChain(String),
+}
+
+/// Registro de dispositivos autorizados (SQLite). El gateway sólo acepta lecturas de
+/// dispositivos `active`; se consulta en cada mensaje, así que los cambios hechos con
+/// `gateway devices` desde otro proceso se aplican sin reiniciar.
+pub struct DeviceRegistry {
+    conn: Mutex<Connection>,
+}
+
+impl DeviceRegistry {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path.as_ref())
+            .map_err(|e| anyhow!("Failed to open device registry {}: {}", path.as_ref().display(), e))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS devices (
+                 device_id   TEXT    PRIMARY KEY,
+                 status      TEXT    NOT NULL DEFAULT 'active',
+                 owner       TEXT    NOT NULL,
+                 sensor_type TEXT    NOT NULL,
+                 public_key  TEXT,
+                 created_at  INTEGER NOT NULL,
+                 updated_at  INTEGER NOT NULL
+             );",
+        )
+        .map_err(|e| anyhow!("Failed to initialize device registry schema: {}", e))?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Device registry lock poisoned"))
+    }
+
+    /// Da de alta un dispositivo `active`
+    pub fn register(&self, device_id: &str, owner: &str, sensor_type: &str, public_key: Option<&str>) -> Result<Device> {
+        if device_id.is_empty() || device_id.len() > MAX_DEVICE_ID_LEN || device_id.contains(['/', '+', '#']) {
+            return Err(anyhow!("Invalid device id '{}'", device_id));
+        }
+        let public_key = public_key.map(normalize_public_key).transpose()?;
+
+        let inserted = self.conn()?.execute(
+            "INSERT OR IGNORE INTO devices (device_id, owner, sensor_type, public_key, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![device_id, owner, sensor_type, public_key, unix_now() as i64],
+        )?;
+        if inserted == 0 {
+            return Err(anyhow!("Device {} is already registered", device_id));
+        }
+
+        self.require(device_id)
+    }
+
+    pub fn get(&self, device_id: &str) -> Result<Option<Device>> {
+        let device = self
+            .conn()?
+            .query_row("SELECT * FROM devices WHERE device_id = ?1", params![device_id], Self::device_from_row)
+            .optional()?;
+
+        Ok(device)
+    }
+
+    fn require(&self, device_id: &str) -> Result<Device> {
+        self.get(device_id)?
+            .ok_or_else(|| anyhow!("Device {} is not registered", device_id))
+    }
+
+    /// Dispositivos registrados, opcionalmente filtrados por estado, por device_id
+    pub fn list(&self, status: Option<DeviceStatus>) -> Result<Vec<Device>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT * FROM devices WHERE ?1 IS NULL OR status = ?1 ORDER BY device_id",
+        )?;
+        let devices = stmt
+            .query_map(params![status.map(|s| s.as_str())], Self::device_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(devices)
+    }
+
+    /// Cambia el estado. Un dispositivo retirado no vuelve a activarse.
+    pub fn set_status(&self, device_id: &str, status: DeviceStatus) -> Result<Device> {
+        let device = self.require(device_id)?;
+        if device.status == DeviceStatus::Retired && status != DeviceStatus::Retired {
+            return Err(anyhow!("Device {} is retired and cannot be reactivated", device_id));
+        }
+
+        self.conn()?.execute(
+            "UPDATE devices SET status = ?2, updated_at = ?3 WHERE device_id = ?1",
+            params![device_id, status.as_str(), unix_now() as i64],
+        )?;
+
+        self.require(device_id)
+    }
+
+    pub fn update(&self, device_id: &str, update: DeviceUpdate) -> Result<Device> {
+        let device = self.require(device_id)?;
+        let public_key = match update.public_key {
+            Some(Some(key)) => Some(normalize_public_key(&key)?),
+            Some(None) => None,
+            None => device.public_key,
+        };
+
+        self.conn()?.execute(
+            "UPDATE devices SET owner = ?2, sensor_type = ?3, public_key = ?4, updated_at = ?5
+             WHERE device_id = ?1",
+            params![
+                device_id,
+                update.owner.unwrap_or(device.owner),
+                update.sensor_type.unwrap_or(device.sensor_type),
+                public_key,
+                unix_now() as i64,
+            ],
+        )?;
+
+        self.require(device_id)
+    }
+
+    /// El dispositivo si puede enviar lecturas; si no, `DeviceRejected`
+    pub fn authorize(&self, device_id: &str) -> Result<Device> {
+        let device = self
+            .get(device_id)?
+            .ok_or_else(|| DeviceRejected::Unknown(device_id.to_string()))?;
+
+        match device.status {
+            DeviceStatus::Active => Ok(device),
+            DeviceStatus::Suspended => Err(DeviceRejected::Suspended(device.device_id).into()),
+            DeviceStatus::Retired => Err(DeviceRejected::Retired(device.device_id).into()),
+        }
+    }
+
+    pub fn count(&self, status: DeviceStatus) -> Result<u64> {
+        let count: i64 = self.conn()?.query_row(
+            "SELECT COUNT(*) FROM devices WHERE status = ?1",
+            params![status.as_str()],
+            |row| row.get(0),
+        )?;
+
+        Ok(count as u64)
+    }
+
+    fn device_from_row(row: &Row) -> rusqlite::Result<Device> {
+        let status: String = row.get("status")?;
+        Ok(Device {
+            device_id: row.get("device_id")?,
+            status: status.parse().unwrap_or(DeviceStatus::Suspended),
+            owner: row.get("owner")?,
+            sensor_type: row.get("sensor_type")?,
+            public_key: row.get("public_key")?,
+            created_at: row.get::<_, i64>("created_at")? as u64,
+            updated_at: row.get::<_, i64>("updated_at")? as u64,
+        })
+    }
+}
+
+fn parse_public_key(public_key_hex: &str) -> Result<VerifyingKey> {
+    let bytes = decode_key(public_key_hex).map_err(|e| anyhow!("Invalid public key: {}", e))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| anyhow!("Invalid Ed25519 public key: {}", e))
+}
+
+/// Valida la clave pública y la guarda siempre igual (hex en minúsculas, sin `0x`)
+fn normalize_public_key(public_key_hex: &str) -> Result<String> {
+    Ok(hex::encode(parse_public_key(public_key_hex)?.to_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_only_active_devices_are_authorized() {
+        let registry = DeviceRegistry::open_in_memory().unwrap();
+        registry.register("ESP32-001", "greenhouse", "climate", None).unwrap();
+
+        assert_eq!(registry.authorize("ESP32-001").unwrap().owner, "greenhouse");
+        let unknown = registry.authorize("ESP32-999").unwrap_err();
+        assert!(matches!(unknown.downcast_ref(), Some(DeviceRejected::Unknown(_))));
+
+        registry.set_status("ESP32-001", DeviceStatus::Suspended).unwrap();
+        let suspended = registry.authorize("ESP32-001").unwrap_err();
+        assert!(matches!(suspended.downcast_ref(), Some(DeviceRejected::Suspended(_))));
+
+        registry.set_status("ESP32-001", DeviceStatus::Active).unwrap();
+        assert!(registry.authorize("ESP32-001").is_ok());
+
+        // Retirar es definitivo
+        registry.set_status("ESP32-001", DeviceStatus::Retired).unwrap();
+        assert!(registry.set_status("ESP32-001", DeviceStatus::Active).is_err());
+        assert!(registry.authorize("ESP32-001").is_err());
+        assert!(registry.register("ESP32-001", "greenhouse", "climate", None).is_err());
+    }
+
+    #[test]
+    fn test_register_and_update() {
+        let registry = DeviceRegistry::open_in_memory().unwrap();
+        let public_key = SigningKey::from_bytes(&[7; 32]).verifying_key();
+
+        let device = registry
+            .register("NODE-7", "lab", "air-quality", Some(&format!("0x{}", hex::encode(public_key.to_bytes()).to_uppercase())))
+            .unwrap();
+        assert_eq!(device.status, DeviceStatus::Active);
+        assert_eq!(device.public_key, Some(hex::encode(public_key.to_bytes())));
+        assert_eq!(device.verifying_key().unwrap(), Some(public_key));
+
+        let device = registry
+            .update("NODE-7", DeviceUpdate { owner: Some("warehouse".to_string()), public_key: Some(None), ..Default::default() })
+            .unwrap();
+        assert_eq!((device.owner.as_str(), device.sensor_type.as_str(), device.public_key), ("warehouse", "air-quality", None));
+
+        assert!(registry.register("NODE-8", "lab", "climate", Some("abcd")).is_err());
+        assert!(registry.register("bae/NODE-9", "lab", "climate", None).is_err());
+        assert!(registry.update("NODE-9", DeviceUpdate::default()).is_err());
+
+        registry.register("NODE-8", "lab", "climate", None).unwrap();
+        registry.set_status("NODE-8", DeviceStatus::Suspended).unwrap();
+        let ids = |status| registry.list(status).unwrap().into_iter().map(|d| d.device_id).collect::<Vec<_>>();
+        assert_eq!(ids(None), vec!["NODE-7", "NODE-8"]);
+        assert_eq!(ids(Some(DeviceStatus::Suspended)), vec!["NODE-8"]);
+        assert_eq!(registry.count(DeviceStatus::Active).unwrap(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{Result, anyhow};
+
+pub use bae_core::body::{BinaryBody, BodyFormat};
+use bae_core::reading::SensorReading;
+
+/// Primer byte del ciphertext on-chain en el formato envelope v1.
+/// Las lecturas antiguas (JSON cifrado sin cabecera) no llevan cabecera.
+pub const ENVELOPE_V1: u8 = 0xE1;
+/// Bytes de `key_id` en la cabecera (huella de la clave, ver `CryptoHandler::key_id_for`)
+pub const KEY_ID_LEN: usize = 8;
+/// versión (1) + codificación del cuerpo (1) + key_id (8)
+pub const HEADER_LEN: usize = 2 + KEY_ID_LEN;
+
+/// Formato con el que el gateway escribe las lecturas on-chain (`ENVELOPE_FORMAT`).
+/// La lectura detecta el formato sola, así que se puede cambiar en cualquier momento.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EnvelopeFormat {
+    /// JSON cifrado sin cabecera (formato original, para lectores antiguos)
+    Legacy,
+    /// Envelope v1 con cuerpo JSON
+    Json,
+    /// Envelope v1 con cuerpo binario (el más barato en gas): el fijo de 16 bytes
+    /// para temperatura y humedad, y el genérico de medidas para el resto
+    #[default]
+    Binary,
+}
+
+impl EnvelopeFormat {
+    /// Lee `ENVELOPE_FORMAT` (`legacy`, `json` o `binary`)
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("ENVELOPE_FORMAT") {
+            Ok(value) => <Self as clap::ValueEnum>::from_str(&value, true)
+                .map_err(|_| anyhow!("Invalid ENVELOPE_FORMAT '{}' (legacy, json or binary)", value)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Codificación del cuerpo de `reading` en este formato
+    pub fn body_format_for(&self, reading: &SensorReading) -> BodyFormat {
+        match self {
+            Self::Legacy | Self::Json => BodyFormat::Json,
+            Self::Binary => reading.compact_body_format(),
+        }
+    }
+}
+
+/// Cabecera en claro del envelope. Va también en el AAD, así que no se puede
+/// cambiar la codificación ni la clave declaradas sin romper el descifrado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub body: BodyFormat,
+    pub key_id: [u8; KEY_ID_LEN],
+}
+
+impl EnvelopeHeader {
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = ENVELOPE_V1;
+        header[1] = self.body.code();
+        header[2..].copy_from_slice(&self.key_id);
+        header
+    }
+
+    /// Separa cabecera y ciphertext; `None` si no parece un envelope (lectura antigua)
+    pub fn parse(bytes: &[u8]) -> Option<(Self, &[u8])> {
+        if bytes.len() < HEADER_LEN || bytes[0] != ENVELOPE_V1 {
+            return None;
+        }
+
+        let body = BodyFormat::from_code(bytes[1])?;
+        let key_id = bytes[2..HEADER_LEN].try_into().ok()?;
+        Some((Self { body, key_id }, &bytes[HEADER_LEN..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = EnvelopeHeader { body: BodyFormat::Binary, key_id: [7; KEY_ID_LEN] };
+        let mut bytes = header.encode().to_vec();
+        bytes.extend_from_slice(b"ciphertext");
+
+        let (parsed, rest) = EnvelopeHeader::parse(&bytes).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(rest, b"ciphertext");
+
+        // Sin el byte de versión o con codificación desconocida no es un envelope
+        assert!(EnvelopeHeader::parse(&bytes[1..]).is_none());
+        bytes[1] = 9;
+        assert!(EnvelopeHeader::parse(&bytes).is_none());
+    }
+}
@@ -0,0 +1,320 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Mutex;
+
+use bae_core::metric::Measurement;
+use bae_core::reading::SensorReading;
+
+/// Hashes de bloque que se conservan para detectar reorgs (los más recientes)
+pub const BLOCK_HISTORY: u64 = 256;
+
+/// Lectura on-chain ya descifrada (o el error al descifrarla), tal como la guarda el indexador
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedReading {
+    pub index: u64,
+    pub device_id: String,
+    /// Timestamp on-chain (el del AAD)
+    pub timestamp: u64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub data: std::result::Result<SensorReading, String>,
+}
+
+/// Copia local (SQLite) de las lecturas del contrato, escrita por `bae-indexer`
+/// y consultada por `bae-api`. Guarda también los hashes de los últimos bloques
+/// indexados para poder deshacer lo indexado si la cadena se reorganiza.
+pub struct IndexStore {
+    conn: Mutex<Connection>,
+}
+
+impl IndexStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path.as_ref())
+            .map_err(|e| anyhow!("Failed to open index {}: {}", path.as_ref().display(), e))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS readings (
+                 idx               INTEGER PRIMARY KEY,
+                 device_id         TEXT    NOT NULL,
+                 timestamp         INTEGER NOT NULL,
+                 block_number      INTEGER NOT NULL,
+                 block_hash        TEXT    NOT NULL,
+                 tx_hash           TEXT    NOT NULL,
+                 temperature       REAL,
+                 humidity          REAL,
+                 reading_timestamp INTEGER,
+                 error             TEXT,
+                 measurements      TEXT
+             );
+             CREATE INDEX IF NOT EXISTS readings_block ON readings (block_number);
+             CREATE INDEX IF NOT EXISTS readings_device ON readings (device_id, idx);
+             CREATE TABLE IF NOT EXISTS blocks (
+                 number INTEGER PRIMARY KEY,
+                 hash   TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS meta (
+                 key   TEXT PRIMARY KEY,
+                 value INTEGER NOT NULL
+             );",
+        )
+        .map_err(|e| anyhow!("Failed to initialize index schema: {}", e))?;
+        Self::ensure_measurements_column(&conn)?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Índices creados antes del esquema multimétrica: sus filas conservan
+    /// `temperature`/`humidity` y se leen como lecturas de clima
+    fn ensure_measurements_column(conn: &Connection) -> Result<()> {
+        let exists = conn
+            .prepare("SELECT 1 FROM pragma_table_info('readings') WHERE name = 'measurements'")?
+            .exists([])?;
+        if !exists {
+            conn.execute_batch("ALTER TABLE readings ADD COLUMN measurements TEXT")?;
+        }
+        Ok(())
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Index lock poisoned"))
+    }
+
+    /// Último bloque indexado por completo
+    pub fn cursor(&self) -> Result<Option<u64>> {
+        let cursor: Option<i64> = self.conn()?
+            .query_row("SELECT value FROM meta WHERE key = 'cursor'", [], |row| row.get(0))
+            .optional()?;
+
+        Ok(cursor.map(|block| block as u64))
+    }
+
+    /// Bloques indexados con su hash, del más reciente al más antiguo
+    pub fn recent_blocks(&self, limit: u64) -> Result<Vec<(u64, String)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT number, hash FROM blocks ORDER BY number DESC LIMIT ?1")?;
+        let blocks = stmt
+            .query_map(params![limit as i64], |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(blocks)
+    }
+
+    /// Guarda las lecturas de un rango de bloques y avanza el cursor hasta `block`
+    /// (cuyo hash se recuerda), todo en una transacción
+    pub fn commit(&self, block: u64, block_hash: &str, readings: &[IndexedReading]) -> Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        for reading in readings {
+            let (temperature, humidity, reading_timestamp, error, measurements) = match &reading.data {
+                Ok(data) => (
+                    data.temperature(),
+                    data.humidity(),
+                    Some(data.timestamp as i64),
+                    None,
+                    Some(serde_json::to_string(&data.measurements)?),
+                ),
+                Err(e) => (None, None, None, Some(e.as_str()), None),
+            };
+            tx.execute(
+                "INSERT OR REPLACE INTO readings
+                     (idx, device_id, timestamp, block_number, block_hash, tx_hash, temperature, humidity, reading_timestamp, error, measurements)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    reading.index as i64,
+                    reading.device_id,
+                    reading.timestamp as i64,
+                    reading.block_number as i64,
+                    reading.block_hash,
+                    reading.tx_hash,
+                    temperature,
+                    humidity,
+                    reading_timestamp,
+                    error,
+                    measurements,
+                ],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO blocks (number, hash) VALUES (?1, ?2)",
+                params![reading.block_number as i64, reading.block_hash],
+            )?;
+        }
+
+        tx.execute("INSERT OR REPLACE INTO blocks (number, hash) VALUES (?1, ?2)", params![block as i64, block_hash])?;
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('cursor', ?1)", params![block as i64])?;
+        tx.execute(
+            "DELETE FROM blocks WHERE number < ?1",
+            params![block.saturating_sub(BLOCK_HISTORY) as i64],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Deshace lo indexado después de `block` (reorg) y devuelve las lecturas borradas
+    pub fn rollback_to(&self, block: u64) -> Result<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let removed = tx.execute("DELETE FROM readings WHERE block_number > ?1", params![block as i64])?;
+        tx.execute("DELETE FROM blocks WHERE number > ?1", params![block as i64])?;
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('cursor', ?1)", params![block as i64])?;
+
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Número de lecturas del contrato hasta la última indexada (índice + 1, como `getReadingCount`)
+    pub fn count(&self) -> Result<u64> {
+        let max: Option<i64> = self.conn()?.query_row("SELECT MAX(idx) FROM readings", [], |row| row.get(0))?;
+        Ok(max.map_or(0, |index| index as u64 + 1))
+    }
+
+    pub fn latest(&self) -> Result<Option<IndexedReading>> {
+        Ok(self.conn()?
+            .query_row("SELECT * FROM readings ORDER BY idx DESC LIMIT 1", [], Self::reading_from_row)
+            .optional()?)
+    }
+
+    pub fn get(&self, index: u64) -> Result<Option<IndexedReading>> {
+        Ok(self.conn()?
+            .query_row("SELECT * FROM readings WHERE idx = ?1", params![index as i64], Self::reading_from_row)
+            .optional()?)
+    }
+
+    /// Lecturas indexadas con índice en `range`, en orden ascendente
+    pub fn range(&self, range: Range<u64>) -> Result<Vec<IndexedReading>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT * FROM readings WHERE idx >= ?1 AND idx < ?2 ORDER BY idx")?;
+        let readings = stmt
+            .query_map(params![range.start as i64, range.end as i64], Self::reading_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(readings)
+    }
+
+    fn reading_from_row(row: &Row<'_>) -> rusqlite::Result<IndexedReading> {
+        let device_id: String = row.get("device_id")?;
+        let error: Option<String> = row.get("error")?;
+        let data = match error {
+            Some(error) => Err(error),
+            None => {
+                let timestamp = row.get::<_, i64>("reading_timestamp")? as u64;
+                match row.get::<_, Option<String>>("measurements")? {
+                    Some(json) => {
+                        let measurements: Vec<Measurement> = serde_json::from_str(&json).map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+                        })?;
+                        Ok(SensorReading::new(device_id.clone(), timestamp, measurements))
+                    }
+                    None => Ok(SensorReading::climate(
+                        device_id.clone(),
+                        row.get("temperature")?,
+                        row.get("humidity")?,
+                        timestamp,
+                    )),
+                }
+            }
+        };
+
+        Ok(IndexedReading {
+            index: row.get::<_, i64>("idx")? as u64,
+            device_id,
+            timestamp: row.get::<_, i64>("timestamp")? as u64,
+            block_number: row.get::<_, i64>("block_number")? as u64,
+            block_hash: row.get("block_hash")?,
+            tx_hash: row.get("tx_hash")?,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(index: u64, block_number: u64, data: std::result::Result<SensorReading, String>) -> IndexedReading {
+        IndexedReading {
+            index,
+            device_id: "ESP32-001".to_string(),
+            timestamp: 1_700_000_000 + index,
+            block_number,
+            block_hash: format!("0xb{}", block_number),
+            tx_hash: format!("0xt{}", index),
+            data,
+        }
+    }
+
+    fn data(index: u64) -> SensorReading {
+        SensorReading::climate("ESP32-001", 20.5, 50.0, 1_700_000_000 + index)
+    }
+
+    #[test]
+    fn test_stores_any_metric_set() {
+        let store = IndexStore::open_in_memory().unwrap();
+        let co2 = SensorReading::new("ESP32-001", 1_700_000_000, vec![Measurement::number("co2", "ppm", 612.0)]);
+        store.commit(10, "0xb10", &[indexed(0, 10, Ok(co2.clone()))]).unwrap();
+
+        assert_eq!(store.get(0).unwrap().unwrap().data, Ok(co2));
+    }
+
+    #[test]
+    fn test_migrates_pre_metrics_index() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE readings (
+                 idx INTEGER PRIMARY KEY, device_id TEXT NOT NULL, timestamp INTEGER NOT NULL,
+                 block_number INTEGER NOT NULL, block_hash TEXT NOT NULL, tx_hash TEXT NOT NULL,
+                 temperature REAL, humidity REAL, reading_timestamp INTEGER, error TEXT
+             );
+             INSERT INTO readings VALUES (0, 'ESP32-001', 1700000000, 10, '0xb10', '0xt0', 20.5, 50.0, 1700000000, NULL);",
+        )
+        .unwrap();
+
+        let store = IndexStore::init(conn).unwrap();
+        assert_eq!(store.get(0).unwrap().unwrap().data, Ok(data(0)));
+    }
+
+    #[test]
+    fn test_commit_and_query() {
+        let store = IndexStore::open_in_memory().unwrap();
+        assert_eq!(store.cursor().unwrap(), None);
+        assert_eq!(store.count().unwrap(), 0);
+
+        let readings = vec![indexed(0, 10, Ok(data(0))), indexed(1, 12, Err("bad tag".to_string()))];
+        store.commit(15, "0xb15", &readings).unwrap();
+
+        assert_eq!(store.cursor().unwrap(), Some(15));
+        assert_eq!(store.count().unwrap(), 2);
+        assert_eq!(store.get(0).unwrap().unwrap(), readings[0]);
+        assert_eq!(store.latest().unwrap().unwrap().data, Err("bad tag".to_string()));
+        assert_eq!(store.range(0..5).unwrap(), readings);
+        assert_eq!(
+            store.recent_blocks(10).unwrap(),
+            vec![(15, "0xb15".to_string()), (12, "0xb12".to_string()), (10, "0xb10".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_rollback_removes_reorged_blocks() {
+        let store = IndexStore::open_in_memory().unwrap();
+        store.commit(10, "0xb10", &[indexed(0, 10, Ok(data(0)))]).unwrap();
+        store.commit(20, "0xb20", &[indexed(1, 18, Ok(data(1))), indexed(2, 20, Ok(data(2)))]).unwrap();
+
+        assert_eq!(store.rollback_to(17).unwrap(), 2);
+        assert_eq!(store.cursor().unwrap(), Some(17));
+        assert_eq!(store.count().unwrap(), 1);
+        assert_eq!(store.recent_blocks(10).unwrap(), vec![(10, "0xb10".to_string())]);
+    }
+}
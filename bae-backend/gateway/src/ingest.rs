@@ -0,0 +1,239 @@
+use anyhow::{Result, anyhow};
+
+use bae_core::reading::SensorReading;
+use bae_core::senml;
+
+use crate::sealed::SealedReading;
+
+/// Topic de las lecturas JSON propias de Bae
+pub const SENSOR_TOPIC: &str = "bae/sensors/+/data";
+/// Topic por defecto de los packs SenML (`SENML_TOPIC`)
+pub const DEFAULT_SENML_TOPIC: &str = "bae/sensors/+/senml";
+/// Topic por defecto de las lecturas Protocol Buffers (`PROTOBUF_TOPIC`)
+pub const DEFAULT_PROTOBUF_TOPIC: &str = "bae/sensors/+/proto";
+/// Topic por defecto de las lecturas cifradas en el dispositivo (`SEALED_TOPIC`)
+pub const DEFAULT_SEALED_TOPIC: &str = "bae/sensors/+/sealed";
+
+/// Formato del payload MQTT, decidido por el topic en el que llega
+/// o por su `content-type` (MQTT 5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// `SensorReading` en JSON (`{"schema_version": 2, "device_id": ..., "measurements": [...]}`)
+    Reading,
+    /// Pack SenML (RFC 8428) en JSON o CBOR
+    Senml,
+    /// `bae.v1.Reading` en Protocol Buffers (`bae-core/proto/reading.proto`)
+    Protobuf,
+    /// `SealedReading`: cifrada y firmada en el dispositivo, el gateway no la descifra
+    Sealed,
+}
+
+impl PayloadFormat {
+    /// Formato de un `content-type` MQTT 5 (sin parámetros como `charset`)
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/json" => Some(Self::Reading),
+            "application/senml+json" | "application/senml+cbor" => Some(Self::Senml),
+            "application/x-protobuf" | "application/protobuf" | "application/vnd.google.protobuf" => {
+                Some(Self::Protobuf)
+            }
+            "application/vnd.bae.sealed+json" => Some(Self::Sealed),
+            _ => None,
+        }
+    }
+}
+
+/// Patrón de topic MQTT con exactamente un nivel `+`, que es el device_id
+/// del publicador: `bae/sensors/+/data`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicPattern {
+    pattern: String,
+    prefix: String,
+    suffix: String,
+}
+
+impl TopicPattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let levels: Vec<&str> = pattern.split('/').collect();
+        let wildcards = levels.iter().filter(|level| level.contains(['+', '#'])).count();
+        let position = levels.iter().position(|level| *level == "+");
+
+        let (Some(position), 1) = (position, wildcards) else {
+            return Err(anyhow!("Topic pattern '{}' must have exactly one '+' level (the device id)", pattern));
+        };
+
+        let prefix: String = levels[..position].iter().map(|level| format!("{}/", level)).collect();
+        let suffix: String = levels[position + 1..].iter().map(|level| format!("/{}", level)).collect();
+        Ok(Self { pattern: pattern.to_string(), prefix, suffix })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// device_id del topic, si encaja en el patrón
+    pub fn device_id<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        let device_id = topic.strip_prefix(&self.prefix)?.strip_suffix(&self.suffix)?;
+
+        if device_id.is_empty() || device_id.contains('/') {
+            return None;
+        }
+
+        Some(device_id)
+    }
+}
+
+/// Topics a los que se suscribe el gateway y el formato de cada uno
+#[derive(Debug, Clone)]
+pub struct IngestTopics {
+    routes: Vec<(TopicPattern, PayloadFormat)>,
+}
+
+/// Topics opcionales: variable de entorno, topic por defecto y formato
+const OPTIONAL_TOPICS: [(&str, &str, PayloadFormat); 3] = [
+    ("SENML_TOPIC", DEFAULT_SENML_TOPIC, PayloadFormat::Senml),
+    ("PROTOBUF_TOPIC", DEFAULT_PROTOBUF_TOPIC, PayloadFormat::Protobuf),
+    ("SEALED_TOPIC", DEFAULT_SEALED_TOPIC, PayloadFormat::Sealed),
+];
+
+impl Default for IngestTopics {
+    /// Lecturas JSON y todos los topics opcionales en sus patrones por defecto
+    fn default() -> Self {
+        OPTIONAL_TOPICS
+            .iter()
+            .try_fold(Self::new(), |topics, (_, pattern, format)| topics.with_topic(pattern, *format))
+            .expect("default topics are valid")
+    }
+}
+
+impl IngestTopics {
+    /// Sólo lecturas JSON en `SENSOR_TOPIC`
+    pub fn new() -> Self {
+        let pattern = TopicPattern::parse(SENSOR_TOPIC).expect("SENSOR_TOPIC is valid");
+        Self { routes: vec![(pattern, PayloadFormat::Reading)] }
+    }
+
+    /// Añade un topic para `format`; cada patrón sólo puede tener un formato
+    pub fn with_topic(mut self, pattern: &str, format: PayloadFormat) -> Result<Self> {
+        let pattern = TopicPattern::parse(pattern)?;
+        if let Some((_, existing)) = self.routes.iter().find(|(existing, _)| *existing == pattern) {
+            return Err(anyhow!("{:?} topic '{}' is already used for {:?}", format, pattern.as_str(), existing));
+        }
+        self.routes.push((pattern, format));
+        Ok(self)
+    }
+
+    /// Lee `SENML_TOPIC`, `PROTOBUF_TOPIC` y `SEALED_TOPIC` (vacíos los desactivan;
+    /// por defecto `bae/sensors/+/senml`, `bae/sensors/+/proto` y `bae/sensors/+/sealed`)
+    pub fn from_env() -> Result<Self> {
+        let mut topics = Self::new();
+        for (name, default, format) in OPTIONAL_TOPICS {
+            match std::env::var(name) {
+                Ok(pattern) if pattern.trim().is_empty() => {}
+                Ok(pattern) => topics = topics.with_topic(pattern.trim(), format)?,
+                Err(_) => topics = topics.with_topic(default, format)?,
+            }
+        }
+
+        Ok(topics)
+    }
+
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.routes.iter().map(|(pattern, _)| pattern.as_str().to_string()).collect()
+    }
+
+    /// Formato y device_id de un mensaje. El topic da el device_id y el formato por
+    /// defecto; el `content-type` de MQTT 5, si viene, decide el formato.
+    pub fn route<'a>(&self, topic: &'a str, content_type: Option<&str>) -> Result<(PayloadFormat, &'a str)> {
+        let (format, device_id) = self
+            .routes
+            .iter()
+            .find_map(|(pattern, format)| pattern.device_id(topic).map(|device_id| (*format, device_id)))
+            .ok_or_else(|| anyhow!("Unexpected topic: {}", topic))?;
+
+        match content_type {
+            Some(content_type) => PayloadFormat::from_content_type(content_type)
+                .map(|format| (format, device_id))
+                .ok_or_else(|| anyhow!("Unsupported content type '{}' on {}", content_type, topic)),
+            None => Ok((format, device_id)),
+        }
+    }
+}
+
+/// Contenido de un mensaje ya parseado
+#[derive(Debug)]
+pub enum Decoded {
+    /// Lecturas en claro (aún sin validar), que el gateway cifra
+    Readings(Vec<SensorReading>),
+    /// Lectura cifrada en el dispositivo (aún sin verificar)
+    Sealed(SealedReading),
+}
+
+/// Normaliza un payload en lecturas internas. Un pack SenML puede traer varias;
+/// sus nombres sin parte de dispositivo son de `topic_device`.
+pub fn decode(format: PayloadFormat, payload: &[u8], topic_device: &str, now: u64) -> Result<Decoded> {
+    let readings = match format {
+        PayloadFormat::Reading => vec![SensorReading::from_json(payload)?],
+        PayloadFormat::Senml => senml::to_readings(payload, topic_device, now)?,
+        PayloadFormat::Protobuf => vec![SensorReading::from_protobuf(payload)?],
+        PayloadFormat::Sealed => return Ok(Decoded::Sealed(SealedReading::from_json(payload)?)),
+    };
+
+    Ok(Decoded::Readings(readings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_id_from_topic() {
+        let pattern = TopicPattern::parse(SENSOR_TOPIC).unwrap();
+        assert_eq!(pattern.device_id("bae/sensors/ESP32-001/data"), Some("ESP32-001"));
+        assert_eq!(pattern.device_id("bae/sensors//data"), None);
+        assert_eq!(pattern.device_id("bae/sensors/a/b/data"), None);
+        assert_eq!(pattern.device_id("bae/other/ESP32-001/data"), None);
+        assert_eq!(pattern.device_id("bae/sensors/ESP32-001/status"), None);
+
+        let leading = TopicPattern::parse("+/senml").unwrap();
+        assert_eq!(leading.device_id("AIR-042/senml"), Some("AIR-042"));
+        let trailing = TopicPattern::parse("senml/+").unwrap();
+        assert_eq!(trailing.device_id("senml/AIR-042"), Some("AIR-042"));
+
+        for invalid in ["bae/sensors/data", "bae/+/+/data", "bae/sensors/#", "bae/sensors/x+/data"] {
+            assert!(TopicPattern::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_routes_by_topic() {
+        let topics = IngestTopics::new().with_topic("lab/+/senml", PayloadFormat::Senml).unwrap();
+        assert_eq!(topics.subscriptions(), vec![SENSOR_TOPIC.to_string(), "lab/+/senml".to_string()]);
+        assert_eq!(topics.route("bae/sensors/ESP32-001/data", None).unwrap(), (PayloadFormat::Reading, "ESP32-001"));
+        assert_eq!(topics.route("lab/AIR-042/senml", None).unwrap(), (PayloadFormat::Senml, "AIR-042"));
+        assert!(topics.route("bae/sensors/AIR-042/senml", None).is_err());
+        assert!(topics.route("bae/sensors/AIR-042/proto", None).is_err());
+
+        let defaults = IngestTopics::default();
+        assert_eq!(defaults.route("bae/sensors/NODE-7/proto", None).unwrap(), (PayloadFormat::Protobuf, "NODE-7"));
+        assert_eq!(defaults.route("bae/sensors/NODE-7/sealed", None).unwrap(), (PayloadFormat::Sealed, "NODE-7"));
+
+        assert!(IngestTopics::new().with_topic(SENSOR_TOPIC, PayloadFormat::Senml).is_err());
+        assert!(IngestTopics::default().with_topic(DEFAULT_SEALED_TOPIC, PayloadFormat::Protobuf).is_err());
+    }
+
+    #[test]
+    fn test_content_type_overrides_topic() {
+        let topics = IngestTopics::default();
+        let topic = "bae/sensors/NODE-7/data";
+
+        assert_eq!(topics.route(topic, Some("application/x-protobuf")).unwrap(), (PayloadFormat::Protobuf, "NODE-7"));
+        assert_eq!(topics.route(topic, Some("application/senml+cbor")).unwrap().0, PayloadFormat::Senml);
+        assert_eq!(topics.route(topic, Some("application/json; charset=utf-8")).unwrap().0, PayloadFormat::Reading);
+        assert!(topics.route(topic, Some("text/plain")).is_err());
+
+        // El content-type no cambia qué topics se aceptan
+        assert!(topics.route("other/NODE-7/data", Some("application/x-protobuf")).is_err());
+    }
+}
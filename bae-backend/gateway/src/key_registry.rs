@@ -0,0 +1,189 @@
+use anyhow::{Result, anyhow};
+use ed25519_dalek::SigningKey;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::crypto::{self, CryptoHandler};
+use crate::keyring::Keyring;
+
+/// Sal fija de la derivación HKDF; el device_id va como `info`
+const DEVICE_KEY_SALT: &[u8] = b"bae-device-key-v1";
+
+/// Registro de claves AES por dispositivo. Cada `device_id` tiene su propia clave:
+/// la del keystore si existe, o una derivada con HKDF-SHA256 de la clave maestra.
+/// Así la filtración de una clave sólo expone el histórico de ese dispositivo.
+/// Las claves derivadas siguen las versiones del `Keyring` maestro.
+#[derive(Default)]
+pub struct KeyRegistry {
+    master_keys: BTreeMap<u32, [u8; 32]>,
+    active_version: u32,
+    stored: HashMap<String, [u8; 32]>,
+    signing_key: Option<SigningKey>,
+}
+
+impl KeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clave maestra única (hex, 32 bytes) para derivar las claves por dispositivo
+    pub fn with_master_key(self, key_hex: &str) -> Result<Self> {
+        self.with_keyring(&Keyring::from_master_key(key_hex)?)
+    }
+
+    /// Anillo de claves maestras: la activa encripta, todas sirven para descifrar
+    pub fn with_keyring(mut self, keyring: &Keyring) -> Result<Self> {
+        self.master_keys = keyring.decoded()?;
+        self.active_version = keyring.active;
+        Ok(self)
+    }
+
+    /// Carga un keystore JSON `{ "<device_id>": "<clave hex>" }`.
+    /// Estas claves tienen prioridad sobre las derivadas.
+    pub fn with_keystore(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read keystore {}: {}", path.display(), e))?;
+        let entries: HashMap<String, String> = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid keystore {}: {}", path.display(), e))?;
+
+        for (device_id, key_hex) in entries {
+            let key = decode_key(&key_hex)
+                .map_err(|e| anyhow!("Invalid key for {} in keystore: {}", device_id, e))?;
+            self.stored.insert(device_id, key);
+        }
+
+        Ok(self)
+    }
+
+    /// Clave Ed25519 del gateway, compartida por todos los handlers
+    pub fn with_signing_key(mut self, key_hex: &str) -> Result<Self> {
+        self.signing_key = Some(crypto::parse_signing_key(key_hex)?);
+        Ok(self)
+    }
+
+    pub fn stored_devices(&self) -> usize {
+        self.stored.len()
+    }
+
+    pub fn public_key_hex(&self) -> Option<String> {
+        self.signing_key.as_ref().map(crypto::verifying_key_hex)
+    }
+
+    /// Clave AES activa de un dispositivo
+    #[cfg(test)]
+    pub fn key_for(&self, device_id: &str) -> Result<[u8; 32]> {
+        if let Some(key) = self.stored.get(device_id) {
+            return Ok(*key);
+        }
+
+        let master_key = self.master_keys
+            .get(&self.active_version)
+            .ok_or_else(|| anyhow!("No key for device {} and no master key configured", device_id))?;
+
+        derive_device_key(master_key, device_id)
+    }
+
+    /// Handler de cifrado (y firma) para un dispositivo. Las claves del keystore
+    /// son fijas; las derivadas incluyen todas las versiones del anillo maestro.
+    pub fn handler_for(&self, device_id: &str) -> Result<CryptoHandler> {
+        let handler = if let Some(key) = self.stored.get(device_id) {
+            CryptoHandler::from_key(key)
+        } else {
+            if self.master_keys.is_empty() {
+                return Err(anyhow!("No key for device {} and no master key configured", device_id));
+            }
+
+            let keys = self.master_keys
+                .iter()
+                .map(|(version, master_key)| Ok((*version, derive_device_key(master_key, device_id)?)))
+                .collect::<Result<BTreeMap<_, _>>>()?;
+            CryptoHandler::from_keyring(self.active_version, &keys)?
+        };
+
+        Ok(handler.with_signer(self.signing_key.clone()))
+    }
+}
+
+fn derive_device_key(master_key: &[u8; 32], device_id: &str) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(DEVICE_KEY_SALT), master_key)
+        .expand(device_id.as_bytes(), &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+
+    Ok(key)
+}
+
+pub fn decode_key(key_hex: &str) -> Result<[u8; 32]> {
+    hex::decode(key_hex.trim().trim_start_matches("0x"))
+        // Sin el detalle del error de hex: citaría caracteres de la clave
+        .map_err(|_| anyhow!("not valid hex"))?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| anyhow!("expected 32 bytes, got {}", bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_derived_keys_are_per_device() {
+        let registry = KeyRegistry::new().with_master_key(MASTER).unwrap();
+
+        let a = registry.key_for("ESP32-001").unwrap();
+        let b = registry.key_for("ESP32-002").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a, registry.key_for("ESP32-001").unwrap());
+
+        // Una lectura de un dispositivo no se descifra con la clave de otro
+        let encrypted = registry.handler_for("ESP32-001").unwrap().encrypt(&"secret", &[]).unwrap();
+        assert_eq!(encrypted.key_id, CryptoHandler::key_id_for(&a));
+        assert!(registry.handler_for("ESP32-002").unwrap().decrypt::<String>(&encrypted, &[]).is_err());
+        assert_eq!(registry.handler_for("ESP32-001").unwrap().decrypt::<String>(&encrypted, &[]).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_keystore_overrides_derivation() {
+        let path = std::env::temp_dir().join(format!("bae-keystore-{}.json", std::process::id()));
+        let stored = "ff".repeat(32);
+        std::fs::write(&path, format!(r#"{{"ESP32-001": "{}"}}"#, stored)).unwrap();
+
+        let registry = KeyRegistry::new()
+            .with_master_key(MASTER)
+            .unwrap()
+            .with_keystore(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(registry.key_for("ESP32-001").unwrap(), [0xff; 32]);
+        assert_ne!(registry.key_for("ESP32-002").unwrap(), [0xff; 32]);
+    }
+
+    #[test]
+    fn test_unknown_device_without_master_key() {
+        let registry = KeyRegistry::new();
+        assert!(registry.key_for("ESP32-001").is_err());
+        assert!(registry.handler_for("ESP32-001").is_err());
+    }
+
+    #[test]
+    fn test_rotation_keeps_device_history_readable() {
+        let mut keyring = Keyring::from_master_key(MASTER).unwrap();
+        let before = KeyRegistry::new().with_keyring(&keyring).unwrap();
+        let old = before.handler_for("ESP32-001").unwrap().encrypt(&"old", &[]).unwrap();
+
+        keyring.rotate(None).unwrap();
+        let after = KeyRegistry::new().with_keyring(&keyring).unwrap();
+        assert_ne!(before.key_for("ESP32-001").unwrap(), after.key_for("ESP32-001").unwrap());
+
+        let handler = after.handler_for("ESP32-001").unwrap();
+        let new = handler.encrypt(&"new", &[]).unwrap();
+        assert_eq!(new.key_version, 2);
+        assert_eq!(handler.decrypt::<String>(&old, &[]).unwrap(), "old");
+        assert_eq!(handler.decrypt::<String>(&new, &[]).unwrap(), "new");
+    }
+}
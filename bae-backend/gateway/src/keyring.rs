@@ -0,0 +1,179 @@
+use anyhow::{Result, anyhow};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::crypto::CryptoHandler;
+use crate::key_registry::decode_key;
+use crate::secrets::{self, SecretStore};
+
+/// Ruta del anillo de claves: `KEYRING_PATH` o `bae-keyring.json`
+pub fn keyring_path() -> String {
+    std::env::var("KEYRING_PATH").unwrap_or_else(|_| "bae-keyring.json".to_string())
+}
+
+/// Anillo de claves maestras versionadas (JSON `{ "active": 2, "keys": { "1": "<hex>", "2": "<hex>" } }`).
+/// Sólo la clave activa encripta; las retiradas se conservan para poder
+/// descifrar las lecturas históricas. Sin `Debug` para no volcar claves a los logs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Keyring {
+    pub active: u32,
+    keys: BTreeMap<u32, String>,
+}
+
+impl Keyring {
+    /// Anillo con una única clave (versión 1), p. ej. desde `ENCRYPTION_KEY`
+    pub fn from_master_key(key_hex: &str) -> Result<Self> {
+        decode_key(key_hex).map_err(|e| anyhow!("Invalid master key: {}", e))?;
+        Ok(Self {
+            active: 1,
+            keys: BTreeMap::from([(1, key_hex.trim().to_string())]),
+        })
+    }
+
+    /// Anillo configurado: el fichero de `KEYRING_PATH` si existe o, si no,
+    /// una única versión con el secreto `ENCRYPTION_KEY`
+    pub fn load_configured(secrets: &SecretStore) -> Result<Self> {
+        let path = keyring_path();
+        if Path::new(&path).exists() {
+            return Self::load(&path);
+        }
+
+        let encryption_key = secrets.require("ENCRYPTION_KEY")
+            .map_err(|e| anyhow!("{} (or create the keyring {})", e, path))?;
+        Self::from_master_key(encryption_key.expose())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read keyring {}: {}", path.display(), e))?;
+        let keyring: Self = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid keyring {}: {}", path.display(), e))?;
+
+        // Valida todas las claves y que la activa exista
+        keyring.decoded()?;
+        Ok(keyring)
+    }
+
+    /// Guarda el anillo de forma atómica (fichero temporal + rename), sólo legible por el dueño
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let content = serde_json::to_string_pretty(self)?;
+
+        // Un temporal de un guardado interrumpido puede tener otros permisos
+        match std::fs::remove_file(&tmp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("Failed to remove stale keyring {}: {}", tmp.display(), e)),
+        }
+        let mut file = secrets::create_private_file(&tmp)?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| anyhow!("Failed to write keyring {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, path)
+            .map_err(|e| anyhow!("Failed to replace keyring {}: {}", path.display(), e))?;
+
+        Ok(())
+    }
+
+    /// Añade una clave nueva (la indicada o una aleatoria) y la hace activa.
+    /// Devuelve la nueva versión.
+    pub fn rotate(&mut self, key_hex: Option<&str>) -> Result<u32> {
+        let key = match key_hex {
+            Some(key_hex) => decode_key(key_hex).map_err(|e| anyhow!("Invalid new key: {}", e))?,
+            None => {
+                let mut key = [0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut key);
+                key
+            }
+        };
+
+        // Se comparan los bytes: `0x…` y mayúsculas son la misma clave
+        if self.decoded()?.values().any(|existing| *existing == key) {
+            return Err(anyhow!("Key is already in the keyring"));
+        }
+
+        let version = self.keys.keys().next_back().copied().unwrap_or(0) + 1;
+        self.keys.insert(version, hex::encode(key));
+        self.active = version;
+
+        Ok(version)
+    }
+
+    /// Claves decodificadas por versión
+    pub fn decoded(&self) -> Result<BTreeMap<u32, [u8; 32]>> {
+        if !self.keys.contains_key(&self.active) {
+            return Err(anyhow!("Active key version {} is not in the keyring", self.active));
+        }
+
+        self.keys
+            .iter()
+            .map(|(version, key_hex)| {
+                if *version == 0 {
+                    return Err(anyhow!("Key version 0 is reserved for unversioned payloads"));
+                }
+                decode_key(key_hex)
+                    .map(|key| (*version, key))
+                    .map_err(|e| anyhow!("Invalid key version {}: {}", version, e))
+            })
+            .collect()
+    }
+
+    /// Versiones con su huella pública (`key_id`), para mostrar sin exponer claves
+    pub fn fingerprints(&self) -> Result<Vec<(u32, String)>> {
+        Ok(self.decoded()?
+            .iter()
+            .map(|(version, key)| (*version, CryptoHandler::key_id_for(key)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_rotate_keeps_retired_keys() {
+        let mut keyring = Keyring::from_master_key(MASTER).unwrap();
+        assert_eq!(keyring.rotate(None).unwrap(), 2);
+        assert_eq!(keyring.rotate(Some(&"ab".repeat(32))).unwrap(), 3);
+
+        assert_eq!(keyring.active, 3);
+        let keys = keyring.decoded().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(hex::encode(keys[&1]), MASTER);
+
+        // No se puede reintroducir una clave existente, se escriba como se escriba
+        assert!(keyring.rotate(Some(MASTER)).is_err());
+        assert!(keyring.rotate(Some(&format!("0x{}", MASTER))).is_err());
+        assert!(keyring.rotate(Some(&"AB".repeat(32))).is_err());
+        assert_eq!(keyring.decoded().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("bae-keyring-{}.json", std::process::id()));
+        let mut keyring = Keyring::from_master_key(MASTER).unwrap();
+        keyring.rotate(None).unwrap();
+        // Un temporal que sobrevivió a un guardado anterior, legible por todos
+        std::fs::write(path.with_extension("tmp"), "{}").unwrap();
+        keyring.save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let loaded = Keyring::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.active, 2);
+        assert_eq!(loaded.fingerprints().unwrap(), keyring.fingerprints().unwrap());
+    }
+}
@@ -0,0 +1,27 @@
+//! Núcleo del gateway Bae: ingesta, criptografía, claves, outbox y acceso al contrato.
+//! Lo comparten el binario `gateway` y las herramientas de operación (`bae-cli`, `bae-api`, `bae-indexer`).
+
+pub mod crypto;
+pub mod envelope;
+pub mod blockchain_sender;
+pub mod reader;
+pub mod mqtt_client;
+pub mod ingest;
+pub mod sealed;
+pub mod outbox;
+pub mod index_store;
+pub mod batch;
+pub mod nonce_manager;
+pub mod key_registry;
+pub mod device_registry;
+pub mod keyring;
+pub mod secrets;
+pub mod wallet;
+
+/// Segundos desde la época Unix
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
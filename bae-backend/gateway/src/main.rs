@@ -1,29 +1,237 @@
 use anyhow::{Result, anyhow};
-use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand};
 use tracing::{info, error, warn};
+use ethers::signers::LocalWallet;
+use ethers::types::Address;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify, Semaphore};
 
-mod crypto;
-mod blockchain_sender;
+use bae_core::metric::MetricCatalog;
+use bae_core::reading::SensorReading;
+use gateway::crypto::CryptoHandler;
+use gateway::device_registry::{Device, DeviceRegistry, DeviceRejected, DeviceStatus, DeviceUpdate};
+use gateway::envelope::EnvelopeFormat;
+use gateway::ingest::{self, Decoded, IngestTopics};
+use gateway::key_registry::{self, KeyRegistry};
+use gateway::keyring::{self, Keyring};
+use gateway::sealed::{SealedReading, SealedRejection};
+use gateway::secrets::{Secret, SecretStore};
+use gateway::wallet;
+use gateway::unix_now;
+use gateway::blockchain_sender::{BlockchainSender, DeviceStatusCache, ReplacementPolicy, TxDropped, TxOutcome};
+use gateway::mqtt_client::{Backoff, MqttConfig, MqttIngest, MqttProtocol};
+use gateway::reader::{OnChainReading, RegistryReader};
+use gateway::outbox::{DuplicateReading, Outbox, OutboxEntry, OutboxReading, OutboxStatus};
+use gateway::batch::{BatchDecision, BatchPolicy};
 
-use crypto::CryptoHandler;
-use blockchain_sender::BlockchainSender;
+const MAX_SUBMIT_ATTEMPTS: u32 = 10;
+/// Cuánto se fía el gateway del estado on-chain de un dispositivo antes de volver a consultarlo
+const DEVICE_STATUS_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Clave de encriptación de ejemplo: sólo se acepta con `--insecure-dev`
+const DEV_ENCRYPTION_KEY: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SensorReading {
-    device_id: String,
-    temperature: f32,
-    humidity: f32,
-    timestamp: u64,
+#[derive(Parser)]
+#[command(name = "gateway", version, about = "Bae IoT gateway: MQTT → Paseo Hub")]
+struct Cli {
+    /// Modo desarrollo: permite la clave de encriptación de ejemplo y una clave
+    /// de firma efímera si no están configuradas. Nunca en producción.
+    #[arg(long, global = true)]
+    insecure_dev: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Arranca el gateway (comando por defecto)
+    Run,
+    /// Lista las lecturas del outbox persistente
+    Outbox {
+        /// Filtrar por estado
+        #[arg(long, value_enum)]
+        status: Option<OutboxStatus>,
+        /// Máximo de entradas a mostrar
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Rota la clave maestra de encriptación: añade una versión nueva y la activa.
+    /// Las claves anteriores se conservan para descifrar lecturas antiguas.
+    RotateKey {
+        /// Nueva clave (64 caracteres hex); si se omite se genera una aleatoria
+        #[arg(long)]
+        key: Option<String>,
+    },
+    /// Gestiona el keystore JSON cifrado de la wallet (`WALLET_KEYSTORE`)
+    Keys {
+        #[command(subcommand)]
+        action: KeysCommand,
+    },
+    /// Gestiona el registro de dispositivos autorizados (`DEVICE_REGISTRY_PATH`).
+    /// Los cambios se aplican al gateway en marcha sin reiniciarlo.
+    Devices {
+        #[command(subcommand)]
+        action: DevicesCommand,
+    },
+    /// Administra el control de acceso del contrato (`RPC_URL`, `CONTRACT_ADDRESS` y la wallet
+    /// del owner u operador)
+    Chain {
+        #[command(subcommand)]
+        action: ChainCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevicesCommand {
+    /// Da de alta un dispositivo (activo)
+    Add {
+        device_id: String,
+        #[arg(long)]
+        owner: String,
+        /// Tipo de sensor (`climate`, `air-quality`...)
+        #[arg(long, default_value = "climate")]
+        sensor_type: String,
+        /// Clave pública Ed25519 (hex) si cifra y firma sus lecturas (modo extremo a extremo)
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+    /// Lista los dispositivos registrados
+    List {
+        /// Filtrar por estado
+        #[arg(long, value_enum)]
+        status: Option<DeviceStatus>,
+    },
+    /// Muestra un dispositivo
+    Show {
+        device_id: String,
+    },
+    /// Cambia dueño, tipo de sensor o clave pública
+    Update {
+        device_id: String,
+        #[arg(long)]
+        owner: Option<String>,
+        #[arg(long)]
+        sensor_type: Option<String>,
+        #[arg(long, conflicts_with = "clear_public_key")]
+        public_key: Option<String>,
+        /// Quita la clave pública: vuelve a enviar lecturas en claro
+        #[arg(long)]
+        clear_public_key: bool,
+    },
+    /// Suspende un dispositivo: sus lecturas se rechazan hasta reactivarlo
+    Suspend {
+        device_id: String,
+    },
+    /// Reactiva un dispositivo suspendido
+    Activate {
+        device_id: String,
+    },
+    /// Da de baja un dispositivo para siempre
+    Retire {
+        device_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Genera una wallet nueva y la guarda cifrada
+    Generate {
+        /// Ruta del keystore (por defecto `WALLET_KEYSTORE` o bae-wallet.json)
+        #[arg(long)]
+        keystore: Option<PathBuf>,
+        /// Sobrescribir el keystore si ya existe
+        #[arg(long)]
+        force: bool,
+    },
+    /// Cifra una clave privada existente (secreto `PRIVATE_KEY` o consola) en el keystore
+    Import {
+        #[arg(long)]
+        keystore: Option<PathBuf>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Muestra la dirección de la wallet del keystore
+    Address {
+        #[arg(long)]
+        keystore: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChainCommand {
+    /// Da de alta un dispositivo en el contrato (owner u operador)
+    RegisterDevice {
+        device_id: String,
+        /// Dirección del dueño del dispositivo
+        #[arg(long)]
+        owner: Address,
+    },
+    /// Revoca un dispositivo: el contrato rechaza sus lecturas (owner u operador)
+    RevokeDevice {
+        device_id: String,
+    },
+    /// Autoriza una wallet de gateway a enviar lecturas (sólo el owner)
+    Gateway {
+        address: Address,
+        /// Retirar la autorización
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Nombra un operador que puede registrar y revocar dispositivos (sólo el owner)
+    Operator {
+        address: Address,
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Muestra los roles de la wallet y si los dispositivos indicados están registrados
+    Status {
+        #[arg(long = "device")]
+        devices: Vec<String>,
+    },
+    /// Lecturas on-chain de un dispositivo, de la más reciente a la más antigua
+    /// (cifradas: `bae-cli` las descifra)
+    Readings {
+        device_id: String,
+        /// Sólo la lectura i-ésima del dispositivo (0 es la más antigua)
+        #[arg(long, conflicts_with = "latest")]
+        index: Option<u64>,
+        /// Sólo la última lectura
+        #[arg(long)]
+        latest: bool,
+        /// Máximo de lecturas a mostrar
+        #[arg(long, default_value_t = 10, conflicts_with_all = ["index", "latest"])]
+        limit: u64,
+    },
+}
+
+/// El device_id del topic no coincide con el del payload
+#[derive(Debug, thiserror::Error)]
+#[error("Device mismatch: topic says '{topic_device}', payload says '{payload_device}'")]
+struct DeviceMismatch {
+    topic_device: String,
+    payload_device: String,
+}
+
+/// Lo necesario para convertir un mensaje MQTT en lecturas del outbox;
+/// lo comparten las tareas que procesan cada mensaje
+struct Processor {
+    topics: IngestTopics,
+    keys: KeyRegistry,
+    devices: Arc<DeviceRegistry>,
+    /// Estado on-chain de los dispositivos: el contrato revierte sus lecturas si no están registrados
+    on_chain: Arc<DeviceStatusCache>,
+    metrics: MetricCatalog,
+    envelope: EnvelopeFormat,
 }
 
 struct Gateway {
-    mqtt_client: AsyncClient,
-    mqtt_eventloop: EventLoop,
-    crypto: CryptoHandler,
-    blockchain: Arc<Mutex<BlockchainSender>>,
+    mqtt: MqttIngest,
+    processor: Arc<Processor>,
+    blockchain: Arc<BlockchainSender>,
+    outbox: Arc<Outbox>,
+    batch_policy: BatchPolicy,
+    max_in_flight: usize,
     stats: Arc<Mutex<GatewayStats>>,
 }
 
@@ -32,53 +240,248 @@ struct GatewayStats {
     messages_received: u64,
     messages_processed: u64,
     messages_failed: u64,
+    device_mismatches: u64,
+    unauthorized_devices: u64,
+    invalid_signatures: u64,
+    duplicates: u64,
     transactions_sent: u64,
     transactions_confirmed: u64,
+    transactions_replaced: u64,
+    transactions_dropped: u64,
+    transactions_failed: u64,
+}
+
+/// Configuración del gateway, leída del entorno en `run_gateway`
+struct GatewayConfig {
+    mqtt: MqttConfig,
+    topics: IngestTopics,
+    rpc_url: String,
+    contract_address: String,
+    wallet: LocalWallet,
+    keyring: Keyring,
+    metrics: MetricCatalog,
+    envelope: EnvelopeFormat,
+    signing_key: Secret,
+    keystore_path: Option<String>,
+    device_registry_path: String,
+    outbox_path: String,
+    batch_policy: BatchPolicy,
+    max_in_flight: usize,
+    replacement: ReplacementPolicy,
+}
+
+impl Processor {
+    /// Parsea, valida, encripta y firma las lecturas de un mensaje (un pack SenML
+    /// puede traer varias), dejándolas listas para el outbox. Las lecturas cifradas
+    /// en el dispositivo sólo se verifican.
+    async fn process_sensor_data(&self, topic: &str, content_type: Option<&str>, payload: &[u8]) -> Result<Vec<OutboxReading>> {
+        // El topic es la identidad del publicador y decide el formato del payload,
+        // salvo que MQTT 5 traiga un content-type
+        let (format, topic_device) = self.topics.route(topic, content_type)?;
+        
+        // Sólo dispositivos registrados y activos; se comprueba antes de parsear nada.
+        // rusqlite bloquea, así que la consulta va a un hilo de `spawn_blocking`.
+        let devices = self.devices.clone();
+        let device_id = topic_device.to_string();
+        let device = tokio::task::spawn_blocking(move || devices.authorize(&device_id)).await??;
+        
+        // Una lectura que el contrato rechaza haría revertir todo lote en el que entre.
+        // Si el RPC no responde se encola igualmente: el submitter la aísla si revierte.
+        match self.on_chain.is_active(topic_device).await {
+            Ok(true) => {}
+            Ok(false) => return Err(DeviceRejected::NotOnChain(topic_device.to_string()).into()),
+            Err(e) => warn!("⚠️  Could not check on-chain status of {}: {}", topic_device, e),
+        }
+        
+        // Parsear datos del sensor y normalizarlos a lecturas internas
+        let decoded = ingest::decode(format, payload, topic_device, unix_now())
+            .map_err(|e| anyhow!("Failed to parse sensor data: {}", e))?;
+        
+        match decoded {
+            Decoded::Readings(readings) => readings
+                .into_iter()
+                .map(|reading| self.prepare_reading(reading, &device))
+                .collect(),
+            Decoded::Sealed(sealed) => Ok(vec![Self::accept_sealed(sealed, &device)?]),
+        }
+    }
+
+    /// Verifica una lectura cifrada y firmada en el dispositivo. El gateway no puede
+    /// descifrarla: se reenvía tal cual, con la firma del dispositivo.
+    fn accept_sealed(sealed: SealedReading, device: &Device) -> Result<OutboxReading> {
+        if device.device_id != sealed.device_id {
+            return Err(DeviceMismatch {
+                topic_device: device.device_id.clone(),
+                payload_device: sealed.device_id,
+            }.into());
+        }
+        
+        let public_key = device.verifying_key()?
+            .ok_or_else(|| SealedRejection::UnknownDevice(sealed.device_id.clone()))?;
+        sealed.verify(&public_key, unix_now())?;
+        
+        info!("📥 {} | end-to-end ({} bytes, key: {}) | ts={}",
+            sealed.device_id, sealed.payload.ciphertext.len(), sealed.payload.key_id, sealed.timestamp);
+        
+        Ok(sealed.into_outbox())
+    }
+
+    /// Valida, encripta y firma una lectura ya normalizada
+    fn prepare_reading(&self, reading: SensorReading, device: &Device) -> Result<OutboxReading> {
+        // El payload debe ser del dispositivo del topic
+        if device.device_id != reading.device_id {
+            return Err(DeviceMismatch {
+                topic_device: device.device_id.clone(),
+                payload_device: reading.device_id,
+            }.into());
+        }
+        
+        // Un dispositivo que cifra en origen no puede degradarse a texto en claro
+        if device.public_key.is_some() {
+            return Err(anyhow!("Device {} sends end-to-end readings; plaintext rejected", reading.device_id));
+        }
+        
+        // Validar datos
+        reading.validate(&self.metrics)?;
+        
+        info!("📥 {} | {} | ts={}", reading.device_id, reading.summary(), reading.timestamp);
+        
+        // Encriptar datos con la clave propia del dispositivo, ligando
+        // device_id y timestamp (que van en claro al contrato) como AAD
+        let crypto = self.keys.handler_for(&reading.device_id)?;
+        let aad = CryptoHandler::reading_aad(&reading.device_id, reading.timestamp);
+        let encrypted = crypto.seal_reading(&reading, self.envelope, &aad)
+            .map_err(|e| anyhow!("Encryption failed: {}", e))?;
+        
+        // Generar firma
+        let signature = crypto.sign(&encrypted, &reading.device_id, reading.timestamp)
+            .map_err(|e| anyhow!("Signing failed: {}", e))?;
+        
+        info!("🔐 Data encrypted ({:?}, ciphertext: {} bytes, nonce: {} bytes, key: {})", 
+            self.envelope, encrypted.ciphertext.len(), encrypted.nonce.len(), encrypted.key_id);
+        
+        Ok(OutboxReading {
+            device_id: reading.device_id,
+            ciphertext: encrypted.ciphertext,
+            nonce: encrypted.nonce,
+            key_id: encrypted.key_id,
+            key_version: encrypted.key_version,
+            signature,
+            timestamp: reading.timestamp,
+        })
+    }
 }
 
 impl Gateway {
-    async fn new(
-        mqtt_broker: &str,
-        mqtt_port: u16,
-        rpc_url: &str,
-        contract_address: &str,
-        private_key: &str,
-        encryption_key: &str,
-    ) -> Result<Self> {
+    async fn new(config: GatewayConfig) -> Result<Self> {
         info!("🔧 Initializing Gateway...");
         
         // Configuración MQTT mejorada
-        let mut mqttoptions = MqttOptions::new("bae-gateway", mqtt_broker, mqtt_port);
-        mqttoptions.set_keep_alive(std::time::Duration::from_secs(30));
-        mqttoptions.set_clean_session(true);
-        mqttoptions.set_max_packet_size(1024 * 1024, 1024 * 1024); // 1MB límite
-        
-        let (mqtt_client, mqtt_eventloop) = AsyncClient::new(mqttoptions, 10);
+        let subscriptions = config.topics.subscriptions();
+        let mqtt = match config.mqtt.protocol {
+            MqttProtocol::V4 => {
+                let mut mqttoptions = config.mqtt.mqtt_options("bae-gateway")?;
+                mqttoptions.set_keep_alive(std::time::Duration::from_secs(30));
+                // Sesión persistente: los mensajes QoS1 sin ACK se reentregan tras una caída
+                mqttoptions.set_clean_session(false);
+                mqttoptions.set_max_packet_size(1024 * 1024, 1024 * 1024); // 1MB límite
+                MqttIngest::new(mqttoptions, subscriptions)
+            }
+            MqttProtocol::V5 => {
+                let mut mqttoptions = config.mqtt.mqtt_options_v5("bae-gateway")?;
+                mqttoptions.set_keep_alive(std::time::Duration::from_secs(30));
+                mqttoptions.set_clean_start(false);
+                mqttoptions.set_max_packet_size(Some(1024 * 1024));
+                MqttIngest::new_v5(mqttoptions, subscriptions)
+            }
+        };
         
-        info!("🔐 Initializing crypto handler...");
-        let crypto = CryptoHandler::new(encryption_key)?;
+        info!("🔐 Initializing key registry...");
+        let mut keys = KeyRegistry::new()
+            .with_keyring(&config.keyring)?
+            .with_signing_key(config.signing_key.expose())?;
+        if let Some(path) = &config.keystore_path {
+            keys = keys.with_keystore(path)?;
+            info!("🗝️  Loaded {} device keys from {}", keys.stored_devices(), path);
+        }
+        info!("✍️  Signing public key (Ed25519): {}", keys.public_key_hex().unwrap_or_default());
         
         info!("🔗 Connecting to blockchain...");
-        let blockchain = BlockchainSender::new(rpc_url, contract_address, private_key).await?;
+        let blockchain = BlockchainSender::new(
+            &config.rpc_url,
+            &config.contract_address,
+            config.wallet,
+            config.replacement,
+        ).await?;
+        
+        info!("📦 Opening outbox: {}", config.outbox_path);
+        let outbox = Outbox::open(&config.outbox_path)?;
+        
+        info!("📇 Opening device registry: {}", config.device_registry_path);
+        let devices = DeviceRegistry::open(&config.device_registry_path)?;
+        let active = devices.count(DeviceStatus::Active)?;
+        info!("   {} active, {} suspended devices", active, devices.count(DeviceStatus::Suspended)?);
+        if active == 0 {
+            warn!("⚠️  No active devices: every reading will be rejected. Register them with `gateway devices add`");
+        }
+        // El contrato rechaza las lecturas de dispositivos que no tiene registrados
+        let blockchain = Arc::new(blockchain);
+        let on_chain = Arc::new(DeviceStatusCache::new(blockchain.clone(), DEVICE_STATUS_TTL));
+        for device in devices.list(Some(DeviceStatus::Active))? {
+            match on_chain.is_active(&device.device_id).await {
+                Ok(true) => {}
+                Ok(false) => warn!(
+                    "⚠️  Device {} is not registered on-chain: its readings will be rejected (`gateway chain register-device`)",
+                    device.device_id
+                ),
+                Err(e) => warn!("⚠️  Could not check on-chain status of {}: {}", device.device_id, e),
+            }
+        }
         
         Ok(Self { 
-            mqtt_client, 
-            mqtt_eventloop, 
-            crypto, 
-            blockchain: Arc::new(Mutex::new(blockchain)),
+            mqtt, 
+            processor: Arc::new(Processor {
+                topics: config.topics,
+                keys,
+                devices: Arc::new(devices),
+                on_chain,
+                metrics: config.metrics,
+                envelope: config.envelope,
+            }),
+            blockchain,
+            outbox: Arc::new(outbox),
+            batch_policy: config.batch_policy,
+            max_in_flight: config.max_in_flight,
             stats: Arc::new(Mutex::new(GatewayStats::default())),
         })
     }
 
-    async fn start(&mut self) -> Result<()> {
-        // Suscribirse al topic de sensores
-        self.mqtt_client.subscribe("bae/sensors/+/data", QoS::AtLeastOnce).await?;
+    async fn start(self) -> Result<()> {
+        // La suscripción se (re)hace en cada ConnAck dentro de MqttIngest
+        let mut messages = self.mqtt.spawn(100);
         
-        info!("✅ Gateway listening on MQTT topic: bae/sensors/+/data");
+        info!("✅ Gateway listening on MQTT topics: {}", self.processor.topics.subscriptions().join(", "));
         info!("🔗 Connected to Paseo Hub");
         info!("📊 Gateway ready to process sensor data");
         info!("");
         
+        // Las lecturas pendientes de una ejecución anterior se reenvían primero
+        let pending = self.outbox.count(OutboxStatus::Pending)?;
+        if pending > 0 {
+            info!("♻️  Replaying {} pending readings from outbox", pending);
+        }
+        
+        let wake_submitter = Arc::new(Notify::new());
+        tokio::spawn(Self::run_submitter(
+            self.outbox.clone(),
+            self.blockchain.clone(),
+            self.processor.on_chain.clone(),
+            self.stats.clone(),
+            wake_submitter.clone(),
+            self.batch_policy.clone(),
+            self.max_in_flight,
+        ));
+        
         // Spawn task para mostrar estadísticas periódicamente
         let stats_clone = self.stats.clone();
         tokio::spawn(async move {
@@ -86,153 +489,291 @@ impl Gateway {
             loop {
                 interval.tick().await;
                 let stats = stats_clone.lock().await;
-                info!("📊 Stats (last 60s): Received={}, Processed={}, Failed={}, TX Sent={}, TX Confirmed={}", 
+                info!("📊 Stats (last 60s): Received={}, Processed={}, Failed={}, Mismatched={}, Unauthorized={}, Bad Signatures={}, Duplicates={}, TX Sent={}, TX Confirmed={} (Replaced={}), TX Dropped={}, TX Failed={}", 
                     stats.messages_received, 
                     stats.messages_processed, 
                     stats.messages_failed,
+                    stats.device_mismatches,
+                    stats.unauthorized_devices,
+                    stats.invalid_signatures,
+                    stats.duplicates,
                     stats.transactions_sent,
-                    stats.transactions_confirmed
+                    stats.transactions_confirmed,
+                    stats.transactions_replaced,
+                    stats.transactions_dropped,
+                    stats.transactions_failed
                 );
             }
         });
         
-        // Loop principal de eventos MQTT
-        loop {
-            match self.mqtt_eventloop.poll().await {
-                Ok(Event::Incoming(Packet::ConnAck(_))) => {
-                    info!("📡 Connected to MQTT broker");
-                }
-                Ok(Event::Incoming(Packet::Publish(publish))) => {
-                    let mut stats = self.stats.lock().await;
-                    stats.messages_received += 1;
-                    drop(stats);
-                    
-                    // Procesar mensaje en una tarea separada para no bloquear el loop
-                    let payload = publish.payload.to_vec();
-                    let crypto = self.crypto.clone();
-                    let blockchain = self.blockchain.clone();
-                    let stats = self.stats.clone();
-                    
-                    tokio::spawn(async move {
-                        match Self::process_sensor_data(payload, crypto, blockchain).await {
-                            Ok(_) => {
+        // Loop principal de mensajes MQTT
+        while let Some(message) = messages.recv().await {
+            let mut stats = self.stats.lock().await;
+            stats.messages_received += 1;
+            drop(stats);
+            
+            // Procesar mensaje en una tarea separada para no bloquear el loop
+            let processor = self.processor.clone();
+            let outbox = self.outbox.clone();
+            let wake_submitter = wake_submitter.clone();
+            let stats = self.stats.clone();
+            
+            tokio::spawn(async move {
+                let topic = message.topic.as_str();
+                let content_type = message.content_type.as_deref();
+                
+                match processor.process_sensor_data(topic, content_type, &message.payload).await {
+                    Ok(readings) => {
+                        // Sólo se hace ACK cuando las lecturas están en disco; si falla la
+                        // escritura el broker las reentregará. rusqlite bloquea, así que la
+                        // escritura va a un hilo de `spawn_blocking` y no a los workers de tokio.
+                        let enqueued = tokio::task::spawn_blocking(move || {
+                            let ids = outbox.enqueue_all(&readings)?;
+                            Ok::<_, anyhow::Error>((ids, readings))
+                        })
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|result| result);
+                        
+                        match enqueued {
+                            Ok((ids, readings)) => {
+                                for (id, reading) in ids.iter().zip(&readings) {
+                                    info!("📦 Queued reading #{} for {}", id, reading.device_id);
+                                }
+                                wake_submitter.notify_one();
                                 let mut s = stats.lock().await;
                                 s.messages_processed += 1;
                             }
+                            Err(e) if e.is::<DuplicateReading>() => {
+                                // Reentrega del broker o repetición de un mensaje capturado,
+                                // cifrado o en claro (se detecta por dispositivo y timestamp)
+                                warn!("🔁 Ignored ({}): {}", topic, e);
+                                let mut s = stats.lock().await;
+                                s.duplicates += 1;
+                            }
                             Err(e) => {
-                                error!("❌ Processing error: {}", e);
+                                error!("❌ Outbox write failed ({}): {}", topic, e);
                                 let mut s = stats.lock().await;
                                 s.messages_failed += 1;
+                                return;
                             }
                         }
-                    });
+                    }
+                    Err(e) if e.is::<DeviceMismatch>() => {
+                        warn!("🚫 Rejected ({}): {}", topic, e);
+                        let mut s = stats.lock().await;
+                        s.device_mismatches += 1;
+                    }
+                    Err(e) if e.is::<DeviceRejected>() => {
+                        warn!("🚫 Rejected ({}): {}", topic, e);
+                        let mut s = stats.lock().await;
+                        s.unauthorized_devices += 1;
+                    }
+                    Err(e) if e.is::<SealedRejection>() => {
+                        warn!("🚫 Rejected ({}): {}", topic, e);
+                        let mut s = stats.lock().await;
+                        s.invalid_signatures += 1;
+                    }
+                    Err(e) => {
+                        error!("❌ Processing error ({}): {}", topic, e);
+                        let mut s = stats.lock().await;
+                        s.messages_failed += 1;
+                    }
                 }
-                Ok(Event::Incoming(Packet::Disconnect)) => {
-                    warn!("⚠️  Disconnected from MQTT broker");
+                
+                // Mensajes inválidos también se confirman para que no se reentreguen
+                if let Err(e) = message.ack().await {
+                    warn!("⚠️  {}", e);
                 }
-                Ok(_) => {}
-                Err(e) => {
-                    error!("❌ MQTT error: {}", e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                }
-            }
+            });
         }
+        
+        Err(anyhow!("MQTT ingest stopped unexpectedly"))
     }
 
-    async fn process_sensor_data(
-        payload: Vec<u8>,
-        crypto: CryptoHandler,
-        blockchain: Arc<Mutex<BlockchainSender>>,
-    ) -> Result<()> {
-        // Parsear datos del sensor
-        let reading: SensorReading = serde_json::from_slice(&payload)
-            .map_err(|e| anyhow!("Failed to parse sensor data: {}", e))?;
-        
-        // Validar datos
-        Self::validate_reading(&reading)?;
-        
-        info!(
-            "📥 {} | T={:.1}°C H={:.1}% | ts={}",
-            reading.device_id, reading.temperature, reading.humidity, reading.timestamp
+    /// Drena el outbox en orden, agrupando lecturas según `BatchPolicy`.
+    /// Hasta `max_in_flight` transacciones se envían y confirman en paralelo;
+    /// cada lectura fallida espera un backoff exponencial antes de reintentarse
+    /// y, al agotar `MAX_SUBMIT_ATTEMPTS`, queda como `failed`.
+    async fn run_submitter(
+        outbox: Arc<Outbox>,
+        blockchain: Arc<BlockchainSender>,
+        on_chain: Arc<DeviceStatusCache>,
+        stats: Arc<Mutex<GatewayStats>>,
+        wake: Arc<Notify>,
+        policy: BatchPolicy,
+        max_in_flight: usize,
+    ) {
+        let backoff = Backoff::new(
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_secs(300),
         );
-        
-        // Encriptar datos
-        let encrypted = crypto.encrypt(&reading)
-            .map_err(|e| anyhow!("Encryption failed: {}", e))?;
-        
-        // Generar firma
-        let signature = crypto.sign(&encrypted)
-            .map_err(|e| anyhow!("Signing failed: {}", e))?;
-        
-        info!("🔐 Data encrypted (ciphertext: {} bytes, nonce: {} bytes)", 
-            encrypted.ciphertext.len(), encrypted.nonce.len());
-        
-        // Enviar a blockchain con retry logic
-        let mut attempts = 0;
-        let max_attempts = 3;
+        let slots = Arc::new(Semaphore::new(max_in_flight));
+        // Lecturas ya asignadas a una transacción en curso (o esperando su backoff)
+        let in_flight: Arc<std::sync::Mutex<HashSet<i64>>> = Arc::default();
         
         loop {
-            attempts += 1;
+            let Ok(slot) = slots.clone().acquire_owned().await else {
+                return;
+            };
             
-            let blockchain_guard = blockchain.lock().await;
-            match blockchain_guard.submit_sensor_data(
-                &reading.device_id,
-                &encrypted.ciphertext,
-                &encrypted.nonce,
-                &signature,
-                reading.timestamp,
-            ).await {
-                Ok(tx_hash) => {
-                    info!("✅ TX confirmed: {}", tx_hash);
-                    return Ok(());
+            // Se lee una lectura de más para saber si el lote está lleno
+            let busy = in_flight.lock().unwrap().clone();
+            let pending = match outbox.pending(policy.max_readings + 1 + busy.len()) {
+                Ok(pending) => pending
+                    .into_iter()
+                    .filter(|entry| !busy.contains(&entry.id))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    error!("❌ Outbox read failed: {}", e);
+                    drop(slot);
+                    tokio::time::sleep(backoff.delay_for_attempt(0)).await;
+                    continue;
                 }
-                Err(e) if attempts < max_attempts => {
-                    warn!("⚠️  Attempt {}/{} failed: {}. Retrying...", attempts, max_attempts, e);
-                    drop(blockchain_guard);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            };
+            
+            let count = match policy.decide(&pending, unix_now()) {
+                BatchDecision::Submit(count) => count,
+                BatchDecision::Wait(max_wait) => {
+                    // Esperar nuevas lecturas o a que venza la latencia del lote
+                    drop(slot);
+                    let _ = tokio::time::timeout(max_wait, wake.notified()).await;
+                    continue;
                 }
-                Err(e) => {
-                    error!("❌ All {} attempts failed: {}", max_attempts, e);
-                    return Err(e);
+            };
+            let batch: Vec<_> = pending.into_iter().take(count).collect();
+            in_flight.lock().unwrap().extend(batch.iter().map(|e| e.id));
+            
+            let outbox = outbox.clone();
+            let blockchain = blockchain.clone();
+            let on_chain = on_chain.clone();
+            let stats = stats.clone();
+            let wake = wake.clone();
+            let in_flight = in_flight.clone();
+            let policy = policy.clone();
+            let backoff = backoff.clone();
+            
+            tokio::spawn(async move {
+                let retry_delay = Self::submit_batch(&outbox, &blockchain, &on_chain, &stats, &policy, &backoff, &batch).await;
+                drop(slot);
+                
+                if let Some(delay) = retry_delay {
+                    tokio::time::sleep(delay).await;
                 }
-            }
+                
+                let mut busy = in_flight.lock().unwrap();
+                for entry in &batch {
+                    busy.remove(&entry.id);
+                }
+                drop(busy);
+                wake.notify_one();
+            });
         }
     }
 
-    fn validate_reading(reading: &SensorReading) -> Result<()> {
-        // Validar device_id
-        if reading.device_id.is_empty() || reading.device_id.len() > 100 {
-            return Err(anyhow!("Invalid device_id length"));
-        }
+    /// Envía un lote y actualiza el outbox. Devuelve la espera antes de reintentar si falló.
+    async fn submit_batch(
+        outbox: &Outbox,
+        blockchain: &BlockchainSender,
+        on_chain: &DeviceStatusCache,
+        stats: &Mutex<GatewayStats>,
+        policy: &BatchPolicy,
+        backoff: &Backoff,
+        batch: &[OutboxEntry],
+    ) -> Option<std::time::Duration> {
+        stats.lock().await.transactions_sent += 1;
         
-        // Validar rangos de temperatura (-50°C a 100°C)
-        if !(-50.0..=100.0).contains(&reading.temperature) {
-            return Err(anyhow!("Temperature out of range: {}", reading.temperature));
-        }
+        let result = if policy.is_batching() {
+            let readings: Vec<_> = batch.iter().map(|e| e.reading.clone()).collect();
+            blockchain.submit_sensor_data_batch(&readings).await
+        } else {
+            let reading = &batch[0].reading;
+            blockchain.submit_sensor_data(
+                &reading.device_id,
+                &reading.ciphertext,
+                &reading.nonce,
+                &reading.signature,
+                reading.timestamp,
+            ).await
+        };
+        let ids: Vec<_> = batch.iter().map(|e| e.id).collect();
         
-        // Validar rangos de humedad (0% a 100%)
-        if !(0.0..=100.0).contains(&reading.humidity) {
-            return Err(anyhow!("Humidity out of range: {}", reading.humidity));
+        match result {
+            Ok(submitted) => {
+                info!("✅ TX confirmed: {} (outbox {:?})", submitted.tx_hash, ids);
+                for entry in batch {
+                    if let Err(e) = outbox.mark_submitted(entry.id, &submitted.tx_hash) {
+                        error!("❌ Failed to mark outbox #{} as submitted: {}", entry.id, e);
+                    }
+                }
+                let mut s = stats.lock().await;
+                s.transactions_confirmed += 1;
+                if submitted.outcome == TxOutcome::Replaced {
+                    s.transactions_replaced += 1;
+                }
+                None
+            }
+            Err(e) => {
+                if e.is::<TxDropped>() {
+                    stats.lock().await.transactions_dropped += 1;
+                } else if Self::isolate_unregistered(outbox, on_chain, batch).await {
+                    // El resto del lote vuelve a la cola sin gastar un intento
+                    return None;
+                }
+                
+                let mut gave_up = 0;
+                for entry in batch {
+                    match outbox.record_failure(entry.id, &e.to_string(), MAX_SUBMIT_ATTEMPTS) {
+                        Ok(OutboxStatus::Failed) => gave_up += 1,
+                        Ok(_) => {}
+                        Err(db_error) => {
+                            error!("❌ Failed to record outbox #{} failure: {}", entry.id, db_error);
+                        }
+                    }
+                }
+                
+                if gave_up > 0 {
+                    error!("❌ {} readings failed after {} attempts: {}", gave_up, MAX_SUBMIT_ATTEMPTS, e);
+                    stats.lock().await.transactions_failed += 1;
+                }
+                
+                let attempt = batch[0].attempts;
+                let delay = backoff.delay_for_attempt(attempt);
+                warn!("⚠️  Attempt {}/{} for outbox {:?} failed: {}. Retrying in {:?}...",
+                    attempt + 1, MAX_SUBMIT_ATTEMPTS, ids, e, delay);
+                Some(delay)
+            }
         }
+    }
+
+    /// Un dispositivo no registrado (o revocado) on-chain hace revertir el lote entero.
+    /// Tras un fallo se consulta el estado real de cada dispositivo del lote y sus
+    /// lecturas pasan a `failed`. Devuelve si se apartó alguna.
+    async fn isolate_unregistered(outbox: &Outbox, on_chain: &DeviceStatusCache, batch: &[OutboxEntry]) -> bool {
+        let mut device_ids: Vec<_> = batch.iter().map(|e| e.reading.device_id.as_str()).collect();
+        device_ids.sort_unstable();
+        device_ids.dedup();
         
-        // Validar timestamp (no puede ser futuro ni muy antiguo)
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        let max_age = 3600; // 1 hora
-        let max_future = 300; // 5 minutos
-        
-        if reading.timestamp > now + max_future {
-            return Err(anyhow!("Timestamp is in the future"));
+        let mut unregistered = HashSet::new();
+        for device_id in device_ids {
+            match on_chain.refresh(device_id).await {
+                Ok(false) => {
+                    unregistered.insert(device_id);
+                }
+                Ok(true) => {}
+                Err(e) => warn!("⚠️  Could not check on-chain status of {}: {}", device_id, e),
+            }
         }
         
-        if reading.timestamp + max_age < now {
-            return Err(anyhow!("Timestamp is too old"));
+        for entry in batch.iter().filter(|e| unregistered.contains(e.reading.device_id.as_str())) {
+            let reason = DeviceRejected::NotOnChain(entry.reading.device_id.clone()).to_string();
+            warn!("🚫 Outbox #{}: {}, not retrying", entry.id, reason);
+            if let Err(e) = outbox.mark_failed(entry.id, &reason) {
+                error!("❌ Failed to mark outbox #{} as failed: {}", entry.id, e);
+            }
         }
         
-        Ok(())
+        !unregistered.is_empty()
     }
 }
 
@@ -249,46 +790,615 @@ async fn main() -> Result<()> {
         )
         .init();
     
-    info!("🚀 Starting Bae Gateway v0.1.0");
-    info!("");
+    let cli = Cli::parse();
     
-    // Leer configuración
-    let mqtt_broker = std::env::var("MQTT_BROKER")
-        .unwrap_or_else(|_| "broker.hivemq.com".to_string());
-    let mqtt_port: u16 = std::env::var("MQTT_PORT")
-        .unwrap_or_else(|_| "1883".to_string())
-        .parse()
-        .map_err(|_| anyhow!("Invalid MQTT_PORT"))?;
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run_gateway(cli.insecure_dev).await,
+        Command::Outbox { status, limit } => list_outbox(status, limit),
+        Command::RotateKey { key } => rotate_key(key.as_deref()),
+        Command::Keys { action } => manage_keys(action),
+        Command::Devices { action } => manage_devices(action),
+        Command::Chain { action } => manage_chain(action).await,
+    }
+}
+
+/// Anillo de claves maestras, con las mismas reglas que `bae-api` y `bae-cli`
+/// (`Keyring::load_configured`). La clave de ejemplo sólo se admite en modo `--insecure-dev`.
+fn load_keyring(secrets: &SecretStore, insecure_dev: bool) -> Result<Keyring> {
+    let path = keyring::keyring_path();
+    let configured = std::path::Path::new(&path).exists() || secrets.get("ENCRYPTION_KEY")?.is_some();
+    let keyring = if configured || !insecure_dev {
+        let keyring = Keyring::load_configured(secrets)
+            .map_err(|e| anyhow!("{}; pass --insecure-dev to run with the example key", e))?;
+        info!("🔑 Encryption keyring loaded (active version {})", keyring.active);
+        keyring
+    } else {
+        warn!("⚠️  ENCRYPTION_KEY not set, using the well-known dev key (--insecure-dev)");
+        Keyring::from_master_key(DEV_ENCRYPTION_KEY)?
+    };
+
+    let dev_key = key_registry::decode_key(DEV_ENCRYPTION_KEY)?;
+    if !insecure_dev && keyring.decoded()?.get(&keyring.active) == Some(&dev_key) {
+        return Err(anyhow!(
+            "The active encryption key is the public example key; configure a real key or pass --insecure-dev"
+        ));
+    }
+
+    Ok(keyring)
+}
+
+/// Clave de firma Ed25519; en modo `--insecure-dev` se genera una efímera si falta
+fn load_signing_key(secrets: &SecretStore, insecure_dev: bool) -> Result<Secret> {
+    let signing_key = match secrets.get("SIGNING_KEY")? {
+        Some(key) => key,
+        None if insecure_dev => {
+            warn!("⚠️  SIGNING_KEY not set, using an ephemeral key (--insecure-dev)");
+            let mut seed = [0u8; 32];
+            rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut seed);
+            Secret::insecure_dev(hex::encode(seed))
+        }
+        None => return Err(anyhow!(
+            "SIGNING_KEY is not set (32-byte Ed25519 seed in hex via SIGNING_KEY_FILE, SIGNING_KEY or SECRETS_PATH)"
+        )),
+    };
+    info!("🔑 SIGNING_KEY loaded from {}", signing_key.source());
+
+    Ok(signing_key)
+}
+
+fn outbox_path() -> String {
+    std::env::var("OUTBOX_PATH").unwrap_or_else(|_| "bae-outbox.db".to_string())
+}
+
+/// `RPC_URL` y `CONTRACT_ADDRESS`
+fn contract_config() -> Result<(String, String)> {
     let rpc_url = std::env::var("RPC_URL")
         .map_err(|_| anyhow!("RPC_URL must be set"))?;
     let contract_address = std::env::var("CONTRACT_ADDRESS")
         .map_err(|_| anyhow!("CONTRACT_ADDRESS must be set"))?;
-    let private_key = std::env::var("PRIVATE_KEY")
-        .map_err(|_| anyhow!("PRIVATE_KEY must be set"))?;
-    let encryption_key = std::env::var("ENCRYPTION_KEY")
-        .unwrap_or_else(|_| {
-            warn!("⚠️  ENCRYPTION_KEY not set, using default (NOT SECURE FOR PRODUCTION)");
-            "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-        });
+    
+    Ok((rpc_url, contract_address))
+}
+
+fn device_registry_path() -> String {
+    std::env::var("DEVICE_REGISTRY_PATH").unwrap_or_else(|_| "bae-devices.db".to_string())
+}
+
+async fn run_gateway(insecure_dev: bool) -> Result<()> {
+    info!("🚀 Starting Bae Gateway v0.1.0");
+    info!("");
+    
+    // Leer configuración
+    let mqtt_config = MqttConfig::from_env()?;
+    let batch_policy = BatchPolicy::from_env()?;
+    let replacement = ReplacementPolicy::from_env()?;
+    let max_in_flight: usize = std::env::var("MAX_IN_FLIGHT_TXS")
+        .unwrap_or_else(|_| "4".to_string())
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| anyhow!("Invalid MAX_IN_FLIGHT_TXS"))?;
+    let (rpc_url, contract_address) = contract_config()?;
+    
+    // Secretos: ficheros (*_FILE), variables de entorno o SECRETS_PATH.
+    // Sólo se loguea su origen, nunca su contenido.
+    if insecure_dev {
+        warn!("⚠️  Running with --insecure-dev: NOT SECURE FOR PRODUCTION");
+    }
+    let secrets = SecretStore::from_env()?;
+    let wallet = wallet::load(&secrets)?;
+    let signing_key = load_signing_key(&secrets, insecure_dev)?;
+    let keyring = load_keyring(&secrets, insecure_dev)?;
+    let envelope = EnvelopeFormat::from_env()?;
+    let topics = IngestTopics::from_env()?;
+    let metrics = MetricCatalog::from_env()?;
     
     // Mostrar configuración (ocultar claves sensibles)
     info!("⚙️  Configuration:");
-    info!("   MQTT Broker: {}:{}", mqtt_broker, mqtt_port);
+    info!("   MQTT Broker: {}", mqtt_config.describe());
+    info!("   MQTT Topics: {}", topics.subscriptions().join(", "));
     info!("   RPC URL: {}", rpc_url);
     info!("   Contract: {}", contract_address);
-    info!("   Private Key: {}...{}", &private_key[..10], &private_key[private_key.len()-4..]);
-    info!("   Encryption Key: configured ({} bytes)", encryption_key.len() / 2);
+    info!("   Outbox: {}", outbox_path());
+    info!("   Device registry: {}", device_registry_path());
+    info!("   Max in-flight TXs: {}", max_in_flight);
+    info!("   Stuck TX replacement: after {:?}, +{}% gas, max {} times",
+        replacement.stuck_after, replacement.gas_bump_percent, replacement.max_replacements);
+    if batch_policy.is_batching() {
+        info!("   Batching: max {} readings, {} bytes, {:?} latency",
+            batch_policy.max_readings, batch_policy.max_bytes, batch_policy.max_latency);
+    }
+    info!("   Envelope format: {:?}", envelope);
+    info!("   Metrics: {} known{}", metrics.len(), if metrics.strict { " (strict)" } else { "" });
+    info!("   Encryption Keys: {} version(s), active v{}", keyring.fingerprints()?.len(), keyring.active);
     info!("");
     
     // Crear y arrancar gateway
-    let mut gateway = Gateway::new(
-        &mqtt_broker,
-        mqtt_port,
-        &rpc_url,
-        &contract_address,
-        &private_key,
-        &encryption_key
-    ).await?;
+    let gateway = Gateway::new(GatewayConfig {
+        mqtt: mqtt_config,
+        topics,
+        rpc_url,
+        contract_address,
+        wallet,
+        keyring,
+        metrics,
+        envelope,
+        signing_key,
+        keystore_path: std::env::var("KEYSTORE_PATH").ok(),
+        device_registry_path: device_registry_path(),
+        outbox_path: outbox_path(),
+        batch_policy,
+        max_in_flight,
+        replacement,
+    }).await?;
     
     gateway.start().await
-}
\ No newline at end of file
+}
+
+fn list_outbox(status: Option<OutboxStatus>, limit: usize) -> Result<()> {
+    let outbox = Outbox::open(outbox_path())?;
+    
+    println!(
+        "Outbox {}: {} pending, {} failed, {} submitted",
+        outbox_path(),
+        outbox.count(OutboxStatus::Pending)?,
+        outbox.count(OutboxStatus::Failed)?,
+        outbox.count(OutboxStatus::Submitted)?,
+    );
+    println!();
+    println!("{:>6}  {:<9}  {:<16}  {:>10}  {:>8}  DETAIL", "ID", "STATUS", "DEVICE", "TIMESTAMP", "ATTEMPTS");
+    
+    for entry in outbox.list(status, limit)? {
+        let detail = match entry.status {
+            OutboxStatus::Submitted => entry.tx_hash.unwrap_or_default(),
+            _ => entry.last_error.unwrap_or_default(),
+        };
+        println!(
+            "{:>6}  {:<9}  {:<16}  {:>10}  {:>8}  {}",
+            entry.id,
+            entry.status.as_str(),
+            entry.reading.device_id,
+            entry.reading.timestamp,
+            entry.attempts,
+            detail,
+        );
+    }
+    
+    Ok(())
+}
+
+fn rotate_key(key: Option<&str>) -> Result<()> {
+    let path = keyring::keyring_path();
+    let mut keyring = if std::path::Path::new(&path).exists() {
+        Keyring::load(&path)?
+    } else {
+        // Primera rotación: la clave actual pasa a ser la versión 1
+        let encryption_key = SecretStore::from_env()?.require("ENCRYPTION_KEY")?;
+        Keyring::from_master_key(encryption_key.expose())?
+    };
+    
+    let version = keyring.rotate(key)?;
+    keyring.save(&path)?;
+    
+    println!("Keyring {}: active key is now v{}", path, version);
+    for (version, key_id) in keyring.fingerprints()? {
+        let marker = if version == keyring.active { "active" } else { "retired" };
+        println!("  v{:<4} {}  {}", version, key_id, marker);
+    }
+    println!();
+    println!("Restart the gateway (and the API) to start encrypting with the new key.");
+    
+    Ok(())
+}
+
+fn manage_keys(action: KeysCommand) -> Result<()> {
+    let secrets = SecretStore::from_env()?;
+    
+    match action {
+        KeysCommand::Generate { keystore, force } => {
+            let path = wallet::keystore_path(keystore);
+            let mut key = [0u8; 32];
+            rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut key);
+            let password = wallet::password(&secrets, true)?;
+            let created = wallet::save_keystore(&path, &key, &password, force)?;
+            
+            println!("Wallet keystore written to {}", path.display());
+            println!("Address: {}", wallet::address(&created));
+            println!("Fund this address and set WALLET_KEYSTORE={} for the gateway.", path.display());
+        }
+        KeysCommand::Import { keystore, force } => {
+            let path = wallet::keystore_path(keystore);
+            let private_key = match secrets.get("PRIVATE_KEY")? {
+                Some(key) => key,
+                None => Secret::prompted(wallet::prompt_hidden("Private key (hex): ")?),
+            };
+            let key = hex::decode(private_key.expose().trim().trim_start_matches("0x"))
+                .ok()
+                .filter(|bytes| bytes.len() == 32)
+                .ok_or_else(|| anyhow!("Invalid private key format (expected 32-byte hex)"))?;
+            let password = wallet::password(&secrets, true)?;
+            let imported = wallet::save_keystore(&path, &key, &password, force)?;
+            
+            println!("Private key imported into {}", path.display());
+            println!("Address: {}", wallet::address(&imported));
+            println!("Remove PRIVATE_KEY from the environment and set WALLET_KEYSTORE={} instead.", path.display());
+        }
+        KeysCommand::Address { keystore } => {
+            let path = wallet::keystore_path(keystore);
+            let password = wallet::password(&secrets, false)?;
+            println!("{}", wallet::address(&wallet::from_keystore(&path, &password)?));
+        }
+    }
+    
+    Ok(())
+}
+
+fn manage_devices(action: DevicesCommand) -> Result<()> {
+    let registry = DeviceRegistry::open(device_registry_path())?;
+    
+    match action {
+        DevicesCommand::Add { device_id, owner, sensor_type, public_key } => {
+            let device = registry.register(&device_id, &owner, &sensor_type, public_key.as_deref())?;
+            println!("Registered {}", device.device_id);
+            print_device(&device);
+        }
+        DevicesCommand::List { status } => {
+            println!("{:<20}  {:<9}  {:<16}  {:<12}  E2E", "DEVICE", "STATUS", "OWNER", "SENSOR");
+            for device in registry.list(status)? {
+                println!(
+                    "{:<20}  {:<9}  {:<16}  {:<12}  {}",
+                    device.device_id,
+                    device.status.as_str(),
+                    device.owner,
+                    device.sensor_type,
+                    if device.public_key.is_some() { "yes" } else { "no" },
+                );
+            }
+        }
+        DevicesCommand::Show { device_id } => {
+            let device = registry.get(&device_id)?
+                .ok_or_else(|| anyhow!("Device {} is not registered", device_id))?;
+            print_device(&device);
+        }
+        DevicesCommand::Update { device_id, owner, sensor_type, public_key, clear_public_key } => {
+            let public_key = match (public_key, clear_public_key) {
+                (Some(key), _) => Some(Some(key)),
+                (None, true) => Some(None),
+                (None, false) => None,
+            };
+            let device = registry.update(&device_id, DeviceUpdate { owner, sensor_type, public_key })?;
+            print_device(&device);
+        }
+        DevicesCommand::Suspend { device_id } => {
+            let device = registry.set_status(&device_id, DeviceStatus::Suspended)?;
+            println!("{} is now {}", device.device_id, device.status.as_str());
+        }
+        DevicesCommand::Activate { device_id } => {
+            let device = registry.set_status(&device_id, DeviceStatus::Active)?;
+            println!("{} is now {}", device.device_id, device.status.as_str());
+        }
+        DevicesCommand::Retire { device_id } => {
+            let device = registry.set_status(&device_id, DeviceStatus::Retired)?;
+            println!("{} is now {}", device.device_id, device.status.as_str());
+        }
+    }
+    
+    Ok(())
+}
+
+async fn manage_chain(action: ChainCommand) -> Result<()> {
+    let (rpc_url, contract_address) = contract_config()?;
+    
+    // Consulta de sólo lectura: no necesita wallet
+    if let ChainCommand::Readings { device_id, index, latest, limit } = action {
+        return show_device_readings(&RegistryReader::new(&rpc_url, &contract_address)?, &device_id, index, latest, limit).await;
+    }
+    
+    let wallet = wallet::load(&SecretStore::from_env()?)?;
+    let chain = BlockchainSender::connect(&rpc_url, &contract_address, wallet, ReplacementPolicy::from_env()?).await?;
+    
+    let tx = match action {
+        ChainCommand::RegisterDevice { device_id, owner } => chain.register_device(&device_id, owner).await?,
+        ChainCommand::RevokeDevice { device_id } => chain.revoke_device(&device_id).await?,
+        ChainCommand::Gateway { address, revoke } => chain.set_gateway(address, !revoke).await?,
+        ChainCommand::Operator { address, revoke } => chain.set_operator(address, !revoke).await?,
+        ChainCommand::Status { devices } => {
+            let roles = chain.roles(chain.address()).await?;
+            println!("Wallet {:?}", chain.address());
+            println!("  Owner:     {}", roles.owner);
+            println!("  Operator:  {}", roles.operator);
+            println!("  Gateway:   {}", roles.gateway);
+            for device_id in devices {
+                let status = if chain.is_device_active(&device_id).await? { "registered" } else { "not registered" };
+                println!("  {:<20} {}", device_id, status);
+            }
+            return Ok(());
+        }
+        ChainCommand::Readings { .. } => unreachable!("handled without a wallet"),
+    };
+    
+    println!("Done: {}", tx.tx_hash);
+    Ok(())
+}
+
+/// Lecturas on-chain de un dispositivo, de la más reciente a la más antigua
+async fn show_device_readings(reader: &RegistryReader, device_id: &str, index: Option<u64>, latest: bool, limit: u64) -> Result<()> {
+    let count = reader.get_device_reading_count(device_id).await?;
+    println!("{}: {} readings on-chain", device_id, count);
+    println!();
+    println!("{:>6}  {:>8}  {:>10}  {:>10}  {:>6}", "#", "INDEX", "TIMESTAMP", "BLOCK", "BYTES");
+    
+    let positions = reading_positions(count, index, latest, limit)
+        .map_err(|e| anyhow!("{}: {}", device_id, e))?;
+    if latest {
+        if let Some(&position) = positions.first() {
+            print_on_chain_reading(position, &reader.get_latest_reading_for_device(device_id).await?);
+        }
+        return Ok(());
+    }
+    for i in positions {
+        print_on_chain_reading(i, &reader.get_device_reading(device_id, i).await?);
+    }
+    Ok(())
+}
+
+/// Posiciones por dispositivo a mostrar de `count` lecturas, de la más reciente a la más antigua
+fn reading_positions(count: u64, index: Option<u64>, latest: bool, limit: u64) -> Result<Vec<u64>> {
+    match index {
+        Some(i) if i >= count => Err(anyhow!("index {} is out of range ({} readings)", i, count)),
+        Some(i) => Ok(vec![i]),
+        None if latest => Ok(count.checked_sub(1).into_iter().collect()),
+        None => Ok((count.saturating_sub(limit)..count).rev().collect()),
+    }
+}
+
+fn print_on_chain_reading(position: u64, reading: &OnChainReading) {
+    println!(
+        "{:>6}  {:>8}  {:>10}  {:>10}  {:>6}",
+        position,
+        reading.index,
+        reading.timestamp,
+        reading.block_number,
+        reading.ciphertext.len(),
+    );
+}
+
+fn print_device(device: &Device) {
+    println!("  Device:      {}", device.device_id);
+    println!("  Status:      {}", device.status.as_str());
+    println!("  Owner:       {}", device.owner);
+    println!("  Sensor type: {}", device.sensor_type);
+    println!("  Public key:  {}", device.public_key.as_deref().unwrap_or("- (gateway encrypts)"));
+    println!("  Updated:     {}", device.updated_at);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Procesador con los dispositivos de los tests registrados y activos
+    fn processor() -> Processor {
+        let keys = KeyRegistry::new()
+            .with_master_key(&"ab".repeat(32))
+            .unwrap()
+            .with_signing_key(&"cd".repeat(32))
+            .unwrap();
+        let devices = DeviceRegistry::open_in_memory().unwrap();
+        for device_id in ["ESP32-001", "AIR-042", "NODE-7"] {
+            devices.register(device_id, "tests", "climate", None).unwrap();
+        }
+
+        Processor {
+            topics: IngestTopics::default(),
+            keys,
+            devices: Arc::new(devices),
+            on_chain: Arc::new(DeviceStatusCache::fixed(["ESP32-001", "AIR-042", "NODE-7"])),
+            metrics: MetricCatalog::default(),
+            envelope: EnvelopeFormat::Binary,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reading_roundtrip_in_every_envelope_format() {
+        let mut processor = processor();
+        let payload = SensorReading::climate("ESP32-001", 22.5, 48.0, unix_now()).to_json().unwrap();
+        let original = SensorReading::from_json(&payload).unwrap();
+
+        let mut sizes = Vec::new();
+        for envelope in [EnvelopeFormat::Legacy, EnvelopeFormat::Json, EnvelopeFormat::Binary] {
+            processor.envelope = envelope;
+            let queued = processor.process_sensor_data("bae/sensors/ESP32-001/data", None, &payload).await.unwrap().remove(0);
+            sizes.push(queued.ciphertext.len());
+
+            // Como lo vería un lector de la cadena: sin key_id ni versión
+            let on_chain = gateway::crypto::EncryptedPayload {
+                ciphertext: queued.ciphertext,
+                nonce: queued.nonce,
+                key_id: String::new(),
+                key_version: 0,
+            };
+            let aad = CryptoHandler::reading_aad(&queued.device_id, queued.timestamp);
+            let (format, body) = processor.keys.handler_for(&queued.device_id).unwrap().open(&on_chain, &aad).unwrap();
+            assert_eq!(format, envelope.body_format_for(&original));
+            assert_eq!(SensorReading::decode_body(format, &body, &queued.device_id).unwrap(), original);
+        }
+
+        // El cuerpo binario es el más compacto
+        assert!(sizes[2] < sizes[0]);
+    }
+
+    #[tokio::test]
+    async fn test_any_metric_set_flows_through() {
+        use bae_core::metric::{Measurement, MetricSpec};
+
+        let mut processor = processor();
+        let reading = SensorReading::new("AIR-042", unix_now(), vec![
+            Measurement::number("co2", "ppm", 612.0),
+            Measurement::number("illuminance", "lx", 320.0),
+        ]);
+        let payload = reading.to_json().unwrap();
+        let topic = "bae/sensors/AIR-042/data";
+
+        let queued = processor.process_sensor_data(topic, None, &payload).await.unwrap().remove(0);
+        let on_chain = gateway::crypto::EncryptedPayload {
+            ciphertext: queued.ciphertext,
+            nonce: queued.nonce,
+            key_id: String::new(),
+            key_version: 0,
+        };
+        let aad = CryptoHandler::reading_aad(&queued.device_id, queued.timestamp);
+        let (format, body) = processor.keys.handler_for("AIR-042").unwrap().open(&on_chain, &aad).unwrap();
+        assert_eq!(format, bae_core::BodyFormat::Metrics);
+        assert_eq!(SensorReading::decode_body(format, &body, "AIR-042").unwrap(), reading);
+
+        // En modo estricto sólo pasan las métricas del catálogo
+        processor.metrics = MetricCatalog::default().with_strict(true);
+        assert!(processor.process_sensor_data(topic, None, &payload).await.is_err());
+        processor.metrics = MetricCatalog::default()
+            .with_strict(true)
+            .with_spec(MetricSpec::new("illuminance", "lx", 0.0, 100_000.0));
+        assert!(processor.process_sensor_data(topic, None, &payload).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_senml_pack_is_normalized() {
+        let mut processor = processor();
+        processor.envelope = EnvelopeFormat::Json;
+        let now = unix_now();
+        let pack = format!(
+            r#"[{{"bn":"AIR-042/","bt":{},"n":"co2","u":"ppm","v":612}},{{"n":"co2","u":"ppm","t":-30,"v":598}}]"#,
+            now
+        );
+
+        let queued = processor.process_sensor_data("bae/sensors/AIR-042/senml", None, pack.as_bytes()).await.unwrap();
+        assert_eq!(queued.iter().map(|r| r.timestamp).collect::<Vec<_>>(), vec![now, now - 30]);
+
+        let aad = CryptoHandler::reading_aad("AIR-042", now - 30);
+        let payload = gateway::crypto::EncryptedPayload {
+            ciphertext: queued[1].ciphertext.clone(),
+            nonce: queued[1].nonce.clone(),
+            key_id: queued[1].key_id.clone(),
+            key_version: queued[1].key_version,
+        };
+        let decrypted: SensorReading = processor.keys.handler_for("AIR-042").unwrap().decrypt(&payload, &aad).unwrap();
+        assert_eq!(decrypted.number("co2"), Some(598.0));
+
+        // El nombre base no puede suplantar a otro dispositivo
+        let spoofed = br#"[{"bn":"ESP32-001/","n":"co2","u":"ppm","v":612}]"#;
+        let err = processor.process_sensor_data("bae/sensors/AIR-042/senml", None, spoofed).await.unwrap_err();
+        assert!(err.is::<DeviceMismatch>());
+    }
+
+    #[test]
+    fn test_chain_readings_arguments() {
+        // Por defecto las `limit` más recientes, empezando por la última
+        assert_eq!(reading_positions(25, None, false, 10).unwrap(), (15..25).rev().collect::<Vec<_>>());
+        assert_eq!(reading_positions(3, None, false, 10).unwrap(), vec![2, 1, 0]);
+        assert!(reading_positions(0, None, false, 10).unwrap().is_empty());
+
+        assert_eq!(reading_positions(25, Some(0), false, 10).unwrap(), vec![0]);
+        assert!(reading_positions(25, Some(25), false, 10).is_err());
+
+        assert_eq!(reading_positions(25, None, true, 10).unwrap(), vec![24]);
+        assert!(reading_positions(0, None, true, 10).unwrap().is_empty());
+
+        // `--limit` no se combina con una lectura concreta
+        let parse = |args: &[&str]| Cli::try_parse_from([&["gateway", "chain", "readings", "NODE-7"], args].concat());
+        assert!(parse(&["--limit", "5"]).is_ok());
+        assert!(parse(&["--index", "2"]).is_ok());
+        assert!(parse(&["--index", "2", "--limit", "5"]).is_err());
+        assert!(parse(&["--latest", "--limit", "5"]).is_err());
+        assert!(parse(&["--latest", "--index", "2"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_plaintext_message_is_queued_once() {
+        let processor = processor();
+        let outbox = Outbox::open_in_memory().unwrap();
+        let payload = SensorReading::climate("ESP32-001", 22.5, 48.0, unix_now()).to_json().unwrap();
+        let topic = "bae/sensors/ESP32-001/data";
+
+        let first = processor.process_sensor_data(topic, None, &payload).await.unwrap();
+        outbox.enqueue_all(&first).unwrap();
+
+        // Reentrega QoS 1: el gateway la cifra con otro nonce, pero es la misma lectura
+        let again = processor.process_sensor_data(topic, None, &payload).await.unwrap();
+        assert_ne!(first[0].nonce, again[0].nonce);
+        assert!(outbox.enqueue_all(&again).unwrap_err().is::<DuplicateReading>());
+        assert_eq!(outbox.count(OutboxStatus::Pending).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_protobuf_reading_by_topic_or_content_type() {
+        let processor = processor();
+        let reading = SensorReading::climate("NODE-7", 21.5, 48.0, unix_now());
+        let payload = reading.to_protobuf();
+
+        let by_topic = processor.process_sensor_data("bae/sensors/NODE-7/proto", None, &payload).await.unwrap();
+        let by_content_type = processor.process_sensor_data("bae/sensors/NODE-7/data", Some("application/x-protobuf"), &payload).await.unwrap();
+        for queued in [&by_topic[0], &by_content_type[0]] {
+            assert_eq!((queued.device_id.as_str(), queued.timestamp), ("NODE-7", reading.timestamp));
+        }
+
+        // Sin content-type, el topic JSON no acepta protobuf
+        assert!(processor.process_sensor_data("bae/sensors/NODE-7/data", None, &payload).await.is_err());
+
+        let spoofed = SensorReading::climate("ESP32-001", 21.5, 48.0, unix_now()).to_protobuf();
+        let err = processor.process_sensor_data("bae/sensors/NODE-7/proto", None, &spoofed).await.unwrap_err();
+        assert!(err.is::<DeviceMismatch>());
+    }
+
+    #[tokio::test]
+    async fn test_sealed_reading_is_forwarded_unchanged() {
+        let device_signer = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let device_public_key = hex::encode(device_signer.verifying_key().to_bytes());
+        let device = CryptoHandler::from_key(&[9; 32]).with_signer(Some(device_signer));
+
+        // El gateway no tiene la clave AES del dispositivo, sólo su clave pública
+        let processor = processor();
+        processor.devices.update("NODE-7", DeviceUpdate { public_key: Some(Some(device_public_key)), ..Default::default() }).unwrap();
+        let topic = "bae/sensors/NODE-7/sealed";
+        let reading = SensorReading::climate("NODE-7", 21.5, 48.0, unix_now());
+        let sealed = SealedReading::seal(&reading, &device, EnvelopeFormat::Binary).unwrap();
+
+        let queued = processor.process_sensor_data(topic, None, &sealed.to_json().unwrap()).await.unwrap().remove(0);
+        assert_eq!(queued.ciphertext, sealed.payload.ciphertext);
+        assert_eq!(queued.signature, sealed.signature);
+        assert!(processor.keys.handler_for("NODE-7").unwrap().open(&sealed.payload, &CryptoHandler::reading_aad("NODE-7", reading.timestamp)).is_err());
+
+        let forged = SealedReading { signature: vec![0; 64], ..sealed.clone() };
+        let err = processor.process_sensor_data(topic, None, &forged.to_json().unwrap()).await.unwrap_err();
+        assert!(err.is::<SealedRejection>());
+
+        // Registrado, pero sin clave pública
+        let unsealed = SealedReading::seal(&SensorReading::climate("AIR-042", 21.5, 48.0, unix_now()), &device, EnvelopeFormat::Binary).unwrap();
+        let err = processor.process_sensor_data("bae/sensors/AIR-042/sealed", None, &unsealed.to_json().unwrap()).await.unwrap_err();
+        assert!(err.is::<SealedRejection>());
+
+        // Un dispositivo extremo a extremo no puede mandar texto en claro
+        let plaintext = reading.to_json().unwrap();
+        assert!(processor.process_sensor_data("bae/sensors/NODE-7/data", None, &plaintext).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_registered_active_devices_are_accepted() {
+        let processor = processor();
+        let payload = |device_id: &str| SensorReading::climate(device_id, 21.5, 48.0, unix_now()).to_json().unwrap();
+
+        let err = processor.process_sensor_data("bae/sensors/ROGUE-1/data", None, &payload("ROGUE-1")).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(DeviceRejected::Unknown(_))));
+
+        // La suspensión se aplica al siguiente mensaje
+        processor.devices.set_status("ESP32-001", DeviceStatus::Suspended).unwrap();
+        let err = processor.process_sensor_data("bae/sensors/ESP32-001/data", None, &payload("ESP32-001")).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(DeviceRejected::Suspended(_))));
+
+        processor.devices.set_status("ESP32-001", DeviceStatus::Active).unwrap();
+        assert!(processor.process_sensor_data("bae/sensors/ESP32-001/data", None, &payload("ESP32-001")).await.is_ok());
+
+        // Activo en local pero sin registrar en el contrato: revertiría su lote
+        processor.devices.register("NODE-9", "tests", "climate", None).unwrap();
+        let err = processor.process_sensor_data("bae/sensors/NODE-9/data", None, &payload("NODE-9")).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(DeviceRejected::NotOnChain(_))));
+    }
+}
@@ -0,0 +1,637 @@
+use anyhow::{Result, anyhow};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS, Transport};
+use rumqttc::v5;
+use rumqttc::tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, error, warn};
+
+/// Versión del protocolo MQTT con el broker (`MQTT_PROTOCOL`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MqttProtocol {
+    /// MQTT 3.1.1
+    #[default]
+    V4,
+    /// MQTT 5: añade propiedades por mensaje como el `content-type`
+    V5,
+}
+
+impl MqttProtocol {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "v4" | "4" | "3.1.1" => Ok(Self::V4),
+            "v5" | "5" | "5.0" => Ok(Self::V5),
+            other => Err(anyhow!("Invalid MQTT_PROTOCOL '{}' (expected v4 or v5)", other)),
+        }
+    }
+}
+
+/// Configuración de conexión al broker: transporte (TCP/TLS), credenciales y protocolo.
+/// No implementa `Debug` a propósito para no filtrar la contraseña en logs.
+#[derive(Clone, Default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub protocol: MqttProtocol,
+    pub ca_file: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl MqttConfig {
+    /// Lee la configuración de `MQTT_BROKER` (host o URL `mqtt://`/`mqtts://`),
+    /// `MQTT_PORT`, `MQTT_CA_FILE`, `MQTT_CLIENT_CERT`, `MQTT_CLIENT_KEY`,
+    /// `MQTT_USERNAME`, `MQTT_PASSWORD` y `MQTT_PROTOCOL` (`v4` por defecto o `v5`)
+    pub fn from_env() -> Result<Self> {
+        let broker = std::env::var("MQTT_BROKER")
+            .unwrap_or_else(|_| "broker.hivemq.com".to_string());
+        let port = match std::env::var("MQTT_PORT") {
+            Ok(port) => Some(port.parse().map_err(|_| anyhow!("Invalid MQTT_PORT"))?),
+            Err(_) => None,
+        };
+        let (host, port, tls) = Self::parse_broker(&broker, port)?;
+        let protocol = match std::env::var("MQTT_PROTOCOL") {
+            Ok(protocol) => MqttProtocol::parse(&protocol)?,
+            Err(_) => MqttProtocol::default(),
+        };
+
+        let env_path = |name: &str| std::env::var(name).ok().map(PathBuf::from);
+        let config = Self {
+            host,
+            port,
+            tls,
+            protocol,
+            ca_file: env_path("MQTT_CA_FILE"),
+            client_cert: env_path("MQTT_CLIENT_CERT"),
+            client_key: env_path("MQTT_CLIENT_KEY"),
+            username: std::env::var("MQTT_USERNAME").ok(),
+            password: std::env::var("MQTT_PASSWORD").ok(),
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Separa esquema, host y puerto. Sin esquema se asume `mqtt://`.
+    /// El puerto explícito tiene prioridad; si no, 1883 (TCP) o 8883 (TLS).
+    /// Las IPv6 con puerto van entre corchetes (`mqtt://[::1]:1884`).
+    pub fn parse_broker(broker: &str, port: Option<u16>) -> Result<(String, u16, bool)> {
+        let (tls, rest) = if let Some(rest) = broker.strip_prefix("mqtts://") {
+            (true, rest)
+        } else if let Some(rest) = broker.strip_prefix("mqtt://") {
+            (false, rest)
+        } else if broker.contains("://") {
+            return Err(anyhow!("Unsupported MQTT scheme in {}", broker));
+        } else {
+            (false, broker)
+        };
+
+        let rest = rest.trim_end_matches('/');
+        let parse_port = |p: &str| p.parse::<u16>().map_err(|_| anyhow!("Invalid port in MQTT_BROKER: {}", p));
+        let (host, url_port) = if let Some(bracketed) = rest.strip_prefix('[') {
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| anyhow!("Unclosed IPv6 address in MQTT_BROKER: {}", broker))?;
+            match after {
+                "" => (host, None),
+                _ => match after.strip_prefix(':') {
+                    Some(p) => (host, Some(parse_port(p)?)),
+                    None => return Err(anyhow!("Invalid MQTT_BROKER: {}", broker)),
+                },
+            }
+        } else if rest.matches(':').count() > 1 {
+            // IPv6 sin corchetes: no puede llevar puerto
+            (rest, None)
+        } else {
+            match rest.rsplit_once(':') {
+                Some((host, p)) => (host, Some(parse_port(p)?)),
+                None => (rest, None),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(anyhow!("MQTT broker host is empty"));
+        }
+
+        let default_port = if tls { 8883 } else { 1883 };
+        Ok((host.to_string(), port.or(url_port).unwrap_or(default_port), tls))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err(anyhow!("MQTT_CLIENT_CERT and MQTT_CLIENT_KEY must be set together"));
+        }
+
+        if !self.tls && (self.ca_file.is_some() || self.client_cert.is_some()) {
+            return Err(anyhow!("TLS certificates configured but MQTT_BROKER is not mqtts://"));
+        }
+
+        if self.password.is_some() && self.username.is_none() {
+            return Err(anyhow!("MQTT_PASSWORD requires MQTT_USERNAME"));
+        }
+
+        Ok(())
+    }
+
+    /// Construye las `MqttOptions` (MQTT 3.1.1) con el transporte y credenciales configurados
+    pub fn mqtt_options(&self, client_id: &str) -> Result<MqttOptions> {
+        self.validate()?;
+
+        let mut options = MqttOptions::new(client_id, &self.host, self.port);
+
+        if let Some(transport) = self.tls_transport()? {
+            options.set_transport(transport);
+        }
+
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+
+        Ok(options)
+    }
+
+    /// Igual que `mqtt_options`, para MQTT 5
+    pub fn mqtt_options_v5(&self, client_id: &str) -> Result<v5::MqttOptions> {
+        self.validate()?;
+
+        let mut options = v5::MqttOptions::new(client_id, &self.host, self.port);
+
+        if let Some(transport) = self.tls_transport()? {
+            options.set_transport(transport);
+        }
+
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+
+        Ok(options)
+    }
+
+    fn tls_transport(&self) -> Result<Option<Transport>> {
+        if !self.tls {
+            return Ok(None);
+        }
+
+        let transport = match (&self.ca_file, self.client_auth()?) {
+            (Some(ca_file), client_auth) => {
+                let ca = std::fs::read(ca_file)
+                    .map_err(|e| anyhow!("Failed to read CA file {}: {}", ca_file.display(), e))?;
+                Transport::tls(ca, client_auth, None)
+            }
+            // Sin CA explícita se usan los certificados raíz del sistema
+            (None, None) => Transport::tls_with_default_config(),
+            (None, Some((cert, key))) => Transport::tls_with_config(Self::system_roots_with_client_auth(&cert, &key)?.into()),
+        };
+        Ok(Some(transport))
+    }
+
+    /// Mutual TLS contra los certificados raíz del sistema (`rumqttc` sólo lo
+    /// admite de serie con una CA explícita)
+    fn system_roots_with_client_auth(cert: &[u8], key: &[u8]) -> Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs()
+            .map_err(|e| anyhow!("Failed to load system root certificates: {}", e))?;
+        roots.add_parsable_certificates(native);
+
+        let certs = rustls_pemfile::certs(&mut &cert[..])
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Invalid MQTT_CLIENT_CERT: {}", e))?;
+        if certs.is_empty() {
+            return Err(anyhow!("No certificate found in MQTT_CLIENT_CERT"));
+        }
+        let key = rustls_pemfile::private_key(&mut &key[..])
+            .map_err(|e| anyhow!("Invalid MQTT_CLIENT_KEY: {}", e))?
+            .ok_or_else(|| anyhow!("No private key found in MQTT_CLIENT_KEY"))?;
+
+        ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(certs, key)
+            .map_err(|e| anyhow!("Invalid MQTT client certificate: {}", e))
+    }
+
+    fn client_auth(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let cert = std::fs::read(cert)
+                    .map_err(|e| anyhow!("Failed to read client cert {}: {}", cert.display(), e))?;
+                let key = std::fs::read(key)
+                    .map_err(|e| anyhow!("Failed to read client key {}: {}", key.display(), e))?;
+                Ok(Some((cert, key)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Descripción apta para logs (sin secretos)
+    pub fn describe(&self) -> String {
+        let scheme = if self.tls { "mqtts" } else { "mqtt" };
+        let auth = match (&self.username, self.client_cert.is_some()) {
+            (Some(user), true) => format!("user={} + client cert", user),
+            (Some(user), false) => format!("user={}", user),
+            (None, true) => "client cert".to_string(),
+            (None, false) => "anonymous".to_string(),
+        };
+        let protocol = match self.protocol {
+            MqttProtocol::V4 => "MQTT 3.1.1",
+            MqttProtocol::V5 => "MQTT 5",
+        };
+        format!("{}://{}:{} ({}, {})", scheme, self.host, self.port, protocol, auth)
+    }
+}
+
+/// Mensaje recibido del broker en alguno de los topics suscritos.
+/// El ACK es manual: se envía con `ack()` cuando el mensaje ya está a salvo.
+#[derive(Debug, Clone)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// Propiedad `content-type` del PUBLISH (solo MQTT 5)
+    pub content_type: Option<String>,
+    origin: Origin,
+}
+
+/// PUBLISH original y cliente por el que se confirma
+#[derive(Debug, Clone)]
+enum Origin {
+    V4(Publish, AsyncClient),
+    V5(v5::mqttbytes::v5::Publish, v5::AsyncClient),
+}
+
+impl MqttMessage {
+    pub async fn ack(&self) -> Result<()> {
+        let result = match &self.origin {
+            Origin::V4(publish, client) => client.ack(publish).await.map_err(|e| e.to_string()),
+            Origin::V5(publish, client) => client.ack(publish).await.map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| anyhow!("Failed to ack MQTT message: {}", e))
+    }
+}
+
+/// Backoff exponencial para reintentos (conexión al broker, envíos al outbox)
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max, current: initial }
+    }
+
+    /// Devuelve la espera actual y duplica la siguiente (hasta `max`)
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    /// Espera correspondiente al intento `attempt` (0 = primer reintento), sin estado
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.min(16));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// Vuelve a la espera inicial tras una conexión exitosa
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+/// Cliente y `EventLoop` de la versión de protocolo negociada
+enum Connection {
+    V4(AsyncClient, Box<EventLoop>),
+    V5(v5::AsyncClient, Box<v5::EventLoop>),
+}
+
+/// Lo que interesa a la ingesta de cada evento del broker
+enum Incoming {
+    Connected,
+    Message(Box<MqttMessage>),
+    Disconnected,
+    Other,
+}
+
+/// Ingesta MQTT: dueña del `AsyncClient` y su `EventLoop`.
+/// Se re-suscribe en cada `ConnAck` y entrega los mensajes por un canal.
+pub struct MqttIngest {
+    connection: Connection,
+    topics: Vec<String>,
+    backoff: Backoff,
+}
+
+impl MqttIngest {
+    pub fn new(mut options: MqttOptions, topics: Vec<String>) -> Self {
+        options.set_manual_acks(true);
+        let (client, eventloop) = AsyncClient::new(options, 10);
+        Self::with_connection(Connection::V4(client, Box::new(eventloop)), topics)
+    }
+
+    /// Ingesta sobre MQTT 5, que entrega además el `content-type` de cada mensaje
+    pub fn new_v5(mut options: v5::MqttOptions, topics: Vec<String>) -> Self {
+        options.set_manual_acks(true);
+        let (client, eventloop) = v5::AsyncClient::new(options, 10);
+        Self::with_connection(Connection::V5(client, Box::new(eventloop)), topics)
+    }
+
+    fn with_connection(connection: Connection, topics: Vec<String>) -> Self {
+        Self {
+            connection,
+            topics,
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// Lanza el loop de eventos en una tarea y devuelve el receptor de mensajes
+    pub fn spawn(self, buffer: usize) -> mpsc::Receiver<MqttMessage> {
+        let (tx, rx) = mpsc::channel(buffer);
+        tokio::spawn(self.run(tx));
+        rx
+    }
+
+    async fn run(mut self, tx: mpsc::Sender<MqttMessage>) {
+        loop {
+            match self.poll().await {
+                Ok(incoming) => {
+                    if !self.handle(incoming, &tx).await {
+                        warn!("⚠️  Message receiver dropped, stopping MQTT ingest");
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let delay = self.backoff.next_delay();
+                    error!("❌ MQTT error: {}. Reconnecting in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> Result<Incoming, String> {
+        match &mut self.connection {
+            Connection::V4(client, eventloop) => {
+                let event = eventloop.poll().await.map_err(|e| e.to_string())?;
+                Ok(Self::incoming_v4(event, client))
+            }
+            Connection::V5(client, eventloop) => {
+                let event = eventloop.poll().await.map_err(|e| e.to_string())?;
+                Ok(Self::incoming_v5(event, client))
+            }
+        }
+    }
+
+    /// Reacciona a un evento: se re-suscribe en cada conexión y entrega los mensajes.
+    /// Devuelve `false` si ya nadie recibe los mensajes.
+    async fn handle(&mut self, incoming: Incoming, tx: &mpsc::Sender<MqttMessage>) -> bool {
+        match incoming {
+            Incoming::Connected => {
+                info!("📡 Connected to MQTT broker");
+                self.backoff.reset();
+                self.subscribe_all();
+            }
+            Incoming::Message(message) => return tx.send(*message).await.is_ok(),
+            Incoming::Disconnected => warn!("⚠️  Disconnected from MQTT broker"),
+            Incoming::Other => {}
+        }
+        true
+    }
+
+    fn incoming_v4(event: Event, client: &AsyncClient) -> Incoming {
+        match event {
+            Event::Incoming(Packet::ConnAck(_)) => Incoming::Connected,
+            Event::Incoming(Packet::Publish(publish)) => Incoming::Message(Box::new(MqttMessage {
+                topic: publish.topic.clone(),
+                payload: publish.payload.to_vec(),
+                content_type: None,
+                origin: Origin::V4(publish, client.clone()),
+            })),
+            Event::Incoming(Packet::Disconnect) => Incoming::Disconnected,
+            _ => Incoming::Other,
+        }
+    }
+
+    fn incoming_v5(event: v5::Event, client: &v5::AsyncClient) -> Incoming {
+        use v5::mqttbytes::v5::Packet;
+
+        match event {
+            v5::Event::Incoming(Packet::ConnAck(_)) => Incoming::Connected,
+            v5::Event::Incoming(Packet::Publish(publish)) => Incoming::Message(Box::new(MqttMessage {
+                // Con alias de topic el broker puede mandarlo vacío; no usamos alias
+                topic: String::from_utf8_lossy(&publish.topic).into_owned(),
+                payload: publish.payload.to_vec(),
+                content_type: publish.properties.as_ref().and_then(|p| p.content_type.clone()),
+                origin: Origin::V5(publish, client.clone()),
+            })),
+            v5::Event::Incoming(Packet::Disconnect(_)) => Incoming::Disconnected,
+            _ => Incoming::Other,
+        }
+    }
+
+    fn subscribe_all(&self) {
+        // `try_subscribe` no bloquea: estamos dentro del loop que hace poll
+        for topic in &self.topics {
+            let result = match &self.connection {
+                Connection::V4(client, _) => client.try_subscribe(topic.as_str(), QoS::AtLeastOnce).map_err(|e| e.to_string()),
+                Connection::V5(client, _) => client
+                    .try_subscribe(topic.as_str(), v5::mqttbytes::QoS::AtLeastOnce)
+                    .map_err(|e| e.to_string()),
+            };
+            match result {
+                Ok(_) => info!("✅ Subscribed to MQTT topic: {}", topic),
+                Err(e) => error!("❌ Failed to subscribe to {}: {}", topic, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_broker() {
+        let (host, port, tls) = MqttConfig::parse_broker("broker.hivemq.com", None).unwrap();
+        assert_eq!((host.as_str(), port, tls), ("broker.hivemq.com", 1883, false));
+
+        let (host, port, tls) = MqttConfig::parse_broker("mqtts://broker.example.com", None).unwrap();
+        assert_eq!((host.as_str(), port, tls), ("broker.example.com", 8883, true));
+
+        let (_, port, _) = MqttConfig::parse_broker("mqtts://broker.example.com:9883", None).unwrap();
+        assert_eq!(port, 9883);
+
+        // MQTT_PORT tiene prioridad sobre el puerto de la URL
+        let (_, port, _) = MqttConfig::parse_broker("mqtt://localhost:1884", Some(1885)).unwrap();
+        assert_eq!(port, 1885);
+
+        // IPv6
+        let (host, port, _) = MqttConfig::parse_broker("mqtt://[::1]:1884", None).unwrap();
+        assert_eq!((host.as_str(), port), ("::1", 1884));
+        let (host, port, _) = MqttConfig::parse_broker("mqtts://[2001:db8::7]", None).unwrap();
+        assert_eq!((host.as_str(), port), ("2001:db8::7", 8883));
+        let (host, port, _) = MqttConfig::parse_broker("fe80::1", None).unwrap();
+        assert_eq!((host.as_str(), port), ("fe80::1", 1883));
+
+        assert!(MqttConfig::parse_broker("ws://broker.example.com", None).is_err());
+        assert!(MqttConfig::parse_broker("mqtts://", None).is_err());
+        assert!(MqttConfig::parse_broker("mqtt://[::1:1884", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_protocol() {
+        assert_eq!(MqttProtocol::parse("v5").unwrap(), MqttProtocol::V5);
+        assert_eq!(MqttProtocol::parse("3.1.1").unwrap(), MqttProtocol::V4);
+        assert_eq!(MqttConfig::default().protocol, MqttProtocol::V4);
+        assert!(MqttProtocol::parse("v3").is_err());
+    }
+
+    #[test]
+    fn test_client_cert_requires_key() {
+        let config = MqttConfig {
+            host: "broker.example.com".to_string(),
+            port: 8883,
+            tls: true,
+            client_cert: Some(PathBuf::from("client.crt")),
+            ..Default::default()
+        };
+        assert!(config.mqtt_options("bae-gateway").is_err());
+    }
+
+    #[test]
+    fn test_backoff_doubles_until_max() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_backoff_delay_for_attempt() {
+        let backoff = Backoff::new(Duration::from_secs(5), Duration::from_secs(300));
+
+        assert_eq!(backoff.delay_for_attempt(0), Duration::from_secs(5));
+        assert_eq!(backoff.delay_for_attempt(3), Duration::from_secs(40));
+        assert_eq!(backoff.delay_for_attempt(40), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_backoff_reset() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        backoff.next_delay();
+        backoff.next_delay();
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    /// Topics con SUBSCRIBE en cola, vistos sin broker: `clean` pasa las peticiones
+    /// del canal del cliente a `pending`
+    fn queued_subscriptions(ingest: &mut MqttIngest) -> Vec<String> {
+        match &mut ingest.connection {
+            Connection::V4(_, eventloop) => {
+                eventloop.clean();
+                eventloop.pending.drain(..).filter_map(|request| match request {
+                    rumqttc::Request::Subscribe(subscribe) => Some(subscribe.filters[0].path.clone()),
+                    _ => None,
+                }).collect()
+            }
+            Connection::V5(_, eventloop) => {
+                eventloop.clean();
+                eventloop.pending.drain(..).filter_map(|request| match request {
+                    v5::Request::Subscribe(subscribe) => Some(subscribe.filters[0].path.clone()),
+                    _ => None,
+                }).collect()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resubscribes_on_every_connack() {
+        let topics = vec!["bae/sensors/+/data".to_string(), "bae/sensors/+/proto".to_string()];
+        let mut ingest = MqttIngest::new(MqttOptions::new("test", "localhost", 1883), topics.clone());
+        let (tx, _rx) = mpsc::channel(4);
+        let connack = || Event::Incoming(Packet::ConnAck(rumqttc::ConnAck::new(rumqttc::ConnectReturnCode::Success, false)));
+
+        // Sin conexión no se suscribe a nada
+        assert!(queued_subscriptions(&mut ingest).is_empty());
+
+        // Cada ConnAck, también el de una reconexión con sesión limpia, suscribe todo de nuevo
+        for _ in 0..2 {
+            let Connection::V4(client, _) = &ingest.connection else { unreachable!() };
+            let incoming = MqttIngest::incoming_v4(connack(), client);
+            assert!(ingest.handle(incoming, &tx).await);
+            assert_eq!(queued_subscriptions(&mut ingest), topics);
+        }
+
+        // Otros eventos no suscriben
+        let Connection::V4(client, _) = &ingest.connection else { unreachable!() };
+        let incoming = MqttIngest::incoming_v4(Event::Incoming(Packet::PingResp), client);
+        assert!(ingest.handle(incoming, &tx).await);
+        assert!(queued_subscriptions(&mut ingest).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delivers_publishes_as_messages() {
+        let mut ingest = MqttIngest::new(MqttOptions::new("test", "localhost", 1883), Vec::new());
+        let (tx, mut rx) = mpsc::channel(4);
+
+        let publish = Publish::new("bae/sensors/NODE-7/data", QoS::AtLeastOnce, b"{\"v\":1}".to_vec());
+        let Connection::V4(client, _) = &ingest.connection else { unreachable!() };
+        let incoming = MqttIngest::incoming_v4(Event::Incoming(Packet::Publish(publish)), client);
+        assert!(ingest.handle(incoming, &tx).await);
+
+        let message = rx.recv().await.unwrap();
+        assert_eq!(message.topic, "bae/sensors/NODE-7/data");
+        assert_eq!(message.payload, b"{\"v\":1}");
+        assert_eq!(message.content_type, None);
+
+        // Sin receptor la ingesta se detiene
+        drop(rx);
+        let publish = Publish::new("bae/sensors/NODE-7/data", QoS::AtLeastOnce, b"{}".to_vec());
+        let Connection::V4(client, _) = &ingest.connection else { unreachable!() };
+        let incoming = MqttIngest::incoming_v4(Event::Incoming(Packet::Publish(publish)), client);
+        assert!(!ingest.handle(incoming, &tx).await);
+    }
+
+    #[tokio::test]
+    async fn test_v5_messages_carry_content_type() {
+        use v5::mqttbytes::v5::{Packet, Publish, PublishProperties};
+
+        let topics = vec!["bae/sensors/+/data".to_string()];
+        let mut ingest = MqttIngest::new_v5(v5::MqttOptions::new("test", "localhost", 1883), topics.clone());
+        let (tx, mut rx) = mpsc::channel(4);
+
+        let Connection::V5(client, _) = &ingest.connection else { unreachable!() };
+        let connack = v5::mqttbytes::v5::ConnAck {
+            session_present: false,
+            code: v5::mqttbytes::v5::ConnectReturnCode::Success,
+            properties: None,
+        };
+        let incoming = MqttIngest::incoming_v5(v5::Event::Incoming(Packet::ConnAck(connack)), client);
+        assert!(ingest.handle(incoming, &tx).await);
+        assert_eq!(queued_subscriptions(&mut ingest), topics);
+
+        let properties = PublishProperties { content_type: Some("application/x-protobuf".to_string()), ..Default::default() };
+        let publish = Publish::new("bae/sensors/NODE-7/data", v5::mqttbytes::QoS::AtLeastOnce, vec![8, 1], Some(properties));
+        let Connection::V5(client, _) = &ingest.connection else { unreachable!() };
+        let incoming = MqttIngest::incoming_v5(v5::Event::Incoming(Packet::Publish(publish)), client);
+        assert!(ingest.handle(incoming, &tx).await);
+
+        let message = rx.recv().await.unwrap();
+        assert_eq!((message.topic.as_str(), message.payload.as_slice()), ("bae/sensors/NODE-7/data", &[8u8, 1][..]));
+        assert_eq!(message.content_type.as_deref(), Some("application/x-protobuf"));
+    }
+}
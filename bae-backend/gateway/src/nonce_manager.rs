@@ -0,0 +1,129 @@
+use ethers::types::U256;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// Asignación local de nonces para poder tener varias transacciones en vuelo.
+/// Los nonces de envíos fallidos se devuelven con `release` y se reutilizan
+/// primero, para no dejar huecos que bloqueen las transacciones posteriores.
+pub struct NonceManager {
+    state: Mutex<NonceState>,
+}
+
+struct NonceState {
+    next: U256,
+    released: BTreeSet<U256>,
+}
+
+impl NonceManager {
+    pub fn new(start: U256) -> Self {
+        Self {
+            state: Mutex::new(NonceState { next: start, released: BTreeSet::new() }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, NonceState> {
+        // El estado es siempre consistente entre operaciones: se puede recuperar
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Reserva el siguiente nonce (rellenando huecos primero)
+    pub fn next(&self) -> U256 {
+        let mut state = self.state();
+
+        if let Some(nonce) = state.released.pop_first() {
+            return nonce;
+        }
+
+        let nonce = state.next;
+        state.next = nonce + 1;
+        nonce
+    }
+
+    /// Devuelve un nonce cuya transacción nunca llegó a la red
+    pub fn release(&self, nonce: U256) {
+        let mut state = self.state();
+
+        if nonce >= state.next {
+            return;
+        }
+
+        state.released.insert(nonce);
+
+        // Si los liberados quedan al final, simplemente se retrocede
+        while state.next > U256::zero() {
+            let last = state.next - 1;
+            if !state.released.remove(&last) {
+                break;
+            }
+            state.next = last;
+        }
+    }
+
+    /// Se sincroniza con el nonce de la cuenta en la red (p. ej. tras "nonce too low")
+    pub fn resync(&self, chain_nonce: U256) {
+        let mut state = self.state();
+
+        state.released.retain(|nonce| *nonce >= chain_nonce);
+        if chain_nonce > state.next {
+            state.next = chain_nonce;
+        }
+    }
+
+    /// Siguiente nonce nuevo (sin contar huecos), útil para logs
+    pub fn peek(&self) -> U256 {
+        self.state().next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_nonces() {
+        let nonces = NonceManager::new(U256::from(7));
+        assert_eq!(nonces.next(), U256::from(7));
+        assert_eq!(nonces.next(), U256::from(8));
+        assert_eq!(nonces.peek(), U256::from(9));
+    }
+
+    #[test]
+    fn test_released_nonce_fills_gap() {
+        let nonces = NonceManager::new(U256::from(0));
+        let first = nonces.next();
+        let _second = nonces.next();
+        let _third = nonces.next();
+
+        // Falló el envío del primero: debe reutilizarse antes que uno nuevo
+        nonces.release(first);
+        assert_eq!(nonces.next(), U256::from(0));
+        assert_eq!(nonces.next(), U256::from(3));
+    }
+
+    #[test]
+    fn test_release_at_tail_rewinds() {
+        let nonces = NonceManager::new(U256::from(0));
+        nonces.next();
+        let second = nonces.next();
+        let third = nonces.next();
+
+        nonces.release(second);
+        nonces.release(third);
+        assert_eq!(nonces.peek(), U256::from(1));
+        assert_eq!(nonces.next(), U256::from(1));
+    }
+
+    #[test]
+    fn test_resync_moves_forward_only() {
+        let nonces = NonceManager::new(U256::from(5));
+        let gap = nonces.next();
+        nonces.next();
+        nonces.release(gap);
+
+        nonces.resync(U256::from(10));
+        assert_eq!(nonces.next(), U256::from(10));
+
+        nonces.resync(U256::from(3));
+        assert_eq!(nonces.next(), U256::from(11));
+    }
+}
@@ -0,0 +1,495 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::unix_now;
+
+/// Estado de una lectura en el outbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutboxStatus {
+    /// Persistida, esperando ser enviada a la blockchain
+    Pending,
+    /// Enviada y confirmada on-chain
+    Submitted,
+    /// Agotó los reintentos; queda para inspección manual
+    Failed,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Submitted => "submitted",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "pending" => Ok(Self::Pending),
+            "submitted" => Ok(Self::Submitted),
+            "failed" => Ok(Self::Failed),
+            other => Err(anyhow!("Unknown outbox status: {}", other)),
+        }
+    }
+}
+
+/// Lectura ya encriptada y firmada, lista para enviarse al contrato
+#[derive(Debug, Clone)]
+pub struct OutboxReading {
+    pub device_id: String,
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    /// Clave AES con la que se encriptó (ver `KeyRegistry`)
+    pub key_id: String,
+    /// Versión de la clave en el anillo (0 si la lectura es anterior a la rotación)
+    pub key_version: u32,
+    pub signature: Vec<u8>,
+    pub timestamp: u64,
+}
+
+/// La lectura ya está en el outbox (mismo dispositivo y timestamp): es una reentrega
+/// o una repetición de un mensaje capturado, y no se envía dos veces. La clave sale de
+/// los datos del dispositivo y no del nonce, que el gateway genera de nuevo al cifrar
+/// una lectura en claro.
+#[derive(Debug, thiserror::Error)]
+#[error("Duplicate reading from {device_id} at {timestamp}")]
+pub struct DuplicateReading {
+    pub device_id: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub reading: OutboxReading,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub tx_hash: Option<String>,
+    pub created_at: u64,
+}
+
+/// Outbox persistente (SQLite) entre `process_sensor_data` y `BlockchainSender`.
+/// Las lecturas se guardan antes de hacer ACK al broker y se drenan en segundo plano,
+/// así sobreviven a caídas del gateway y a cortes del RPC.
+pub struct Outbox {
+    conn: Mutex<Connection>,
+}
+
+impl Outbox {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path.as_ref())
+            .map_err(|e| anyhow!("Failed to open outbox {}: {}", path.as_ref().display(), e))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = FULL;
+             CREATE TABLE IF NOT EXISTS outbox (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 device_id   TEXT    NOT NULL,
+                 ciphertext  BLOB    NOT NULL,
+                 nonce       BLOB    NOT NULL,
+                 signature   BLOB    NOT NULL,
+                 timestamp   INTEGER NOT NULL,
+                 status      TEXT    NOT NULL DEFAULT 'pending',
+                 attempts    INTEGER NOT NULL DEFAULT 0,
+                 last_error  TEXT,
+                 tx_hash     TEXT,
+                 created_at  INTEGER NOT NULL,
+                 updated_at  INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS outbox_status ON outbox (status, id);",
+        )
+        .map_err(|e| anyhow!("Failed to initialize outbox schema: {}", e))?;
+
+        // Columnas añadidas después de la primera versión del esquema
+        Self::ensure_column(&conn, "key_id", "TEXT NOT NULL DEFAULT ''")?;
+        Self::ensure_column(&conn, "key_version", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::ensure_column(&conn, "duplicate_of", "INTEGER")?;
+        Self::ensure_unique_readings(&conn)?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Un dispositivo no manda dos lecturas con el mismo timestamp: la segunda es una
+    /// repetición. Los duplicados que ya hubiera en un outbox anterior se marcan con
+    /// `duplicate_of` (quedan fuera del índice) y los pendientes pasan a `failed`.
+    fn ensure_unique_readings(conn: &Connection) -> Result<()> {
+        let exists = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'outbox_device_timestamp'")?
+            .exists([])?;
+        if exists {
+            return Ok(());
+        }
+
+        // Se conserva la enviada on-chain si la hay, si no la primera en llegar
+        let marked = conn.execute(
+            "UPDATE outbox SET duplicate_of = (
+                 SELECT keep.id FROM outbox keep
+                 WHERE keep.device_id = outbox.device_id AND keep.timestamp = outbox.timestamp
+                 ORDER BY keep.status = 'submitted' DESC, keep.id
+                 LIMIT 1
+             )
+             WHERE duplicate_of IS NULL AND id != (
+                 SELECT keep.id FROM outbox keep
+                 WHERE keep.device_id = outbox.device_id AND keep.timestamp = outbox.timestamp
+                 ORDER BY keep.status = 'submitted' DESC, keep.id
+                 LIMIT 1
+             )",
+            [],
+        )
+        .map_err(|e| anyhow!("Failed to mark duplicate outbox readings: {}", e))?;
+
+        if marked > 0 {
+            conn.execute(
+                "UPDATE outbox SET status = 'failed', last_error = 'Duplicate of #' || duplicate_of, updated_at = ?1
+                 WHERE duplicate_of IS NOT NULL AND status = 'pending'",
+                params![unix_now() as i64],
+            )?;
+            warn!("🔁 Marked {} duplicate outbox readings; they will not be submitted", marked);
+        }
+
+        conn.execute_batch(
+            "DROP INDEX IF EXISTS outbox_reading;
+             CREATE UNIQUE INDEX outbox_device_timestamp ON outbox (device_id, timestamp) WHERE duplicate_of IS NULL;",
+        )
+        .map_err(|e| anyhow!("Failed to create outbox unique index: {}", e))?;
+
+        Ok(())
+    }
+
+    fn ensure_column(conn: &Connection, name: &str, definition: &str) -> Result<()> {
+        let exists = conn
+            .prepare("SELECT 1 FROM pragma_table_info('outbox') WHERE name = ?1")?
+            .exists(params![name])?;
+
+        if !exists {
+            conn.execute_batch(&format!("ALTER TABLE outbox ADD COLUMN {} {}", name, definition))
+                .map_err(|e| anyhow!("Failed to migrate outbox ({}): {}", name, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Outbox lock poisoned"))
+    }
+
+    /// Guarda una lectura como `pending` y devuelve su id
+    pub fn enqueue(&self, reading: &OutboxReading) -> Result<i64> {
+        let conn = self.conn()?;
+        Self::insert(&conn, reading, unix_now())
+    }
+
+    /// Guarda varias lecturas en una transacción: o todas o ninguna.
+    /// Si alguna ya estaba, falla con `DuplicateReading`.
+    pub fn enqueue_all(&self, readings: &[OutboxReading]) -> Result<Vec<i64>> {
+        let now = unix_now();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let ids = readings
+            .iter()
+            .map(|reading| Self::insert(&tx, reading, now))
+            .collect::<Result<Vec<_>>>()?;
+
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    fn insert(conn: &Connection, reading: &OutboxReading, now: u64) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO outbox (device_id, ciphertext, nonce, key_id, key_version, signature, timestamp, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+            params![
+                reading.device_id,
+                reading.ciphertext,
+                reading.nonce,
+                reading.key_id,
+                reading.key_version,
+                reading.signature,
+                reading.timestamp as i64,
+                now as i64,
+            ],
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(err, _) if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE => {
+                DuplicateReading { device_id: reading.device_id.clone(), timestamp: reading.timestamp }.into()
+            }
+            e => anyhow::Error::from(e),
+        })?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Hasta `limit` lecturas pendientes, en orden de llegada
+    pub fn pending(&self, limit: usize) -> Result<Vec<OutboxEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT * FROM outbox WHERE status = 'pending' ORDER BY id LIMIT ?1",
+        )?;
+        let entries = stmt
+            .query_map(params![limit as i64], Self::entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    pub fn mark_submitted(&self, id: i64, tx_hash: &str) -> Result<()> {
+        self.conn()?.execute(
+            "UPDATE outbox SET status = 'submitted', tx_hash = ?2, attempts = attempts + 1,
+                    last_error = NULL, updated_at = ?3
+             WHERE id = ?1",
+            params![id, tx_hash, unix_now() as i64],
+        )?;
+
+        Ok(())
+    }
+
+    /// Registra un intento fallido. Al llegar a `max_attempts` la lectura pasa a `failed`.
+    pub fn record_failure(&self, id: i64, error: &str, max_attempts: u32) -> Result<OutboxStatus> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE outbox SET attempts = attempts + 1, last_error = ?2, updated_at = ?4,
+                    status = CASE WHEN attempts + 1 >= ?3 THEN 'failed' ELSE status END
+             WHERE id = ?1",
+            params![id, error, max_attempts, unix_now() as i64],
+        )?;
+
+        let status: String = conn.query_row(
+            "SELECT status FROM outbox WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+
+        OutboxStatus::parse(&status)
+    }
+
+    /// Pasa una lectura a `failed` sin más reintentos (el contrato nunca la aceptará)
+    pub fn mark_failed(&self, id: i64, error: &str) -> Result<()> {
+        self.conn()?.execute(
+            "UPDATE outbox SET status = 'failed', attempts = attempts + 1, last_error = ?2, updated_at = ?3
+             WHERE id = ?1",
+            params![id, error, unix_now() as i64],
+        )?;
+
+        Ok(())
+    }
+
+    /// Lista entradas (opcionalmente filtradas por estado), más recientes primero
+    pub fn list(&self, status: Option<OutboxStatus>, limit: usize) -> Result<Vec<OutboxEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT * FROM outbox WHERE (?1 IS NULL OR status = ?1) ORDER BY id DESC LIMIT ?2",
+        )?;
+        let entries = stmt
+            .query_map(
+                params![status.map(|s| s.as_str()), limit as i64],
+                Self::entry_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    /// Número de entradas por estado
+    pub fn count(&self, status: OutboxStatus) -> Result<u64> {
+        let count: i64 = self.conn()?.query_row(
+            "SELECT COUNT(*) FROM outbox WHERE status = ?1",
+            params![status.as_str()],
+            |row| row.get(0),
+        )?;
+
+        Ok(count as u64)
+    }
+
+    fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<OutboxEntry> {
+        let status: String = row.get("status")?;
+        let status = OutboxStatus::parse(&status).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+        })?;
+
+        Ok(OutboxEntry {
+            id: row.get("id")?,
+            reading: OutboxReading {
+                device_id: row.get("device_id")?,
+                ciphertext: row.get("ciphertext")?,
+                nonce: row.get("nonce")?,
+                key_id: row.get("key_id")?,
+                key_version: row.get("key_version")?,
+                signature: row.get("signature")?,
+                timestamp: row.get::<_, i64>("timestamp")? as u64,
+            },
+            status,
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            tx_hash: row.get("tx_hash")?,
+            created_at: row.get::<_, i64>("created_at")? as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(device_id: &str) -> OutboxReading {
+        OutboxReading {
+            device_id: device_id.to_string(),
+            ciphertext: vec![1, 2, 3],
+            nonce: vec![0; 12],
+            key_id: "0011223344556677".to_string(),
+            key_version: 1,
+            signature: vec![9; 32],
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_pending_in_arrival_order() {
+        let outbox = Outbox::open_in_memory().unwrap();
+        let first = outbox.enqueue(&reading("ESP32-001")).unwrap();
+        outbox.enqueue(&reading("ESP32-002")).unwrap();
+
+        let pending = outbox.pending(10).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].id, first);
+        assert_eq!(pending[0].reading.device_id, "ESP32-001");
+        assert_eq!(pending[0].reading.nonce.len(), 12);
+
+        outbox.mark_submitted(first, "0xabc").unwrap();
+        let pending = outbox.pending(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].reading.device_id, "ESP32-002");
+        assert_eq!(outbox.count(OutboxStatus::Submitted).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_enqueue_all() {
+        let outbox = Outbox::open_in_memory().unwrap();
+        let mut later = reading("AIR-042");
+        later.timestamp += 30;
+        let ids = outbox.enqueue_all(&[reading("AIR-042"), later]).unwrap();
+
+        assert_eq!(ids.len(), 2);
+        assert_eq!(outbox.pending(10).unwrap().iter().map(|e| e.id).collect::<Vec<_>>(), ids);
+    }
+
+    #[test]
+    fn test_rejects_replayed_readings() {
+        let outbox = Outbox::open_in_memory().unwrap();
+        outbox.enqueue(&reading("NODE-7")).unwrap();
+
+        let err = outbox.enqueue(&reading("NODE-7")).unwrap_err();
+        assert!(err.is::<DuplicateReading>());
+
+        // El gateway cifra cada reentrega de una lectura en claro con otro nonce
+        let mut reencrypted = reading("NODE-7");
+        reencrypted.nonce = vec![2; 12];
+        assert!(outbox.enqueue(&reencrypted).unwrap_err().is::<DuplicateReading>());
+
+        // Ni siquiera dentro de un lote: no entra ninguna
+        let mut fresh = reading("NODE-7");
+        fresh.timestamp += 30;
+        assert!(outbox.enqueue_all(&[fresh.clone(), reading("NODE-7")]).unwrap_err().is::<DuplicateReading>());
+        assert_eq!(outbox.count(OutboxStatus::Pending).unwrap(), 1);
+
+        // También después de enviada
+        let id = outbox.pending(1).unwrap()[0].id;
+        outbox.mark_submitted(id, "0xabc").unwrap();
+        assert!(outbox.enqueue(&reading("NODE-7")).is_err());
+        outbox.enqueue(&fresh).unwrap();
+    }
+
+    #[test]
+    fn test_migration_marks_existing_duplicates() {
+        // Outbox de antes del índice único, con una lectura repetida dos veces
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE outbox (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 device_id   TEXT    NOT NULL,
+                 ciphertext  BLOB    NOT NULL,
+                 nonce       BLOB    NOT NULL,
+                 signature   BLOB    NOT NULL,
+                 timestamp   INTEGER NOT NULL,
+                 status      TEXT    NOT NULL DEFAULT 'pending',
+                 attempts    INTEGER NOT NULL DEFAULT 0,
+                 last_error  TEXT,
+                 tx_hash     TEXT,
+                 created_at  INTEGER NOT NULL,
+                 updated_at  INTEGER NOT NULL
+             );
+             INSERT INTO outbox (device_id, ciphertext, nonce, signature, timestamp, status, created_at, updated_at) VALUES
+                 ('NODE-7', x'01', x'01', x'01', 100, 'pending',   1, 1),
+                 ('NODE-7', x'02', x'02', x'02', 100, 'submitted', 2, 2),
+                 ('NODE-7', x'03', x'03', x'03', 100, 'pending',   3, 3),
+                 ('NODE-7', x'04', x'04', x'04', 130, 'pending',   4, 4);",
+        )
+        .unwrap();
+
+        let outbox = Outbox::init(conn).unwrap();
+
+        // Se queda la ya enviada; las otras copias no se envían
+        let pending: Vec<i64> = outbox.pending(10).unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(pending, vec![4]);
+        let failed = outbox.list(Some(OutboxStatus::Failed), 10).unwrap();
+        assert_eq!(failed.len(), 2);
+        assert!(failed.iter().all(|e| e.last_error.as_deref() == Some("Duplicate of #2")));
+
+        // A partir de aquí el índice rechaza la repetición
+        let mut replay = reading("NODE-7");
+        replay.timestamp = 100;
+        assert!(outbox.enqueue(&replay).unwrap_err().is::<DuplicateReading>());
+    }
+
+    #[test]
+    fn test_failed_after_max_attempts() {
+        let outbox = Outbox::open_in_memory().unwrap();
+        let id = outbox.enqueue(&reading("ESP32-001")).unwrap();
+
+        assert_eq!(outbox.record_failure(id, "rpc down", 2).unwrap(), OutboxStatus::Pending);
+        assert_eq!(outbox.record_failure(id, "rpc down", 2).unwrap(), OutboxStatus::Failed);
+        assert!(outbox.pending(10).unwrap().is_empty());
+
+        let failed = outbox.list(Some(OutboxStatus::Failed), 10).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 2);
+        assert_eq!(failed[0].last_error.as_deref(), Some("rpc down"));
+
+        // Sin reintentos
+        let id = outbox.enqueue(&reading("ESP32-002")).unwrap();
+        outbox.mark_failed(id, "not registered on-chain").unwrap();
+        assert!(outbox.pending(10).unwrap().is_empty());
+        assert_eq!(outbox.count(OutboxStatus::Failed).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("bae-outbox-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let outbox = Outbox::open(&path).unwrap();
+            outbox.enqueue(&reading("ESP32-001")).unwrap();
+        }
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.count(OutboxStatus::Pending).unwrap(), 1);
+
+        drop(outbox);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
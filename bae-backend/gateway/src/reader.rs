@@ -0,0 +1,257 @@
+use anyhow::{Result, anyhow};
+use ethers::prelude::*;
+use std::sync::Arc;
+
+use bae_core::reading::SensorReading;
+
+use crate::blockchain_sender::BaeSensorRegistry;
+use crate::crypto::{CryptoHandler, EncryptedPayload};
+use crate::key_registry::KeyRegistry;
+
+/// Acceso de sólo lectura al contrato (sin wallet): para auditar lecturas
+pub struct RegistryReader {
+    contract: BaeSensorRegistry<Provider<Http>>,
+}
+
+/// Registro tal como está guardado on-chain
+#[derive(Debug, Clone)]
+pub struct OnChainReading {
+    pub index: u64,
+    pub device_id: String,
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub timestamp: u64,
+    pub block_number: u64,
+}
+
+/// Evento `SensorDataSubmitted` con su posición en la cadena
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmittedEvent {
+    pub index: u64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+}
+
+/// Tupla `SensorData` que devuelven `getReading`, `getLatestReading`,
+/// `getDeviceReading` y `getLatestReadingForDevice`
+pub(crate) type SensorDataTuple = (String, Bytes, Bytes, Bytes, U256, U256);
+
+impl OnChainReading {
+    pub(crate) fn from_contract(index: u64, data: SensorDataTuple) -> Self {
+        let (device_id, ciphertext, nonce, signature, timestamp, block_number) = data;
+        Self {
+            index,
+            device_id,
+            ciphertext: ciphertext.to_vec(),
+            nonce: nonce.to_vec(),
+            signature: signature.to_vec(),
+            timestamp: timestamp.as_u64(),
+            block_number: block_number.as_u64(),
+        }
+    }
+
+    /// Desencripta con la clave del dispositivo. Las lecturas anteriores al AAD
+    /// (device_id + timestamp) se encriptaron sin datos asociados.
+    pub fn decrypt(&self, keys: &KeyRegistry) -> Result<SensorReading> {
+        let handler = keys.handler_for(&self.device_id)?;
+        let payload = EncryptedPayload {
+            ciphertext: self.ciphertext.clone(),
+            nonce: self.nonce.clone(),
+            key_id: String::new(),
+            key_version: 0,
+        };
+
+        let aad = CryptoHandler::reading_aad(&self.device_id, self.timestamp);
+        let (format, body) = handler
+            .open(&payload, &aad)
+            .or_else(|e| handler.open(&payload, &[]).map_err(|_| e))?;
+
+        SensorReading::decode_body(format, &body, &self.device_id)
+    }
+}
+
+// Consultas por dispositivo, compartidas con `BlockchainSender`
+
+pub(crate) async fn device_reading_count<M: Middleware>(contract: &BaeSensorRegistry<M>, device_id: &str) -> Result<u64> {
+    let count = contract
+        .get_device_reading_count(device_id.to_string())
+        .call()
+        .await
+        .map_err(|e| anyhow!("Failed to get reading count for {}: {}", device_id, e))?;
+
+    Ok(count.as_u64())
+}
+
+pub(crate) async fn device_reading<M: Middleware>(contract: &BaeSensorRegistry<M>, device_id: &str, i: u64) -> Result<OnChainReading> {
+    let (data, index) = contract
+        .get_device_reading(device_id.to_string(), U256::from(i))
+        .call()
+        .await
+        .map_err(|e| anyhow!("Failed to get reading {} of {}: {}", i, device_id, e))?;
+
+    Ok(OnChainReading::from_contract(index.as_u64(), data))
+}
+
+pub(crate) async fn latest_reading_for_device<M: Middleware>(contract: &BaeSensorRegistry<M>, device_id: &str) -> Result<OnChainReading> {
+    let (data, index) = contract
+        .get_latest_reading_for_device(device_id.to_string())
+        .call()
+        .await
+        .map_err(|e| anyhow!("Failed to get latest reading of {}: {}", device_id, e))?;
+
+    Ok(OnChainReading::from_contract(index.as_u64(), data))
+}
+
+impl RegistryReader {
+    pub fn new(rpc_url: &str, contract_address: &str) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(rpc_url)
+            .map_err(|e| anyhow!("Failed to connect to RPC: {}", e))?;
+        let address: Address = contract_address
+            .parse()
+            .map_err(|e| anyhow!("Invalid contract address format: {:?}", e))?;
+
+        Ok(Self { contract: BaeSensorRegistry::new(address, Arc::new(provider)) })
+    }
+
+    pub async fn reading_count(&self) -> Result<u64> {
+        let count = self.contract
+            .get_reading_count()
+            .call()
+            .await
+            .map_err(|e| anyhow!("Failed to get reading count: {}", e))?;
+
+        Ok(count.as_u64())
+    }
+
+    pub async fn reading(&self, index: u64) -> Result<OnChainReading> {
+        let data = self.contract
+            .get_reading(U256::from(index))
+            .call()
+            .await
+            .map_err(|e| anyhow!("Failed to get reading {}: {}", index, e))?;
+
+        Ok(OnChainReading::from_contract(index, data))
+    }
+
+    /// Lectura `index` según el estado del bloque `block` (consistente con sus eventos)
+    pub async fn reading_at(&self, index: u64, block: u64) -> Result<OnChainReading> {
+        let data = self.contract
+            .get_reading(U256::from(index))
+            .block(BlockId::from(U64::from(block)))
+            .call()
+            .await
+            .map_err(|e| anyhow!("Failed to get reading {} at block {}: {}", index, block, e))?;
+
+        Ok(OnChainReading::from_contract(index, data))
+    }
+
+    /// Eventos `SensorDataSubmitted` emitidos en los bloques `from..=to`
+    pub async fn submitted_events(&self, from: u64, to: u64) -> Result<Vec<SubmittedEvent>> {
+        let events = self.contract
+            .sensor_data_submitted_filter()
+            .from_block(from)
+            .to_block(to)
+            .query_with_meta()
+            .await
+            .map_err(|e| anyhow!("Failed to get events for blocks {}..={}: {}", from, to, e))?;
+
+        Ok(events
+            .into_iter()
+            .map(|(event, meta)| SubmittedEvent {
+                index: event.index.as_u64(),
+                block_number: meta.block_number.as_u64(),
+                block_hash: format!("{:?}", meta.block_hash),
+                tx_hash: format!("{:?}", meta.transaction_hash),
+            })
+            .collect())
+    }
+
+    /// Último bloque de la cadena
+    pub async fn head_block(&self) -> Result<u64> {
+        let head = self.contract
+            .client()
+            .get_block_number()
+            .await
+            .map_err(|e| anyhow!("Failed to get block number: {}", e))?;
+
+        Ok(head.as_u64())
+    }
+
+    /// Hash del bloque `number` (`None` si todavía no existe)
+    pub async fn block_hash(&self, number: u64) -> Result<Option<String>> {
+        let block = self.contract
+            .client()
+            .get_block(number)
+            .await
+            .map_err(|e| anyhow!("Failed to get block {}: {}", number, e))?;
+
+        Ok(block.and_then(|block| block.hash).map(|hash| format!("{:?}", hash)))
+    }
+
+    pub async fn get_device_reading_count(&self, device_id: &str) -> Result<u64> {
+        device_reading_count(&self.contract, device_id).await
+    }
+
+    /// `i`-ésima lectura del dispositivo (0 es la más antigua)
+    pub async fn get_device_reading(&self, device_id: &str, i: u64) -> Result<OnChainReading> {
+        device_reading(&self.contract, device_id, i).await
+    }
+
+    pub async fn get_latest_reading_for_device(&self, device_id: &str) -> Result<OnChainReading> {
+        latest_reading_for_device(&self.contract, device_id).await
+    }
+
+    pub async fn latest_reading(&self) -> Result<OnChainReading> {
+        // El contrato no devuelve el índice: es el último
+        let count = self.reading_count().await?;
+        if count == 0 {
+            return Err(anyhow!("No readings stored yet"));
+        }
+
+        let data = self.contract
+            .get_latest_reading()
+            .call()
+            .await
+            .map_err(|e| anyhow!("Failed to get latest reading: {}", e))?;
+
+        Ok(OnChainReading::from_contract(count - 1, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::EnvelopeFormat;
+
+    fn on_chain(device_id: &str, timestamp: u64, payload: EncryptedPayload) -> OnChainReading {
+        OnChainReading {
+            index: 0,
+            device_id: device_id.to_string(),
+            ciphertext: payload.ciphertext,
+            nonce: payload.nonce,
+            signature: Vec::new(),
+            timestamp,
+            block_number: 1,
+        }
+    }
+
+    #[test]
+    fn test_decrypts_current_and_pre_aad_readings() {
+        let keys = KeyRegistry::new().with_master_key(&"ab".repeat(32)).unwrap();
+        let handler = keys.handler_for("ESP32-001").unwrap();
+        let reading = SensorReading::climate("ESP32-001", 21.0, 40.0, 1_700_000_000);
+
+        let aad = CryptoHandler::reading_aad("ESP32-001", reading.timestamp);
+        let current = handler.seal_reading(&reading, EnvelopeFormat::Binary, &aad).unwrap();
+        assert_eq!(on_chain("ESP32-001", reading.timestamp, current.clone()).decrypt(&keys).unwrap(), reading);
+
+        // Lecturas anteriores al AAD
+        let old = handler.encrypt(&reading, &[]).unwrap();
+        assert_eq!(on_chain("ESP32-001", reading.timestamp, old).decrypt(&keys).unwrap(), reading);
+
+        // Metadatos on-chain cambiados: no descifra
+        assert!(on_chain("ESP32-001", reading.timestamp + 1, current).decrypt(&keys).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::{Result, anyhow};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+use bae_core::reading::{self, SensorReading};
+
+use crate::crypto::{CryptoHandler, EncryptedPayload};
+use crate::envelope::{EnvelopeFormat, EnvelopeHeader};
+use crate::outbox::OutboxReading;
+
+/// Tamaño máximo del ciphertext de una lectura sellada (cabecera incluida)
+pub const MAX_SEALED_CIPHERTEXT_LEN: usize = 4096;
+
+/// Lectura cifrada y firmada por el propio dispositivo (modo extremo a extremo).
+/// El gateway no tiene su clave AES: comprueba la firma y los metadatos en claro
+/// y la reenvía al contrato sin tocarla. En MQTT viaja como JSON con los bytes en hex:
+/// `{"device_id": ..., "timestamp": ..., "ciphertext": "e101...", "nonce": ..., "key_id": ..., "key_version": 1, "signature": ...}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "WireSealed", into = "WireSealed")]
+pub struct SealedReading {
+    pub device_id: String,
+    pub timestamp: u64,
+    pub payload: EncryptedPayload,
+    /// Firma Ed25519 del dispositivo (mismo mensaje que firma el gateway, ver `CryptoHandler::sign`)
+    pub signature: Vec<u8>,
+}
+
+/// Rechazos de una lectura sellada por su origen (no por estar mal formada)
+#[derive(Debug, thiserror::Error)]
+pub enum SealedRejection {
+    #[error("Device {0} has no public key for end-to-end readings in the registry")]
+    UnknownDevice(String),
+    #[error("Invalid device signature for {0}")]
+    InvalidSignature(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireSealed {
+    device_id: String,
+    timestamp: u64,
+    ciphertext: String,
+    nonce: String,
+    key_id: String,
+    key_version: u32,
+    signature: String,
+}
+
+impl TryFrom<WireSealed> for SealedReading {
+    type Error = String;
+
+    fn try_from(wire: WireSealed) -> Result<Self, Self::Error> {
+        let bytes = |field: &str, value: &str| hex::decode(value).map_err(|_| format!("'{}' is not valid hex", field));
+
+        Ok(Self {
+            device_id: wire.device_id,
+            timestamp: wire.timestamp,
+            payload: EncryptedPayload {
+                ciphertext: bytes("ciphertext", &wire.ciphertext)?,
+                nonce: bytes("nonce", &wire.nonce)?,
+                key_id: wire.key_id,
+                key_version: wire.key_version,
+            },
+            signature: bytes("signature", &wire.signature)?,
+        })
+    }
+}
+
+impl From<SealedReading> for WireSealed {
+    fn from(sealed: SealedReading) -> Self {
+        Self {
+            device_id: sealed.device_id,
+            timestamp: sealed.timestamp,
+            ciphertext: hex::encode(&sealed.payload.ciphertext),
+            nonce: hex::encode(&sealed.payload.nonce),
+            key_id: sealed.payload.key_id,
+            key_version: sealed.payload.key_version,
+            signature: hex::encode(&sealed.signature),
+        }
+    }
+}
+
+impl SealedReading {
+    /// Cifra y firma una lectura en el dispositivo. `crypto` lleva la clave AES
+    /// propia del dispositivo y su clave de firma.
+    pub fn seal(reading: &SensorReading, crypto: &CryptoHandler, format: EnvelopeFormat) -> Result<Self> {
+        // Sin cabecera el gateway no podría comprobar con qué clave se cifró
+        if format == EnvelopeFormat::Legacy {
+            return Err(anyhow!("End-to-end readings need an envelope format (json or binary)"));
+        }
+
+        let aad = CryptoHandler::reading_aad(&reading.device_id, reading.timestamp);
+        let payload = crypto.seal_reading(reading, format, &aad)?;
+        let signature = crypto.sign(&payload, &reading.device_id, reading.timestamp)?;
+
+        Ok(Self { device_id: reading.device_id.clone(), timestamp: reading.timestamp, payload, signature })
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| anyhow!("Serialization failed: {}", e))
+    }
+
+    pub fn from_json(payload: &[u8]) -> Result<Self> {
+        serde_json::from_slice(payload).map_err(|e| anyhow!("Deserialization failed: {}", e))
+    }
+
+    /// Valida lo que el gateway puede ver sin descifrar: device_id y timestamp, forma
+    /// del envelope (su cabecera en claro debe declarar la misma clave) y la firma
+    /// del dispositivo, que autentica todo lo anterior. La firma no prueba que el mensaje
+    /// sea nuevo: las repeticiones las rechaza el outbox (`DuplicateReading`).
+    pub fn verify(&self, public_key: &VerifyingKey, now: u64) -> Result<()> {
+        reading::validate_metadata(&self.device_id, self.timestamp, now)?;
+
+        if self.payload.nonce.len() != 12 {
+            return Err(anyhow!("Invalid nonce length: expected 12 bytes, got {}", self.payload.nonce.len()));
+        }
+
+        if self.payload.ciphertext.len() > MAX_SEALED_CIPHERTEXT_LEN {
+            return Err(anyhow!(
+                "Ciphertext too large: {} bytes (max {})",
+                self.payload.ciphertext.len(),
+                MAX_SEALED_CIPHERTEXT_LEN
+            ));
+        }
+
+        let (header, _) = EnvelopeHeader::parse(&self.payload.ciphertext)
+            .ok_or_else(|| anyhow!("End-to-end reading is not an envelope"))?;
+        if hex::encode(header.key_id) != self.payload.key_id {
+            return Err(anyhow!(
+                "Key mismatch: envelope uses key {}, reading says {}",
+                hex::encode(header.key_id),
+                self.payload.key_id
+            ));
+        }
+
+        if !CryptoHandler::verify(public_key, &self.payload, &self.device_id, self.timestamp, &self.signature) {
+            return Err(SealedRejection::InvalidSignature(self.device_id.clone()).into());
+        }
+
+        Ok(())
+    }
+
+    /// Entrada del outbox con el ciphertext y la firma del dispositivo tal cual
+    pub fn into_outbox(self) -> OutboxReading {
+        OutboxReading {
+            device_id: self.device_id,
+            ciphertext: self.payload.ciphertext,
+            nonce: self.payload.nonce,
+            key_id: self.payload.key_id,
+            key_version: self.payload.key_version,
+            signature: self.signature,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bae_core::body::BodyFormat;
+    use ed25519_dalek::SigningKey;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn device() -> (CryptoHandler, VerifyingKey) {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = signing_key.verifying_key();
+        (CryptoHandler::from_key(&[9; 32]).with_signer(Some(signing_key)), public_key)
+    }
+
+    #[test]
+    fn test_sealed_reading_roundtrip() {
+        let (crypto, public_key) = device();
+        let reading = SensorReading::climate("ESP32-001", 23.5, 61.25, NOW);
+        let sealed = SealedReading::seal(&reading, &crypto, EnvelopeFormat::Binary).unwrap();
+
+        let received = SealedReading::from_json(&sealed.to_json().unwrap()).unwrap();
+        assert_eq!(received, sealed);
+        received.verify(&public_key, NOW).unwrap();
+
+        // Sólo quien tiene la clave del dispositivo lo descifra
+        let aad = CryptoHandler::reading_aad("ESP32-001", NOW);
+        let (format, body) = crypto.open(&received.payload, &aad).unwrap();
+        assert_eq!(format, BodyFormat::Binary);
+        assert_eq!(SensorReading::decode_body(format, &body, "ESP32-001").unwrap(), reading);
+
+        assert!(SealedReading::seal(&reading, &crypto, EnvelopeFormat::Legacy).is_err());
+    }
+
+    #[test]
+    fn test_rejects_tampered_or_foreign_readings() {
+        let (crypto, public_key) = device();
+        let sealed = SealedReading::seal(&SensorReading::climate("ESP32-001", 23.5, 61.25, NOW), &crypto, EnvelopeFormat::Json).unwrap();
+
+        // La firma cubre device_id y timestamp
+        let moved = SealedReading { device_id: "ESP32-002".to_string(), ..sealed.clone() };
+        let err = moved.verify(&public_key, NOW).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(SealedRejection::InvalidSignature(_))));
+
+        let mut flipped = sealed.clone();
+        *flipped.payload.ciphertext.last_mut().unwrap() ^= 1;
+        assert!(flipped.verify(&public_key, NOW).is_err());
+
+        let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(sealed.verify(&other_key, NOW).is_err());
+
+        // Metadatos en claro
+        assert!(sealed.verify(&public_key, NOW + 2 * reading::MAX_AGE_SECS).is_err());
+        let relabeled = SealedReading {
+            payload: EncryptedPayload { key_id: "00".repeat(8), ..sealed.payload.clone() },
+            ..sealed.clone()
+        };
+        assert!(relabeled.verify(&public_key, NOW).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Valor secreto (clave privada, clave de encriptación...). `Debug` y `Display`
+/// nunca muestran el contenido; hay que pedirlo explícitamente con `expose`.
+#[derive(Clone)]
+pub struct Secret {
+    value: String,
+    source: SecretSource,
+}
+
+/// De dónde se cargó un secreto (se puede loguear: no contiene el valor)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// Fichero indicado en `<NOMBRE>_FILE` (p. ej. Docker/Kubernetes secrets)
+    File(PathBuf),
+    /// Variable de entorno `<NOMBRE>`
+    Env,
+    /// Entrada del almacén local `SECRETS_PATH`
+    Store(PathBuf),
+    /// Introducido por consola
+    Prompt,
+    /// Valor de desarrollo generado o fijo (`--insecure-dev`)
+    InsecureDev,
+}
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    pub fn source(&self) -> &SecretSource {
+        &self.source
+    }
+
+    /// Secreto introducido por consola
+    pub fn prompted(value: String) -> Self {
+        Self { value, source: SecretSource::Prompt }
+    }
+
+    /// Secreto de desarrollo; sólo para `--insecure-dev`
+    pub fn insecure_dev(value: impl Into<String>) -> Self {
+        Self { value: value.into(), source: SecretSource::InsecureDev }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(<redacted>, {})", self.source)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Env => f.write_str("environment"),
+            Self::Store(path) => write!(f, "secret store {}", path.display()),
+            Self::Prompt => f.write_str("prompt"),
+            Self::InsecureDev => f.write_str("insecure dev default"),
+        }
+    }
+}
+
+/// Capa de carga de secretos. Para un nombre `X` busca, por orden:
+/// 1. el fichero de la variable `X_FILE`,
+/// 2. la variable de entorno `X`,
+/// 3. la entrada `X` del almacén local JSON de `SECRETS_PATH` (`{ "X": "..." }`).
+pub struct SecretStore {
+    store_path: Option<PathBuf>,
+    store: HashMap<String, String>,
+}
+
+impl SecretStore {
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("SECRETS_PATH") {
+            Ok(path) => Self::with_store(path),
+            Err(_) => Ok(Self { store_path: None, store: HashMap::new() }),
+        }
+    }
+
+    pub fn with_store(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read secret store {}: {}", path.display(), e))?;
+        // No se incluye el error de serde: podría citar parte del contenido
+        let store = serde_json::from_str(&content)
+            .map_err(|_| anyhow!("Invalid secret store {}: expected a JSON object of strings", path.display()))?;
+
+        Ok(Self { store_path: Some(path.to_path_buf()), store })
+    }
+
+    /// Busca el secreto `name`; `None` si no está configurado en ningún origen
+    pub fn get(&self, name: &str) -> Result<Option<Secret>> {
+        let file_var = format!("{}_FILE", name);
+        if let Ok(path) = std::env::var(&file_var) {
+            let path = PathBuf::from(path);
+            let value = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read {} from {}: {}", name, path.display(), e))?;
+            return Self::non_empty(name, value, SecretSource::File(path));
+        }
+
+        if let Ok(value) = std::env::var(name) {
+            return Self::non_empty(name, value, SecretSource::Env);
+        }
+
+        match (self.store.get(name), &self.store_path) {
+            (Some(value), Some(path)) => Self::non_empty(name, value.clone(), SecretSource::Store(path.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Como `get`, pero es un error que falte
+    pub fn require(&self, name: &str) -> Result<Secret> {
+        self.get(name)?.ok_or_else(|| {
+            anyhow!("{} is not set (use {}_FILE, the {} variable or SECRETS_PATH)", name, name, name)
+        })
+    }
+
+    fn non_empty(name: &str, value: String, source: SecretSource) -> Result<Option<Secret>> {
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return Err(anyhow!("{} from {} is empty", name, source));
+        }
+        Ok(Some(Secret { value, source }))
+    }
+}
+
+/// Crea `path` sólo legible por el dueño (0600 en Unix) desde el primer byte, sin la
+/// ventana de un `chmod` posterior. Falla si ya existe: no hereda permisos de otro fichero.
+pub fn create_private_file(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path).map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_is_never_formatted() {
+        let secret = Secret { value: "0xdeadbeef".to_string(), source: SecretSource::Env };
+        assert_eq!(secret.to_string(), "<redacted>");
+        assert!(!format!("{:?}", secret).contains("deadbeef"));
+        assert_eq!(secret.expose(), "0xdeadbeef");
+    }
+
+    #[test]
+    fn test_store_lookup() {
+        let path = std::env::temp_dir().join(format!("bae-secrets-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"BAE_TEST_STORE_SECRET": " abc \n", "BAE_TEST_EMPTY": ""}"#).unwrap();
+        let store = SecretStore::with_store(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let secret = store.require("BAE_TEST_STORE_SECRET").unwrap();
+        assert_eq!(secret.expose(), "abc");
+        assert_eq!(secret.source(), &SecretSource::Store(path));
+
+        assert!(store.get("BAE_TEST_MISSING").unwrap().is_none());
+        assert!(store.require("BAE_TEST_MISSING").is_err());
+        assert!(store.get("BAE_TEST_EMPTY").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{Result, anyhow};
+use ethers::signers::{LocalWallet, Signer};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::secrets::{self, Secret, SecretStore};
+
+/// Ruta por defecto del keystore JSON cifrado de la wallet
+pub const DEFAULT_WALLET_KEYSTORE: &str = "bae-wallet.json";
+
+/// Ruta del keystore: `WALLET_KEYSTORE` o la indicada explícitamente
+pub fn keystore_path(explicit: Option<PathBuf>) -> PathBuf {
+    explicit
+        .or_else(|| std::env::var("WALLET_KEYSTORE").ok().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_WALLET_KEYSTORE))
+}
+
+/// Wallet del gateway: el keystore cifrado de `WALLET_KEYSTORE` si está configurado,
+/// o la clave en claro del secreto `PRIVATE_KEY`
+pub fn load(secrets: &SecretStore) -> Result<LocalWallet> {
+    if let Ok(path) = std::env::var("WALLET_KEYSTORE") {
+        let wallet = from_keystore(Path::new(&path), &password(secrets, false)?)?;
+        info!("🔑 Wallet loaded from keystore {}", path);
+        return Ok(wallet);
+    }
+
+    let private_key = secrets.require("PRIVATE_KEY")
+        .map_err(|e| anyhow!("{} (or set WALLET_KEYSTORE)", e))?;
+    info!("🔑 PRIVATE_KEY loaded from {}", private_key.source());
+    from_private_key(&private_key)
+}
+
+pub fn from_private_key(private_key: &Secret) -> Result<LocalWallet> {
+    private_key
+        .expose()
+        .trim_start_matches("0x")
+        .parse()
+        // Sin el detalle del error: podría citar parte de la clave
+        .map_err(|_| anyhow!("Invalid private key format (expected 32-byte hex)"))
+}
+
+pub fn from_keystore(path: &Path, password: &Secret) -> Result<LocalWallet> {
+    LocalWallet::decrypt_keystore(path, password.expose())
+        .map_err(|e| anyhow!("Failed to decrypt wallet keystore {}: {}", path.display(), e))
+}
+
+/// Cifra la clave en un keystore JSON (formato Ethereum v3, scrypt) en `path`
+pub fn save_keystore(path: &Path, private_key: &[u8], password: &Secret, overwrite: bool) -> Result<LocalWallet> {
+    if path.exists() && !overwrite {
+        return Err(anyhow!("{} already exists (use --force to overwrite)", path.display()));
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid keystore path {}", path.display()))?;
+
+    // El fichero se crea ya privado; `encrypt_keystore` sólo lo trunca y escribe,
+    // así que nunca queda legible por otros con los permisos por defecto
+    if path.exists() {
+        std::fs::remove_file(path)
+            .map_err(|e| anyhow!("Failed to replace wallet keystore {}: {}", path.display(), e))?;
+    }
+    secrets::create_private_file(path)?;
+
+    LocalWallet::encrypt_keystore(dir, &mut rand::thread_rng(), private_key, password.expose(), Some(name))
+        .map(|(wallet, _)| wallet)
+        .map_err(|e| {
+            let _ = std::fs::remove_file(path);
+            anyhow!("Failed to write wallet keystore {}: {}", path.display(), e)
+        })
+}
+
+/// Contraseña del keystore: secreto `WALLET_PASSWORD` (fichero, entorno o almacén)
+/// o, si hay terminal, se pide por consola. `confirm` la pide dos veces.
+pub fn password(secrets: &SecretStore, confirm: bool) -> Result<Secret> {
+    if let Some(password) = secrets.get("WALLET_PASSWORD")? {
+        return Ok(password);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "WALLET_PASSWORD is not set (use WALLET_PASSWORD_FILE, WALLET_PASSWORD or SECRETS_PATH) and there is no terminal to prompt"
+        ));
+    }
+
+    let password = prompt_hidden("Wallet keystore password: ")?;
+    if confirm && prompt_hidden("Repeat password: ")? != password {
+        return Err(anyhow!("Passwords do not match"));
+    }
+    if password.is_empty() {
+        return Err(anyhow!("Password must not be empty"));
+    }
+
+    Ok(Secret::prompted(password))
+}
+
+/// Lee una línea de la consola sin eco (vía `stty`, disponible en cualquier Unix)
+pub fn prompt_hidden(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+
+    let echo_off = set_echo(false);
+    let mut line = String::new();
+    let read = std::io::stdin().lock().read_line(&mut line);
+    if echo_off {
+        set_echo(true);
+        eprintln!();
+    }
+    read?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn set_echo(enabled: bool) -> bool {
+    std::process::Command::new("stty")
+        .arg(if enabled { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Dirección de la wallet en formato checksum
+pub fn address(wallet: &LocalWallet) -> String {
+    ethers::utils::to_checksum(&wallet.address(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let path = std::env::temp_dir().join(format!("bae-wallet-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key = [7u8; 32];
+        let password = Secret::prompted("correct horse".to_string());
+
+        let saved = save_keystore(&path, &key, &password, false).unwrap();
+        assert!(save_keystore(&path, &key, &password, false).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let loaded = from_keystore(&path, &password).unwrap();
+        assert!(from_keystore(&path, &Secret::prompted("wrong".to_string())).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.address(), saved.address());
+        let from_hex = from_private_key(&Secret::prompted(hex::encode(key))).unwrap();
+        assert_eq!(address(&loaded), address(&from_hex));
+    }
+}
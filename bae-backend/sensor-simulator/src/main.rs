@@ -1,62 +1,185 @@
 use anyhow::Result;
 use rand::Rng;
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
-use serde::{Serialize, Deserialize};
+use rumqttc::{AsyncClient, Event, Packet, QoS};
+use rumqttc::v5;
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{info, error, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SensorReading {
-    device_id: String,
-    temperature: f32,
-    humidity: f32,
-    timestamp: u64,
+use bae_core::reading::SensorReading;
+use gateway::crypto::CryptoHandler;
+use gateway::envelope::EnvelopeFormat;
+use gateway::key_registry::decode_key;
+use gateway::ingest::{DEFAULT_PROTOBUF_TOPIC, DEFAULT_SEALED_TOPIC, SENSOR_TOPIC};
+use gateway::mqtt_client::{MqttConfig, MqttProtocol};
+use gateway::sealed::SealedReading;
+
+/// Codificación de las lecturas publicadas (`PAYLOAD_FORMAT`); cada una tiene su topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PayloadFormat {
+    Json,
+    Protobuf,
+    /// Cifrada y firmada aquí, como haría el firmware (modo extremo a extremo)
+    Sealed,
 }
 
-struct SensorSimulator {
-    device_id: String,
-    client: AsyncClient,
-    connected: std::sync::Arc<std::sync::atomic::AtomicBool>,
+impl PayloadFormat {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "protobuf" | "proto" => Ok(Self::Protobuf),
+            "sealed" => Ok(Self::Sealed),
+            other => Err(anyhow::anyhow!("Invalid PAYLOAD_FORMAT '{}' (expected json, protobuf or sealed)", other)),
+        }
+    }
+
+    /// Topic del dispositivo, a partir del filtro al que se suscribe el gateway por defecto
+    fn topic(&self, device_id: &str) -> String {
+        let filter = match self {
+            Self::Json => SENSOR_TOPIC,
+            Self::Protobuf => DEFAULT_PROTOBUF_TOPIC,
+            Self::Sealed => DEFAULT_SEALED_TOPIC,
+        };
+        filter.replace('+', device_id)
+    }
+
+    /// `content-type` con el que se publica en MQTT 5
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Protobuf => "application/x-protobuf",
+            Self::Sealed => "application/vnd.bae.sealed+json",
+        }
+    }
 }
 
-impl SensorSimulator {
-    fn new(device_id: String, mqtt_broker: &str, mqtt_port: u16) -> Result<Self> {
-        let mut mqttoptions = MqttOptions::new(
-            format!("sensor-{}", device_id),
-            mqtt_broker,
-            mqtt_port,
-        );
-        mqttoptions.set_keep_alive(std::time::Duration::from_secs(30));
-        mqttoptions.set_clean_session(true); // Importante para evitar mensajes antiguos
-
-        let (client, mut eventloop) = AsyncClient::new(mqttoptions, 10);
-        let connected = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-        let connected_clone = connected.clone();
-
-        // Mejorado: manejo de conexión más robusto
-        tokio::spawn(async move {
-            loop {
-                match eventloop.poll().await {
-                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
-                        info!("📡 Connected to MQTT broker");
-                        connected_clone.store(true, std::sync::atomic::Ordering::Relaxed);
-                    }
-                    Ok(Event::Incoming(Packet::Disconnect)) => {
-                        warn!("⚠️  Disconnected from MQTT broker");
-                        connected_clone.store(false, std::sync::atomic::Ordering::Relaxed);
+/// Cliente MQTT según `MQTT_PROTOCOL`; en MQTT 5 cada mensaje lleva su `content-type`
+enum Publisher {
+    V4(AsyncClient),
+    V5(v5::AsyncClient),
+}
+
+impl Publisher {
+    /// Conecta al broker y lanza el loop de eventos, que mantiene `connected` al día
+    fn connect(mqtt_config: &MqttConfig, client_id: &str, connected: Arc<AtomicBool>) -> Result<Self> {
+        let keep_alive = std::time::Duration::from_secs(30);
+
+        match mqtt_config.protocol {
+            MqttProtocol::V4 => {
+                let mut mqttoptions = mqtt_config.mqtt_options(client_id)?;
+                mqttoptions.set_keep_alive(keep_alive);
+                mqttoptions.set_clean_session(true); // Importante para evitar mensajes antiguos
+
+                let (client, mut eventloop) = AsyncClient::new(mqttoptions, 10);
+                tokio::spawn(async move {
+                    loop {
+                        let event = match eventloop.poll().await {
+                            Ok(Event::Incoming(Packet::ConnAck(_))) => Ok(Some(true)),
+                            Ok(Event::Incoming(Packet::Disconnect)) => Ok(Some(false)),
+                            Ok(_) => Ok(None),
+                            Err(e) => Err(e.to_string()),
+                        };
+                        track_connection(&connected, event).await;
                     }
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("❌ MQTT error: {}", e);
-                        connected_clone.store(false, std::sync::atomic::Ordering::Relaxed);
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                });
+                Ok(Self::V4(client))
+            }
+            MqttProtocol::V5 => {
+                use v5::mqttbytes::v5::Packet;
+
+                let mut mqttoptions = mqtt_config.mqtt_options_v5(client_id)?;
+                mqttoptions.set_keep_alive(keep_alive);
+                mqttoptions.set_clean_start(true);
+
+                let (client, mut eventloop) = v5::AsyncClient::new(mqttoptions, 10);
+                tokio::spawn(async move {
+                    loop {
+                        let event = match eventloop.poll().await {
+                            Ok(v5::Event::Incoming(Packet::ConnAck(_))) => Ok(Some(true)),
+                            Ok(v5::Event::Incoming(Packet::Disconnect(_))) => Ok(Some(false)),
+                            Ok(_) => Ok(None),
+                            Err(e) => Err(e.to_string()),
+                        };
+                        track_connection(&connected, event).await;
                     }
-                }
+                });
+                Ok(Self::V5(client))
+            }
+        }
+    }
+
+    async fn publish(&self, topic: String, content_type: &str, payload: Vec<u8>) -> Result<()> {
+        match self {
+            Self::V4(client) => client.publish(topic, QoS::AtLeastOnce, false, payload).await?,
+            Self::V5(client) => {
+                let properties = PublishProperties {
+                    content_type: Some(content_type.to_string()),
+                    ..Default::default()
+                };
+                client
+                    .publish_with_properties(topic, v5::mqttbytes::QoS::AtLeastOnce, false, payload, properties)
+                    .await?
             }
-        });
+        }
+        Ok(())
+    }
+}
+
+/// Refleja en `connected` un evento del broker: `Some(true)` al conectar, `Some(false)` al desconectar
+async fn track_connection(connected: &AtomicBool, event: Result<Option<bool>, String>) {
+    match event {
+        Ok(Some(true)) => {
+            info!("📡 Connected to MQTT broker");
+            connected.store(true, Ordering::Relaxed);
+        }
+        Ok(Some(false)) => {
+            warn!("⚠️  Disconnected from MQTT broker");
+            connected.store(false, Ordering::Relaxed);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("❌ MQTT error: {}", e);
+            connected.store(false, Ordering::Relaxed);
+            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        }
+    }
+}
+
+/// Claves propias del dispositivo para el modo extremo a extremo: la AES con la que
+/// cifra (`DEVICE_KEY`, la misma que tendrán los lectores en su keystore) y la
+/// semilla Ed25519 con la que firma (`DEVICE_SIGNING_KEY`). Devuelve también la
+/// clave pública en hex.
+fn device_crypto() -> Result<(CryptoHandler, String)> {
+    let env_var = |name: &str| -> Result<String> {
+        std::env::var(name).map_err(|_| anyhow::anyhow!("PAYLOAD_FORMAT=sealed requires {}", name))
+    };
+    let key = decode_key(&env_var("DEVICE_KEY")?).map_err(|e| anyhow::anyhow!("Invalid DEVICE_KEY: {}", e))?;
+    let crypto = CryptoHandler::from_key(&key).with_signing_key(&env_var("DEVICE_SIGNING_KEY")?)?;
+    let public_key = crypto.public_key_hex().unwrap_or_default();
+
+    Ok((crypto, public_key))
+}
+
+struct SensorSimulator {
+    device_id: String,
+    format: PayloadFormat,
+    /// Sólo en `PayloadFormat::Sealed`
+    crypto: Option<CryptoHandler>,
+    client: Publisher,
+    connected: Arc<AtomicBool>,
+}
+
+impl SensorSimulator {
+    fn new(device_id: String, format: PayloadFormat, crypto: Option<CryptoHandler>, mqtt_config: &MqttConfig) -> Result<Self> {
+        let connected = Arc::new(AtomicBool::new(false));
+        let client = Publisher::connect(mqtt_config, &format!("sensor-{}", device_id), connected.clone())?;
 
         Ok(Self { 
             device_id, 
+            format,
+            crypto,
             client,
             connected,
         })
@@ -79,7 +202,7 @@ impl SensorSimulator {
         };
 
         // Humedad con límites realistas
-        let humidity = (55.0_f32 + rng.gen_range(-10.0..10.0)).clamp(20.0, 80.0);
+        let humidity = (55.0_f64 + rng.gen_range(-10.0..10.0)).clamp(20.0, 80.0);
         
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -94,35 +217,36 @@ impl SensorSimulator {
             );
         }
 
-        SensorReading {
-            device_id: self.device_id.clone(),
-            temperature,
-            humidity,
-            timestamp,
+        SensorReading::climate(self.device_id.clone(), temperature, humidity, timestamp)
+    }
+
+    fn encode(&self, reading: &SensorReading) -> Result<Vec<u8>> {
+        match (self.format, &self.crypto) {
+            (PayloadFormat::Json, _) => reading.to_json(),
+            (PayloadFormat::Protobuf, _) => Ok(reading.to_protobuf()),
+            (PayloadFormat::Sealed, Some(crypto)) => SealedReading::seal(reading, crypto, EnvelopeFormat::Binary)?.to_json(),
+            (PayloadFormat::Sealed, None) => Err(anyhow::anyhow!("Sealed payloads need the device keys")),
         }
     }
 
     async fn publish_reading(&mut self) -> Result<()> {
         // Verificar conexión antes de publicar
-        if !self.connected.load(std::sync::atomic::Ordering::Relaxed) {
+        if !self.connected.load(Ordering::Relaxed) {
             warn!("⚠️  Not connected to MQTT broker, skipping publish");
             return Ok(());
         }
 
         let reading = self.generate_reading();
         
-        info!(
-            "📊 Device {}: T={:.1}°C, H={:.1}%",
-            reading.device_id, reading.temperature, reading.humidity
-        );
+        info!("📊 Device {}: {}", reading.device_id, reading.summary());
 
-        let topic = format!("bae/sensors/{}/data", self.device_id);
-        let payload = serde_json::to_vec(&reading)?;
+        let topic = self.format.topic(&self.device_id);
+        let payload = self.encode(&reading)?;
 
         // Mejorado: timeout para publicación
         match tokio::time::timeout(
             tokio::time::Duration::from_secs(5),
-            self.client.publish(topic, QoS::AtLeastOnce, false, payload)
+            self.client.publish(topic, self.format.content_type(), payload)
         ).await {
             Ok(Ok(_)) => {
                 info!("✅ Published successfully");
@@ -130,7 +254,7 @@ impl SensorSimulator {
             }
             Ok(Err(e)) => {
                 error!("❌ Failed to publish: {}", e);
-                Err(e.into())
+                Err(e)
             }
             Err(_) => {
                 error!("❌ Publish timeout");
@@ -139,6 +263,7 @@ impl SensorSimulator {
         }
     }
 
+    #[allow(clippy::manual_is_multiple_of)]
     async fn run(&mut self, interval_secs: u64) -> Result<()> {
         info!("🚀 Starting sensor simulator for device: {}", self.device_id);
         info!("📡 Publishing every {} seconds", interval_secs);
@@ -150,13 +275,13 @@ impl SensorSimulator {
         // Esperar conexión inicial
         info!("⏳ Waiting for MQTT connection...");
         for _ in 0..30 {
-            if self.connected.load(std::sync::atomic::Ordering::Relaxed) {
+            if self.connected.load(Ordering::Relaxed) {
                 break;
             }
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
         }
 
-        if !self.connected.load(std::sync::atomic::Ordering::Relaxed) {
+        if !self.connected.load(Ordering::Relaxed) {
             error!("❌ Failed to connect to MQTT broker after 30 seconds");
             return Err(anyhow::anyhow!("MQTT connection timeout"));
         }
@@ -199,25 +324,34 @@ async fn main() -> Result<()> {
         .init();
 
     // Leer configuración
-    let mqtt_broker = std::env::var("MQTT_BROKER")
-        .unwrap_or_else(|_| "broker.hivemq.com".to_string());
-    let mqtt_port = std::env::var("MQTT_PORT")
-        .unwrap_or_else(|_| "1883".to_string())
-        .parse()
-        .unwrap_or(1883);
+    let mqtt_config = MqttConfig::from_env()?;
     let device_id = std::env::var("DEVICE_ID")
         .unwrap_or_else(|_| "ESP32-001".to_string());
     let interval = std::env::var("INTERVAL_SECS")
         .unwrap_or_else(|_| "30".to_string())
         .parse()
         .unwrap_or(30);
+    let format = match std::env::var("PAYLOAD_FORMAT") {
+        Ok(format) => PayloadFormat::parse(&format)?,
+        Err(_) => PayloadFormat::Json,
+    };
 
     info!("⚙️  Configuration:");
-    info!("   MQTT Broker: {}:{}", mqtt_broker, mqtt_port);
+    info!("   MQTT Broker: {}", mqtt_config.describe());
     info!("   Device ID: {}", device_id);
     info!("   Interval: {}s", interval);
+    info!("   Payload: {:?} ({})", format, format.topic(&device_id));
+    let crypto = match format {
+        PayloadFormat::Sealed => {
+            let (crypto, public_key) = device_crypto()?;
+            // Se registra en el gateway (`gateway devices add --public-key`) para que acepte sus lecturas
+            info!("   Device public key (Ed25519): {}", public_key);
+            Some(crypto)
+        }
+        _ => None,
+    };
     info!("");
 
-    let mut simulator = SensorSimulator::new(device_id, &mqtt_broker, mqtt_port)?;
+    let mut simulator = SensorSimulator::new(device_id, format, crypto, &mqtt_config)?;
     simulator.run(interval).await
 }
\ No newline at end of file